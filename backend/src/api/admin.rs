@@ -0,0 +1,542 @@
+//! Admin API for moderating squads
+//!
+//! Mounted under `/api/vN/admin` behind the dashboard session cookie and CSRF
+//! header. Every action is written to the audit log, which is also queryable
+//! and exportable from here.
+
+use std::sync::Arc;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::api::error::{ApiError, ErrorCode};
+use crate::api::extract::ValidJson;
+use crate::api::squads::purge_squad;
+use crate::api::version::VersionUsage;
+use crate::models::{
+    CreateWebhookRequest, CreateWebhookResponse, WebhookDeliveriesResponse, WebhookEventKind,
+    WebhooksResponse,
+};
+use crate::services::audit::{AuditContext, AuditEntry, AuditQuery, ChainVerification};
+use crate::services::snapshot::{Snapshot, SnapshotFormat, SNAPSHOT_VERSION};
+use crate::AppState;
+
+/// Largest snapshot accepted by the import endpoint
+pub const MAX_SNAPSHOT_BYTES: usize = 64 * 1024 * 1024;
+
+/// Response after rotating a join code
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RotateJoinCodeResponse {
+    pub squad_id: Uuid,
+    pub join_code: String,
+}
+
+/// Response carrying how many records an action removed
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RemovedCountResponse {
+    pub removed: usize,
+}
+
+/// Query for snapshot export
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SnapshotExportQuery {
+    #[serde(default)]
+    pub format: SnapshotFormat,
+}
+
+/// Counts of what a snapshot import loaded
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SnapshotImportResponse {
+    pub squads: usize,
+    pub locations: usize,
+    pub sessions: usize,
+}
+
+/// DELETE /api/v1/admin/squads/:squad_id - Delete a squad and everything tied to it
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/squads/{squad_id}",
+    operation_id = "admin_delete_squad",
+    tag = "admin",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 204, description = "Squad and everything tied to it deleted"),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn delete_squad(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .squad_manager
+        .write()
+        .await
+        .force_delete_squad(&ctx, &squad_id)?;
+    purge_squad(&state, &ctx, &squad_id).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/v1/admin/squads/:squad_id/members/:member_id - Kick a member
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/squads/{squad_id}/members/{member_id}",
+    tag = "admin",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("member_id" = Uuid, Path, description = "Member ID"),
+    ),
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 204, description = "Member removed and their sessions revoked"),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+        (status = 404, description = "Squad or member not found", body = ApiError),
+        (status = 409, description = "The leader cannot be kicked", body = ApiError),
+    )
+)]
+pub async fn kick_member(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Path((squad_id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let member = state
+        .squad_manager
+        .write()
+        .await
+        .kick_member(&ctx, &squad_id, &member_id)?;
+
+    state.location_store.write().await.remove_member(&squad_id, &member_id);
+    state.session_store.revoke_member(&ctx, &member_id);
+    state.webhooks.emit(
+        squad_id,
+        WebhookEventKind::MemberLeft,
+        serde_json::json!({
+            "member_id": member_id,
+            "display_name": member.display_name,
+            "reason": "kicked",
+        }),
+        Utc::now(),
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/v1/admin/members/:member_id/revoke-sessions - Revoke all of a member's API keys
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/members/{member_id}/revoke-sessions",
+    tag = "admin",
+    params(("member_id" = Uuid, Path, description = "Member ID")),
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "Number of sessions revoked", body = RemovedCountResponse),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+    )
+)]
+pub async fn revoke_member_sessions(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Path(member_id): Path<Uuid>,
+) -> Json<RemovedCountResponse> {
+    let removed = state.session_store.revoke_member(&ctx, &member_id);
+    Json(RemovedCountResponse { removed })
+}
+
+/// POST /api/v1/admin/squads/:squad_id/rotate-code - Issue a new join code
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/squads/{squad_id}/rotate-code",
+    tag = "admin",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "New join code", body = RotateJoinCodeResponse),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn rotate_join_code(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<RotateJoinCodeResponse>, ApiError> {
+    let join_code = state
+        .squad_manager
+        .write()
+        .await
+        .rotate_join_code(&ctx, &squad_id)?;
+
+    Ok(Json(RotateJoinCodeResponse { squad_id, join_code }))
+}
+
+/// POST /api/v1/admin/squads/:squad_id/webhooks - Register a webhook for a squad
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/squads/{squad_id}/webhooks",
+    operation_id = "admin_create_webhook",
+    tag = "admin",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = CreateWebhookRequest,
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "Webhook registered; the secret is not shown again", body = CreateWebhookResponse),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 409, description = "Squad already has the maximum number of webhooks", body = ApiError),
+        (status = 422, description = "Invalid URL, or its host is not a public address", body = ApiError),
+    )
+)]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<CreateWebhookRequest>,
+) -> Result<Json<CreateWebhookResponse>, ApiError> {
+    require_squad(&state, &squad_id).await?;
+    state.webhooks.check_destination(&req.url).await?;
+    let (webhook, secret) = state.webhooks.create(&ctx, squad_id, req.url, req.events)?;
+    Ok(Json(CreateWebhookResponse { webhook, secret }))
+}
+
+/// GET /api/v1/admin/squads/:squad_id/webhooks - List a squad's webhooks
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/squads/{squad_id}/webhooks",
+    operation_id = "admin_list_webhooks",
+    tag = "admin",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("admin_session" = [])),
+    responses(
+        (status = 200, description = "Registered webhooks, oldest first", body = WebhooksResponse),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<WebhooksResponse>, ApiError> {
+    require_squad(&state, &squad_id).await?;
+    Ok(Json(WebhooksResponse {
+        squad_id,
+        webhooks: state.webhooks.list(&squad_id),
+    }))
+}
+
+/// DELETE /api/v1/admin/squads/:squad_id/webhooks/:webhook_id - Remove a webhook
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/squads/{squad_id}/webhooks/{webhook_id}",
+    operation_id = "admin_delete_webhook",
+    tag = "admin",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("webhook_id" = Uuid, Path, description = "Webhook ID"),
+    ),
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 204, description = "Webhook removed; queued deliveries are dropped"),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+        (status = 404, description = "Webhook not found", body = ApiError),
+    )
+)]
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Path((squad_id, webhook_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    state.webhooks.delete(&ctx, &squad_id, &webhook_id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/v1/admin/squads/:squad_id/webhooks/:webhook_id/deliveries - A webhook's delivery log
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/squads/{squad_id}/webhooks/{webhook_id}/deliveries",
+    operation_id = "admin_list_webhook_deliveries",
+    tag = "admin",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("webhook_id" = Uuid, Path, description = "Webhook ID"),
+    ),
+    security(("admin_session" = [])),
+    responses(
+        (status = 200, description = "Recent deliveries, newest first", body = WebhookDeliveriesResponse),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 404, description = "Webhook not found", body = ApiError),
+    )
+)]
+pub async fn list_webhook_deliveries(
+    State(state): State<Arc<AppState>>,
+    Path((squad_id, webhook_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<WebhookDeliveriesResponse>, ApiError> {
+    Ok(Json(WebhookDeliveriesResponse {
+        webhook_id,
+        deliveries: state.webhooks.deliveries(&squad_id, &webhook_id)?,
+    }))
+}
+
+/// POST /api/v1/admin/locations/expire-stale - Drop all stale locations now
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/locations/expire-stale",
+    tag = "admin",
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "Number of locations dropped", body = RemovedCountResponse),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+    )
+)]
+pub async fn expire_stale_locations(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+) -> Json<RemovedCountResponse> {
+    let removed = state.location_store.write().await.expire_stale();
+
+    state.audit.record(
+        &ctx,
+        "location.expire_stale",
+        None,
+        None,
+        serde_json::json!({ "removed": removed }),
+    );
+    Json(RemovedCountResponse { removed })
+}
+
+/// GET /api/v1/admin/audit - Query audit entries (newest `limit`, oldest first)
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    tag = "audit",
+    params(AuditQuery),
+    security(("admin_session" = [])),
+    responses(
+        (status = 200, description = "Matching entries, oldest first", body = Vec<AuditEntry>),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 500, description = "Audit log file could not be read", body = ApiError),
+    )
+)]
+pub async fn query_audit(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    let entries = state.audit.query(&query).map_err(audit_read_error)?;
+    Ok(Json(entries))
+}
+
+/// GET /api/v1/admin/audit/export - Download matching entries as JSON Lines
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit/export",
+    tag = "audit",
+    params(AuditQuery),
+    security(("admin_session" = [])),
+    responses(
+        (status = 200, description = "Matching entries as JSON Lines", body = String, content_type = "application/x-ndjson"),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 500, description = "Audit log file could not be read", body = ApiError),
+    )
+)]
+pub async fn export_audit(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let export = state.audit.export_jsonl(&query).map_err(audit_read_error)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"squadz-audit.jsonl\""),
+        ],
+        export,
+    ))
+}
+
+/// GET /api/v1/admin/audit/verify - Re-check the audit hash chain
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit/verify",
+    tag = "audit",
+    security(("admin_session" = [])),
+    responses(
+        (status = 200, description = "Hash chain check result", body = ChainVerification),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 500, description = "Audit log file could not be read", body = ApiError),
+    )
+)]
+pub async fn verify_audit(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ChainVerification>, ApiError> {
+    let verification = state.audit.verify().map_err(audit_read_error)?;
+    Ok(Json(verification))
+}
+
+fn audit_read_error(e: std::io::Error) -> ApiError {
+    error!("Failed to read the audit log: {}", e);
+    ApiError::internal("Failed to read the audit log")
+}
+
+/// GET /api/v1/admin/api-versions - Requests per API version since startup
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/api-versions",
+    tag = "admin",
+    security(("admin_session" = [])),
+    responses(
+        (status = 200, description = "Usage and deprecation schedule of every version", body = Vec<VersionUsage>),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+    )
+)]
+pub async fn api_version_usage(State(state): State<Arc<AppState>>) -> Json<Vec<VersionUsage>> {
+    Json(state.api_versions.usage())
+}
+
+/// GET /api/v1/admin/snapshot - Download the full server state
+///
+/// `?format=binary` returns MessagePack instead of JSON.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/snapshot",
+    tag = "admin",
+    params(SnapshotExportQuery),
+    security(("admin_session" = [])),
+    responses(
+        (status = 200, description = "Full server state", content(
+            (Snapshot = "application/json"),
+            (Vec<u8> = "application/msgpack"),
+        )),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+    )
+)]
+pub async fn export_snapshot(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Query(query): Query<SnapshotExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let snapshot = Snapshot::capture(
+        &*state.squad_manager.read().await,
+        &*state.location_store.read().await,
+        &state.session_store,
+        &state.webhooks,
+        &state.sos,
+        &state.messages,
+    );
+    let body = snapshot
+        .encode(query.format)
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+
+    state.audit.record(
+        &ctx,
+        "snapshot.export",
+        None,
+        None,
+        serde_json::json!({
+            "format": query.format.extension(),
+            "squads": snapshot.squads.len(),
+            "sessions": snapshot.sessions.len(),
+        }),
+    );
+
+    let disposition = format!(
+        "attachment; filename=\"squadz-snapshot.{}\"",
+        query.format.extension()
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
+/// POST /api/v1/admin/snapshot - Replace the full server state
+///
+/// Accepts JSON or MessagePack; every existing squad, location and member
+/// session is discarded.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/snapshot",
+    tag = "admin",
+    request_body(content(
+        (Snapshot = "application/json"),
+        (Vec<u8> = "application/msgpack"),
+    )),
+    security(("admin_session" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "State replaced", body = SnapshotImportResponse),
+        (status = 400, description = "Snapshot could not be decoded", body = ApiError),
+        (status = 401, description = "Dashboard login required", body = ApiError),
+        (status = 403, description = "Missing or invalid CSRF token", body = ApiError),
+    )
+)]
+pub async fn import_snapshot(
+    State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
+    body: Bytes,
+) -> Result<Json<SnapshotImportResponse>, ApiError> {
+    let snapshot = Snapshot::decode(&body).map_err(|e| {
+        ApiError::new(ErrorCode::InvalidSnapshot, e.to_string())
+            .with_details(serde_json::json!({ "supported_version": SNAPSHOT_VERSION }))
+    })?;
+    let response = SnapshotImportResponse {
+        squads: snapshot.squads.len(),
+        locations: snapshot.locations.len(),
+        sessions: snapshot.sessions.len(),
+    };
+    let created_at = snapshot.created_at;
+
+    {
+        let mut manager = state.squad_manager.write().await;
+        let mut store = state.location_store.write().await;
+        snapshot.restore(
+            &mut manager,
+            &mut store,
+            &state.session_store,
+            &state.webhooks,
+            &state.sos,
+            &state.messages,
+        );
+    }
+
+    state.audit.record(
+        &ctx,
+        "snapshot.import",
+        None,
+        None,
+        serde_json::json!({
+            "created_at": created_at,
+            "squads": response.squads,
+            "locations": response.locations,
+            "sessions": response.sessions,
+        }),
+    );
+    Ok(Json(response))
+}
+
+async fn require_squad(state: &AppState, squad_id: &Uuid) -> Result<(), ApiError> {
+    match state.squad_manager.read().await.get_squad(squad_id) {
+        Some(_) => Ok(()),
+        None => Err(ApiError::new(ErrorCode::SquadNotFound, "Squad not found")),
+    }
+}
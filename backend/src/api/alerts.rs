@@ -0,0 +1,58 @@
+//! Separation alert endpoints (v2, leader only)
+//!
+//! Leaders poll the active alerts and event log here; each event is also sent
+//! to the leader on the squad stream as it is raised or cleared. A stream
+//! client that reconnects catches up with `GET .../alerts?since=`.
+
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::api::error::ApiError;
+use crate::api::squads::require_leader;
+use crate::models::AlertsResponse;
+use crate::services::auth::AuthenticatedMember;
+use crate::AppState;
+
+/// Query for the alert log
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AlertsQuery {
+    /// Only events after this time
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Active separation alerts and recent alert events (leader only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/alerts",
+    tag = "alerts",
+    params(("squad_id" = Uuid, Path, description = "Squad ID"), AlertsQuery),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Active alerts and the event log", body = AlertsResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn get_alerts(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+    Query(query): Query<AlertsQuery>,
+) -> Result<Json<AlertsResponse>, ApiError> {
+    require_leader(&state, &auth.session, &squad_id).await?;
+    let (active, events) = state.alerts.squad_alerts(&squad_id, query.since);
+    Ok(Json(AlertsResponse {
+        squad_id,
+        active,
+        events,
+    }))
+}
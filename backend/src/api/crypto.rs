@@ -2,18 +2,18 @@
 //!
 //! Uses AES-256-GCM which is compatible with WebCrypto API
 
-use axum::{
-    http::StatusCode,
-    Json,
-};
+use axum::Json;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::error::{ApiError, ErrorCode};
 
 /// Shared secret for demo (in production, use proper key exchange)
 const DEMO_SECRET: &[u8; 32] = b"omni-core-lite-demo-key-32bytes!";
 
 /// Request with encrypted payload
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct EncryptedRequest {
     /// Base64-encoded nonce (12 bytes for AES-GCM)
     pub nonce: String,
@@ -22,7 +22,7 @@ pub struct EncryptedRequest {
 }
 
 /// Response with encrypted payload
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct EncryptedResponse {
     /// Base64-encoded nonce
     pub nonce: String,
@@ -33,7 +33,7 @@ pub struct EncryptedResponse {
 }
 
 /// Health check for crypto endpoint
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CryptoHealthResponse {
     pub status: String,
     pub algorithm: String,
@@ -41,6 +41,12 @@ pub struct CryptoHealthResponse {
 }
 
 /// GET /api/v1/crypto/health - Check crypto endpoint availability
+#[utoipa::path(
+    get,
+    path = "/api/v1/crypto/health",
+    tag = "crypto",
+    responses((status = 200, description = "Crypto endpoints available", body = CryptoHealthResponse))
+)]
 pub async fn crypto_health() -> Json<CryptoHealthResponse> {
     Json(CryptoHealthResponse {
         status: "ok".to_string(),
@@ -50,9 +56,19 @@ pub async fn crypto_health() -> Json<CryptoHealthResponse> {
 }
 
 /// POST /api/v1/crypto/echo - Decrypt, echo back encrypted
+#[utoipa::path(
+    post,
+    path = "/api/v1/crypto/echo",
+    tag = "crypto",
+    request_body = EncryptedRequest,
+    responses(
+        (status = 200, description = "Echoed message, re-encrypted", body = EncryptedResponse),
+        (status = 400, description = "Bad nonce or ciphertext, or decryption failed", body = ApiError),
+    )
+)]
 pub async fn crypto_echo(
     Json(req): Json<EncryptedRequest>,
-) -> Result<Json<EncryptedResponse>, (StatusCode, String)> {
+) -> Result<Json<EncryptedResponse>, ApiError> {
     use aes_gcm::{
         aead::{Aead, KeyInit},
         Aes256Gcm, Nonce,
@@ -63,23 +79,23 @@ pub async fn crypto_echo(
     // Decode nonce
     let nonce_bytes: [u8; 12] = b64
         .decode(&req.nonce)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid nonce: {}", e)))?
+        .map_err(|e| ApiError::new(ErrorCode::InvalidNonce, format!("Invalid nonce: {}", e)))?
         .try_into()
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Nonce must be 12 bytes".to_string()))?;
+        .map_err(|_| ApiError::new(ErrorCode::InvalidNonce, "Nonce must be 12 bytes"))?;
 
     // Decode ciphertext
     let ciphertext = b64
         .decode(&req.ciphertext)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid ciphertext: {}", e)))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidCiphertext, format!("Invalid ciphertext: {}", e)))?;
 
     // Decrypt
     let cipher = Aes256Gcm::new_from_slice(DEMO_SECRET)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Invalid key".to_string()))?;
+        .map_err(|_| ApiError::internal("Invalid key"))?;
     
     let nonce = Nonce::from_slice(&nonce_bytes);
     let plaintext = cipher
         .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Decryption failed".to_string()))?;
+        .map_err(|_| ApiError::new(ErrorCode::DecryptionFailed, "Decryption failed"))?;
 
     let plaintext_str = String::from_utf8_lossy(&plaintext).to_string();
 
@@ -91,7 +107,7 @@ pub async fn crypto_echo(
     let response_plaintext = format!("Echo: {}", plaintext_str);
     let new_ciphertext = cipher
         .encrypt(new_nonce, response_plaintext.as_bytes())
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Encryption failed".to_string()))?;
+        .map_err(|_| ApiError::internal("Encryption failed"))?;
 
     Ok(Json(EncryptedResponse {
         nonce: b64.encode(new_nonce_bytes),
@@ -101,14 +117,21 @@ pub async fn crypto_echo(
 }
 
 /// POST /api/v1/crypto/encrypt - Encrypt plaintext (for testing)
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct EncryptRequest {
     pub plaintext: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/crypto/encrypt",
+    tag = "crypto",
+    request_body = EncryptRequest,
+    responses((status = 200, description = "Encrypted plaintext", body = EncryptedResponse))
+)]
 pub async fn crypto_encrypt(
     Json(req): Json<EncryptRequest>,
-) -> Result<Json<EncryptedResponse>, (StatusCode, String)> {
+) -> Result<Json<EncryptedResponse>, ApiError> {
     use aes_gcm::{
         aead::{Aead, KeyInit},
         Aes256Gcm, Nonce,
@@ -117,7 +140,7 @@ pub async fn crypto_encrypt(
     let b64 = base64::engine::general_purpose::STANDARD;
 
     let cipher = Aes256Gcm::new_from_slice(DEMO_SECRET)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Invalid key".to_string()))?;
+        .map_err(|_| ApiError::internal("Invalid key"))?;
 
     let mut nonce_bytes = [0u8; 12];
     getrandom::getrandom(&mut nonce_bytes).unwrap();
@@ -125,7 +148,7 @@ pub async fn crypto_encrypt(
 
     let ciphertext = cipher
         .encrypt(nonce, req.plaintext.as_bytes())
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Encryption failed".to_string()))?;
+        .map_err(|_| ApiError::internal("Encryption failed"))?;
 
     Ok(Json(EncryptedResponse {
         nonce: b64.encode(nonce_bytes),
@@ -135,9 +158,19 @@ pub async fn crypto_encrypt(
 }
 
 /// POST /api/v1/crypto/decrypt - Decrypt ciphertext (for testing)
+#[utoipa::path(
+    post,
+    path = "/api/v1/crypto/decrypt",
+    tag = "crypto",
+    request_body = EncryptedRequest,
+    responses(
+        (status = 200, description = "Decrypted plaintext as `{\"plaintext\": ...}`", body = Object),
+        (status = 400, description = "Bad nonce or ciphertext, or decryption failed", body = ApiError),
+    )
+)]
 pub async fn crypto_decrypt(
     Json(req): Json<EncryptedRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     use aes_gcm::{
         aead::{Aead, KeyInit},
         Aes256Gcm, Nonce,
@@ -147,21 +180,21 @@ pub async fn crypto_decrypt(
 
     let nonce_bytes: [u8; 12] = b64
         .decode(&req.nonce)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid nonce: {}", e)))?
+        .map_err(|e| ApiError::new(ErrorCode::InvalidNonce, format!("Invalid nonce: {}", e)))?
         .try_into()
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Nonce must be 12 bytes".to_string()))?;
+        .map_err(|_| ApiError::new(ErrorCode::InvalidNonce, "Nonce must be 12 bytes"))?;
 
     let ciphertext = b64
         .decode(&req.ciphertext)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid ciphertext: {}", e)))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidCiphertext, format!("Invalid ciphertext: {}", e)))?;
 
     let cipher = Aes256Gcm::new_from_slice(DEMO_SECRET)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Invalid key".to_string()))?;
+        .map_err(|_| ApiError::internal("Invalid key"))?;
     
     let nonce = Nonce::from_slice(&nonce_bytes);
     let plaintext = cipher
         .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Decryption failed".to_string()))?;
+        .map_err(|_| ApiError::new(ErrorCode::DecryptionFailed, "Decryption failed"))?;
 
     let plaintext_str = String::from_utf8_lossy(&plaintext).to_string();
 
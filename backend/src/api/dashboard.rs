@@ -1,20 +1,70 @@
 //! Dashboard endpoints for viewing squads and members
 //!
-//! Password-protected admin dashboard with env var support
+//! Admin dashboard behind a POST login form. A successful login sets an
+//! HttpOnly session cookie; logout and other forms are CSRF-protected. The
+//! live map polls `/dashboard/locations` with the same cookie.
 
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::Html,
+    extract::{Form, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Json,
 };
+use chrono::Utc;
 use serde::Deserialize;
 use std::sync::Arc;
 
+use crate::models::SquadLocationsResponse;
+use crate::services::admin_auth::{self, get_cookie, AdminSession, LOGIN_CSRF_COOKIE, SESSION_COOKIE};
 use crate::AppState;
 
+/// Login form submission
 #[derive(Debug, Deserialize)]
-pub struct DashboardQuery {
-    pub password: Option<String>,
+pub struct LoginForm {
+    pub username: String,
+    pub password: String,
+    pub csrf_token: String,
+}
+
+/// Logout form submission
+#[derive(Debug, Deserialize)]
+pub struct LogoutForm {
+    pub csrf_token: String,
+}
+
+/// Build a `Set-Cookie` header value; `max_age_secs = 0` clears the cookie
+fn cookie_header(name: &str, value: &str, max_age_secs: u64, secure: bool) -> HeaderValue {
+    let secure = if secure { "; Secure" } else { "" };
+    HeaderValue::from_str(&format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}",
+        name, value, max_age_secs, secure
+    ))
+    .expect("cookie values are URL-safe")
+}
+
+/// Look up the admin session from the request cookie
+fn current_session(state: &AppState, headers: &HeaderMap) -> Option<AdminSession> {
+    get_cookie(headers, SESSION_COOKIE).and_then(|token| state.admin_auth.validate(token))
+}
+
+/// Render the login page with a fresh login CSRF cookie
+fn login_response(state: &AppState, status: StatusCode, error: Option<&str>) -> Response {
+    let csrf_token = admin_auth::generate_token();
+    let cookie = cookie_header(
+        LOGIN_CSRF_COOKIE,
+        &csrf_token,
+        600,
+        state.config.dashboard_secure_cookie,
+    );
+    (
+        status,
+        [
+            (header::SET_COOKIE, cookie),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+        ],
+        Html(login_page(&csrf_token, error)),
+    )
+        .into_response()
 }
 
 fn html_escape(s: &str) -> String {
@@ -24,16 +74,21 @@ fn html_escape(s: &str) -> String {
         .replace('"', "&quot;")
 }
 
-fn login_page() -> String {
-    r#"<!DOCTYPE html>
+fn login_page(csrf_token: &str, error: Option<&str>) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, html_escape(e)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
 <html lang="en">
 <head>
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
     <title>Squadz Dashboard - Login</title>
     <style>
-        * { margin: 0; padding: 0; box-sizing: border-box; }
-        body { 
+        * {{ margin: 0; padding: 0; box-sizing: border-box; }}
+        body {{ 
             font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
             background: linear-gradient(135deg, #1a1a2e 0%, #16213e 100%);
             color: #eee;
@@ -41,8 +96,8 @@ fn login_page() -> String {
             display: flex;
             align-items: center;
             justify-content: center;
-        }
-        .login-card {
+        }}
+        .login-card {{
             background: #16213e;
             border-radius: 12px;
             padding: 2rem;
@@ -50,10 +105,10 @@ fn login_page() -> String {
             text-align: center;
             max-width: 400px;
             width: 90%;
-        }
-        h1 { color: #4ade80; margin-bottom: 1rem; }
-        p { color: #888; margin-bottom: 1.5rem; }
-        input {
+        }}
+        h1 {{ color: #4ade80; margin-bottom: 1rem; }}
+        p {{ color: #888; margin-bottom: 1.5rem; }}
+        input {{
             width: 100%;
             padding: 0.75rem;
             margin-bottom: 1rem;
@@ -62,8 +117,8 @@ fn login_page() -> String {
             background: #0f3460;
             color: #fff;
             font-size: 1rem;
-        }
-        button {
+        }}
+        button {{
             width: 100%;
             padding: 0.75rem;
             border: none;
@@ -73,36 +128,123 @@ fn login_page() -> String {
             font-weight: bold;
             cursor: pointer;
             font-size: 1rem;
-        }
-        button:hover { background: #22c55e; }
+        }}
+        button:hover {{ background: #22c55e; }}
+        .error {{ color: #f87171; }}
     </style>
 </head>
 <body>
     <div class="login-card">
         <h1>🎯 Squadz Dashboard</h1>
-        <p>Enter password to view squads</p>
-        <form method="GET">
-            <input type="password" name="password" placeholder="Password" autofocus>
+        <p>Sign in to view squads</p>
+        {}
+        <form method="POST" action="/login">
+            <input type="hidden" name="csrf_token" value="{}">
+            <input type="text" name="username" placeholder="Username" autocomplete="username" autofocus required>
+            <input type="password" name="password" placeholder="Password" autocomplete="current-password" required>
             <button type="submit">Login</button>
         </form>
     </div>
 </body>
-</html>"#.to_string()
+</html>"#,
+        error_html,
+        html_escape(csrf_token)
+    )
 }
 
-/// GET / - Dashboard HTML page
-pub async fn dashboard_page(
+/// GET /dashboard/locations - Latest member positions across all squads
+/// (dashboard session required)
+pub async fn dashboard_locations(
     State(state): State<Arc<AppState>>,
-    Query(query): Query<DashboardQuery>,
-) -> Result<Html<String>, (StatusCode, String)> {
-    // Check password from env var or use generated one
-    let expected_password = &state.dashboard_password;
-    let authenticated = query.password.as_deref() == Some(expected_password.as_str());
-    
-    if !authenticated {
-        return Ok(Html(login_page()));
+    headers: HeaderMap,
+) -> Result<Json<Vec<SquadLocationsResponse>>, StatusCode> {
+    current_session(&state, &headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let manager = state.squad_manager.read().await;
+    let store = state.location_store.read().await;
+    let now = Utc::now();
+
+    let squads = manager
+        .list_squads()
+        .into_iter()
+        .map(|squad| SquadLocationsResponse {
+            squad_id: squad.squad_id,
+            squad_name: squad.name.clone(),
+            locations: store.get_squad_locations(&squad.squad_id),
+            updated_at: now,
+        })
+        .collect();
+
+    Ok(Json(squads))
+}
+
+/// POST /login - Check the admin credential and start a dashboard session
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let csrf_ok = get_cookie(&headers, LOGIN_CSRF_COOKIE)
+        .is_some_and(|expected| admin_auth::constant_time_eq(expected, &form.csrf_token));
+    if !csrf_ok {
+        return login_response(&state, StatusCode::FORBIDDEN, Some("Login form expired, please try again"));
+    }
+
+    if state.admin_auth.login_throttled(Utc::now()) {
+        return login_response(
+            &state,
+            StatusCode::TOO_MANY_REQUESTS,
+            Some("Too many failed logins, please try again later"),
+        );
+    }
+
+    // Argon2 is slow by design: verify on a blocking thread
+    let auth = state.admin_auth.clone();
+    let login = tokio::task::spawn_blocking(move || auth.login(&form.username, &form.password));
+    let Ok(Some(session)) = login.await else {
+        return login_response(&state, StatusCode::UNAUTHORIZED, Some("Invalid username or password"));
+    };
+
+    let secure = state.config.dashboard_secure_cookie;
+    let mut response = Redirect::to("/").into_response();
+    let cookies = response.headers_mut();
+    cookies.append(
+        header::SET_COOKIE,
+        cookie_header(SESSION_COOKIE, &session.token, state.admin_auth.session_ttl_secs(), secure),
+    );
+    cookies.append(header::SET_COOKIE, cookie_header(LOGIN_CSRF_COOKIE, "", 0, secure));
+    response
+}
+
+/// POST /logout - End the dashboard session
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(form): Form<LogoutForm>,
+) -> Response {
+    let Some(session) = current_session(&state, &headers) else {
+        return Redirect::to("/").into_response();
+    };
+    if !session.verify_csrf(&form.csrf_token) {
+        return (StatusCode::FORBIDDEN, "Invalid CSRF token").into_response();
     }
 
+    state.admin_auth.logout(&session.token);
+
+    let mut response = Redirect::to("/").into_response();
+    response.headers_mut().append(
+        header::SET_COOKIE,
+        cookie_header(SESSION_COOKIE, "", 0, state.config.dashboard_secure_cookie),
+    );
+    response
+}
+
+/// GET / - Dashboard HTML page
+pub async fn dashboard_page(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let Some(session) = current_session(&state, &headers) else {
+        return login_response(&state, StatusCode::OK, None);
+    };
+
     // Get all squads
     let squad_manager = state.squad_manager.read().await;
     let squads = squad_manager.list_squads();
@@ -115,15 +257,30 @@ pub async fn dashboard_page(
     
     for squad in &squads {
         let members_html: String = squad.members.iter().map(|m| {
+            let kick_button = if m.is_leader {
+                String::new()
+            } else {
+                format!(
+                    r#"<button class="btn-small btn-danger" onclick="adminAction('DELETE', '/api/v1/admin/squads/{}/members/{}', 'Kick this member?')">Kick</button>"#,
+                    squad.squad_id,
+                    m.member_id
+                )
+            };
             format!(
                 r#"<tr>
                     <td>{}</td>
                     <td><code>{}</code></td>
                     <td>{}</td>
+                    <td>
+                        {}
+                        <button class="btn-small" onclick="adminAction('POST', '/api/v1/admin/members/{}/revoke-sessions', 'Revoke all sessions for this member?')">Revoke sessions</button>
+                    </td>
                 </tr>"#,
                 html_escape(&m.display_name),
                 &m.member_id.to_string()[..8],
-                if m.is_leader { "👑 Leader" } else { "Member" }
+                if m.is_leader { "👑 Leader" } else { "Member" },
+                kick_button,
+                m.member_id
             )
         }).collect();
 
@@ -134,9 +291,13 @@ pub async fn dashboard_page(
                 <p><strong>Join Code:</strong> <code class="join-code">{}</code></p>
                 <p><strong>Squad ID:</strong> <code>{}</code></p>
                 <p><strong>Members:</strong> {}</p>
+                <div class="squad-actions">
+                    <button class="btn-small" onclick="adminAction('POST', '/api/v1/admin/squads/{}/rotate-code', 'Rotate the join code? The current code will stop working.')">Rotate join code</button>
+                    <button class="btn-small btn-danger" onclick="adminAction('DELETE', '/api/v1/admin/squads/{}', 'Delete this squad and revoke all its sessions?')">Delete squad</button>
+                </div>
                 <table>
                     <thead>
-                        <tr><th>Name</th><th>ID</th><th>Role</th></tr>
+                        <tr><th>Name</th><th>ID</th><th>Role</th><th>Actions</th></tr>
                     </thead>
                     <tbody>{}</tbody>
                 </table>
@@ -146,6 +307,8 @@ pub async fn dashboard_page(
             squad.join_code,
             &squad.squad_id.to_string()[..8],
             squad.members.len(),
+            squad.squad_id,
+            squad.squad_id,
             members_html
         ));
     }
@@ -161,6 +324,7 @@ pub async fn dashboard_page(
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
     <title>Squadz Dashboard</title>
+    <link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css" />
     <style>
         * {{ margin: 0; padding: 0; box-sizing: border-box; }}
         body {{ 
@@ -228,6 +392,24 @@ pub async fn dashboard_page(
             white-space: pre-wrap;
             display: none;
         }}
+        .map-section {{
+            background: #16213e;
+            border-radius: 12px;
+            padding: 1.5rem;
+            margin-bottom: 2rem;
+            box-shadow: 0 4px 6px rgba(0,0,0,0.3);
+        }}
+        .map-section h3 {{ color: #4ade80; margin-bottom: 0.5rem; }}
+        #map {{ height: 480px; border-radius: 8px; margin-top: 1rem; }}
+        .map-meta {{ color: #888; font-size: 0.9rem; }}
+        .legend-stale {{ color: #9ca3af; }}
+        .squad-actions {{ margin-top: 0.75rem; }}
+        .btn-small {{
+            background: #0f3460; color: #eee; border: 1px solid #2a3f5f; padding: 0.3rem 0.75rem;
+            border-radius: 6px; cursor: pointer; font-size: 0.85rem; margin-right: 0.25rem;
+        }}
+        .btn-small:hover {{ background: #1e3a5f; }}
+        .btn-danger {{ border-color: #f87171; color: #f87171; }}
     </style>
 </head>
 <body>
@@ -250,8 +432,23 @@ pub async fn dashboard_page(
             </div>
         </div>
 
-        <a href="?password={}" class="btn">🔄 Refresh</a>
+        <a href="/" class="btn">🔄 Refresh</a>
+        <form method="POST" action="/logout" style="display: inline;">
+            <input type="hidden" name="csrf_token" value="{}">
+            <button type="submit" class="btn">🚪 Log out</button>
+        </form>
+        <button class="btn" onclick="adminAction('POST', '/api/v1/admin/locations/expire-stale', 'Drop every stale location now?')">🧹 Expire stale locations</button>
         
+        <div class="map-section">
+            <h3>🗺️ Live Map</h3>
+            <p class="map-meta">
+                Latest position of every member, refreshed every 10 seconds.
+                <span class="legend-stale">Grey dashed markers are stale.</span>
+                <span id="map-status"></span>
+            </p>
+            <div id="map"></div>
+        </div>
+
         <div class="crypto-section">
             <h3>🔐 Omni-Core-Lite Crypto Test</h3>
             <p>Test AES-256-GCM encryption round-trip with the server</p>
@@ -263,7 +460,87 @@ pub async fn dashboard_page(
         {}
     </div>
     
+    <script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
     <script>
+        const CSRF_TOKEN = '{}';
+
+        async function adminAction(method, url, confirmMsg) {{
+            if (!confirm(confirmMsg)) return;
+            const res = await fetch(url, {{
+                method,
+                credentials: 'same-origin',
+                headers: {{ 'X-CSRF-Token': CSRF_TOKEN }}
+            }});
+            if (!res.ok) {{
+                alert('Action failed: ' + res.status + ' ' + await res.text());
+                return;
+            }}
+            window.location.reload();
+        }}
+
+        const SQUAD_COLORS = ['#4ade80', '#3b82f6', '#fbbf24', '#f472b6', '#a78bfa', '#fb923c', '#22d3ee'];
+        const map = L.map('map').setView([20, 0], 2);
+        L.tileLayer('https://{{s}}.tile.openstreetmap.org/{{z}}/{{x}}/{{y}}.png', {{
+            attribution: '&copy; OpenStreetMap contributors',
+            maxZoom: 19
+        }}).addTo(map);
+        const markerLayer = L.layerGroup().addTo(map);
+        let fittedOnce = false;
+
+        function escapeHtml(s) {{
+            return String(s).replace(/[&<>"']/g, c => ({{
+                '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;', "'": '&#39;'
+            }})[c]);
+        }}
+
+        async function refreshMap() {{
+            const status = document.getElementById('map-status');
+            try {{
+                const res = await fetch('/dashboard/locations', {{ credentials: 'same-origin' }});
+                if (res.status === 401) {{
+                    window.location.reload();
+                    return;
+                }}
+                const squads = await res.json();
+                markerLayer.clearLayers();
+                const bounds = [];
+
+                squads.forEach((squad, i) => {{
+                    const color = SQUAD_COLORS[i % SQUAD_COLORS.length];
+                    squad.locations.forEach(loc => {{
+                        const latlng = [loc.location.latitude, loc.location.longitude];
+                        bounds.push(latlng);
+                        L.circleMarker(latlng, {{
+                            radius: 8,
+                            color: loc.is_stale ? '#9ca3af' : color,
+                            fillColor: loc.is_stale ? '#6b7280' : color,
+                            fillOpacity: loc.is_stale ? 0.3 : 0.8,
+                            dashArray: loc.is_stale ? '4 4' : null,
+                            weight: 2
+                        }})
+                            .bindPopup(
+                                '<strong>' + escapeHtml(loc.display_name) + '</strong><br>' +
+                                'Squad: ' + escapeHtml(squad.squad_name) + '<br>' +
+                                'Updated: ' + new Date(loc.updated_at).toLocaleString() +
+                                (loc.is_stale ? '<br><em>Stale</em>' : '')
+                            )
+                            .addTo(markerLayer);
+                    }});
+                }});
+
+                if (!fittedOnce && bounds.length > 0) {{
+                    map.fitBounds(bounds, {{ padding: [40, 40], maxZoom: 15 }});
+                    fittedOnce = true;
+                }}
+                status.textContent = '(' + bounds.length + ' positions, updated ' + new Date().toLocaleTimeString() + ')';
+            }} catch (err) {{
+                status.textContent = '(refresh failed: ' + err.message + ')';
+            }}
+        }}
+
+        refreshMap();
+        setInterval(refreshMap, 10000);
+
         async function testCrypto() {{
             const result = document.getElementById('crypto-result');
             result.style.display = 'block';
@@ -303,9 +580,14 @@ Decrypted: ${{JSON.stringify(decrypted, null, 2)}}`;
 </html>"##,
         squads.len(),
         total_members,
-        expected_password,
-        squad_html
+        html_escape(&session.csrf_token),
+        squad_html,
+        html_escape(&session.csrf_token)
     );
 
-    Ok(Html(html))
+    (
+        [(header::CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Html(html),
+    )
+        .into_response()
 }
@@ -0,0 +1,385 @@
+//! Unified API error type
+//!
+//! Every error from an `/api` route is a JSON body of
+//! `{code, message, details, request_id}`. `code` comes from the fixed
+//! [`ErrorCode`] catalogue and is what clients should branch on; `message` is
+//! for humans and may change between releases.
+
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::services::location_store::LocationError;
+use crate::services::messages::MessageError;
+use crate::services::routes::RouteError;
+use crate::services::sos::SosError;
+use crate::services::squad_manager::SquadError;
+use crate::services::webhooks::WebhookError;
+use crate::validation::{FieldError, ValidationError};
+
+/// Largest plain-text error body that is folded into an [`ApiError`] message
+const MAX_REWRAPPED_BODY: usize = 8 * 1024;
+
+/// Stable, machine-readable error codes
+///
+/// Codes are serialized in snake_case. Never rename or reuse one; add a new
+/// variant instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // Generic request errors
+    BadRequest,
+    InvalidBody,
+    ValidationFailed,
+    NotFound,
+    MethodNotAllowed,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    InternalError,
+
+    // Authentication
+    Unauthorized,
+    Forbidden,
+    InvalidCsrfToken,
+
+    // Squads and membership
+    SquadNotFound,
+    MemberNotFound,
+    InvalidJoinCode,
+    JoinCodeMismatch,
+    DisplayNameTaken,
+    NotSquadLeader,
+    NotSquadMember,
+    SquadFull,
+    CannotKickLeader,
+    PassphraseRequired,
+    InvalidPassphrase,
+    TooManyJoinAttempts,
+
+    // Locations
+    InvalidBatch,
+    FixInFuture,
+
+    // Distress calls
+    SosNotFound,
+    CannotAcknowledgeOwnSos,
+
+    // Messages
+    MessageNotFound,
+
+    // Waypoints and routes
+    WaypointNotFound,
+    TooManyWaypoints,
+    RouteNotFound,
+    InvalidGpx,
+
+    // Webhooks
+    WebhookNotFound,
+    TooManyWebhooks,
+
+    // Crypto endpoints
+    InvalidNonce,
+    InvalidCiphertext,
+    DecryptionFailed,
+
+    // Admin
+    InvalidSnapshot,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        use ErrorCode::*;
+        match self {
+            BadRequest | JoinCodeMismatch | InvalidNonce | InvalidCiphertext | DecryptionFailed
+            | InvalidSnapshot => StatusCode::BAD_REQUEST,
+            InvalidBody | ValidationFailed | InvalidBatch | FixInFuture | InvalidGpx => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            NotFound | SquadNotFound | MemberNotFound | InvalidJoinCode | SosNotFound
+            | MessageNotFound | WaypointNotFound | RouteNotFound | WebhookNotFound => {
+                StatusCode::NOT_FOUND
+            }
+            MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            Unauthorized | PassphraseRequired => StatusCode::UNAUTHORIZED,
+            Forbidden | InvalidCsrfToken | NotSquadLeader | NotSquadMember | InvalidPassphrase => {
+                StatusCode::FORBIDDEN
+            }
+            DisplayNameTaken | SquadFull | CannotKickLeader | CannotAcknowledgeOwnSos
+            | TooManyWaypoints | TooManyWebhooks => {
+                StatusCode::CONFLICT
+            }
+            TooManyJoinAttempts => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    /// Generic code for a bare status, e.g. from an extractor rejection
+    fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNPROCESSABLE_ENTITY => ErrorCode::InvalidBody,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => ErrorCode::MethodNotAllowed,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            s if s.is_server_error() => ErrorCode::InternalError,
+            _ => ErrorCode::BadRequest,
+        }
+    }
+}
+
+/// Error returned by every API handler
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Code-specific context; for `validation_failed`, `{"errors": [FieldError]}`
+    #[schema(value_type = Option<Object>)]
+    pub details: Value,
+    /// ID from the `x-request-id` response header, for support requests
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: Value::Null,
+            request_id: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InternalError, message)
+    }
+}
+
+impl From<SquadError> for ApiError {
+    fn from(e: SquadError) -> Self {
+        let code = match e {
+            SquadError::SquadNotFound => ErrorCode::SquadNotFound,
+            SquadError::InvalidJoinCode => ErrorCode::InvalidJoinCode,
+            SquadError::MemberNotFound => ErrorCode::MemberNotFound,
+            SquadError::NameTaken => ErrorCode::DisplayNameTaken,
+            SquadError::NotLeader => ErrorCode::NotSquadLeader,
+            SquadError::SquadFull => ErrorCode::SquadFull,
+            SquadError::CannotKickLeader => ErrorCode::CannotKickLeader,
+            SquadError::PassphraseRequired => ErrorCode::PassphraseRequired,
+            SquadError::InvalidPassphrase => ErrorCode::InvalidPassphrase,
+            SquadError::WaypointNotFound => ErrorCode::WaypointNotFound,
+            SquadError::NotWaypointCreator => ErrorCode::Forbidden,
+            SquadError::TooManyWaypoints => ErrorCode::TooManyWaypoints,
+            SquadError::RouteNotFound => ErrorCode::RouteNotFound,
+            SquadError::TooManyJoinAttempts => ErrorCode::TooManyJoinAttempts,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<LocationError> for ApiError {
+    fn from(e: LocationError) -> Self {
+        let details = match &e {
+            LocationError::FixInFuture {
+                index,
+                ahead_secs,
+                max_skew_secs,
+            } => serde_json::json!({
+                "index": index,
+                "ahead_secs": ahead_secs,
+                "max_clock_skew_secs": max_skew_secs,
+            }),
+        };
+        let code = match e {
+            LocationError::FixInFuture { .. } => ErrorCode::FixInFuture,
+        };
+        Self::new(code, e.to_string()).with_details(details)
+    }
+}
+
+impl From<SosError> for ApiError {
+    fn from(e: SosError) -> Self {
+        let code = match e {
+            SosError::NotFound => ErrorCode::SosNotFound,
+            SosError::OwnCall => ErrorCode::CannotAcknowledgeOwnSos,
+            SosError::NotAllowed => ErrorCode::Forbidden,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<MessageError> for ApiError {
+    fn from(e: MessageError) -> Self {
+        let code = match e {
+            MessageError::NotFound => ErrorCode::MessageNotFound,
+            MessageError::NotAllowed => ErrorCode::Forbidden,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<RouteError> for ApiError {
+    fn from(e: RouteError) -> Self {
+        let code = match e {
+            RouteError::InvalidGpx(_) => ErrorCode::InvalidGpx,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<WebhookError> for ApiError {
+    fn from(e: WebhookError) -> Self {
+        let code = match e {
+            WebhookError::NotFound => ErrorCode::WebhookNotFound,
+            WebhookError::TooMany => ErrorCode::TooManyWebhooks,
+            WebhookError::Destination(message) => {
+                return ValidationError(vec![FieldError { field: "url".to_string(), message }])
+                    .into();
+            }
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<ValidationError> for ApiError {
+    fn from(e: ValidationError) -> Self {
+        let message = e.to_string();
+        Self::new(ErrorCode::ValidationFailed, message)
+            .with_details(serde_json::json!({ "errors": e.0 }))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.code.status(), Json(&self)).into_response();
+        // Lets `error_envelope` stamp the request ID without re-parsing the body
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Middleware that makes every `/api` error response an [`ApiError`]
+///
+/// Stamps the request ID into errors raised by handlers, and wraps bare
+/// statuses and plain-text bodies (extractor rejections, unknown routes) in
+/// the JSON envelope with a generic code.
+pub async fn error_envelope(request: Request<Body>, next: Next) -> Response {
+    let is_api = request.uri().path().starts_with("/api/");
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let mut response = next.run(request).await;
+    if !is_api {
+        return response;
+    }
+
+    if let Some(mut error) = response.extensions_mut().remove::<ApiError>() {
+        error.request_id = request_id;
+        return error.into_response();
+    }
+
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if !(status.is_client_error() || status.is_server_error()) || is_json {
+        return response;
+    }
+
+    let body = axum::body::to_bytes(response.into_body(), MAX_REWRAPPED_BODY)
+        .await
+        .unwrap_or_default();
+    let message = match std::str::from_utf8(&body).map(str::trim) {
+        Ok(text) if !text.is_empty() => text.to_string(),
+        _ => status.canonical_reason().unwrap_or("Error").to_string(),
+    };
+
+    let mut error = ApiError::new(ErrorCode::from_status(status), message);
+    error.request_id = request_id;
+    let mut response = error.into_response();
+    // Keep the original status even when the generic code maps elsewhere
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn body_json(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_codes_are_stable() {
+        assert_eq!(serde_json::to_value(ErrorCode::SquadNotFound).unwrap(), "squad_not_found");
+        assert_eq!(serde_json::to_value(ErrorCode::InvalidCsrfToken).unwrap(), "invalid_csrf_token");
+    }
+
+    #[test]
+    fn test_squad_errors_map_consistently() {
+        assert_eq!(ApiError::from(SquadError::MemberNotFound).code.status(), StatusCode::NOT_FOUND);
+        assert_eq!(ApiError::from(SquadError::SquadNotFound).code.status(), StatusCode::NOT_FOUND);
+        assert_eq!(ApiError::from(SquadError::NotLeader).code.status(), StatusCode::FORBIDDEN);
+        assert_eq!(ApiError::from(SquadError::SquadFull).code.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_envelope_adds_request_id_and_wraps_plain_errors() {
+        let app = Router::new()
+            .route(
+                "/api/v1/fail",
+                get(|| async { ApiError::from(SquadError::SquadNotFound) }),
+            )
+            .route(
+                "/api/v1/plain",
+                get(|| async { (StatusCode::BAD_REQUEST, "nope") }),
+            )
+            .layer(middleware::from_fn(error_envelope));
+
+        let request = Request::get("/api/v1/fail")
+            .header("x-request-id", "req-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = body_json(response).await;
+        assert_eq!(body["code"], "squad_not_found");
+        assert_eq!(body["request_id"], "req-42");
+
+        let request = Request::get("/api/v1/plain").body(Body::empty()).unwrap();
+        let body = body_json(app.clone().oneshot(request).await.unwrap()).await;
+        assert_eq!(body["code"], "bad_request");
+        assert_eq!(body["message"], "nope");
+
+        let request = Request::get("/api/v1/missing").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_json(response).await["code"], "not_found");
+    }
+}
@@ -0,0 +1,35 @@
+//! Request extractors
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::api::error::ApiError;
+use crate::validation::{self, Validate};
+
+/// JSON body that has been normalized and validated
+///
+/// Malformed JSON is rejected like [`Json`] (`invalid_body`); a well-formed
+/// body with invalid fields gets a 422 `validation_failed` listing them all.
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(mut value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        validation::validate(&mut value).map_err(|e| ApiError::from(e).into_response())?;
+        Ok(Self(value))
+    }
+}
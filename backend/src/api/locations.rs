@@ -1,64 +1,537 @@
 //! Location tracking endpoints
+//!
+//! v1 and v2 differ only in the payload shape ([`GeoPoint`] vs the flat
+//! [`LocationFix`]); both go through the same store logic. Batch upload,
+//! track history, proximity and the quarantine review are only offered in the
+//! v2 shape.
+//!
+//! Fixes are checked against the squad's plausibility settings; suspect ones
+//! carry a `suspect` reason or are quarantined for the leader, who releases
+//! them into the track or discards them. Squads with
+//! smoothing enabled also get a `smoothed` position beside the raw one.
+//! While a member has an open distress call their fixes are never
+//! quarantined or dropped, and each one moves the call.
 
 use std::sync::Arc;
 use axum::{
-    extract::{Path, State, Extension},
+    extract::{Path, Query, State, Extension},
     http::StatusCode,
     Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::IntoParams;
 use uuid::Uuid;
 
-use crate::models::{GeoPoint, SquadLocationsResponse};
+use crate::api::error::{ApiError, ErrorCode};
+use crate::api::extract::ValidJson;
+use crate::api::squads::{require_leader, require_member};
+use crate::models::{
+    GeoPoint, LocationBatchRequest, LocationBatchResponse, LocationFix, MemberTrackResponse,
+    ProximityResponse, QuarantineResponse, QuarantineReviewRequest, QuarantineReviewResponse,
+    QuarantinedFix, SquadFixesResponse,
+    SquadLocationsResponse, SquadSettings, SuspectAction, TrackFix,
+};
+use crate::services::audit::AuditContext;
+use crate::services::location_store::QuarantineReview;
+use crate::services::{alerts, proximity, sos};
+use crate::services::session::MemberSession;
 use crate::services::auth::AuthenticatedMember;
+use crate::validation::{self, Validate, Validator};
 use crate::AppState;
 
+/// Most fixes accepted in one batch upload
+pub const MAX_BATCH_FIXES: usize = 1000;
+
 /// Request to update location (simplified - uses session for member/squad)
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
 pub struct AuthenticatedLocationUpdate {
     pub location: GeoPoint,
 }
 
+impl Validate for AuthenticatedLocationUpdate {
+    fn validate(&mut self, v: &mut Validator) {
+        v.field("location", |v| self.location.validate(v));
+    }
+}
+
 /// Update a member's location (requires auth)
+#[utoipa::path(
+    post,
+    path = "/api/v1/locations",
+    tag = "locations",
+    request_body = AuthenticatedLocationUpdate,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Location stored"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "No longer a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Invalid coordinates or fix time too far in the future", body = ApiError),
+    )
+)]
 pub async fn update_location(
     State(state): State<Arc<AppState>>,
     Extension(auth): Extension<AuthenticatedMember>,
-    Json(req): Json<AuthenticatedLocationUpdate>,
-) -> Result<StatusCode, (StatusCode, String)> {
+    ValidJson(req): ValidJson<AuthenticatedLocationUpdate>,
+) -> Result<StatusCode, ApiError> {
+    store_location(&state, &auth.session, req.location).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Get all member locations for a squad
+#[utoipa::path(
+    get,
+    path = "/api/v1/squads/{squad_id}/locations",
+    tag = "locations",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    responses(
+        (status = 200, description = "Latest location of every member", body = SquadLocationsResponse),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn get_squad_locations(
+    State(state): State<Arc<AppState>>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<SquadLocationsResponse>, ApiError> {
+    squad_locations(&state, squad_id).await.map(Json)
+}
+
+/// Update a member's location with a v2 fix (requires auth)
+#[utoipa::path(
+    post,
+    path = "/api/v2/locations",
+    tag = "locations",
+    request_body = LocationFix,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Location stored"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "No longer a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Invalid coordinates or fix time too far in the future", body = ApiError),
+    )
+)]
+pub async fn update_location_v2(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ValidJson(fix): ValidJson<LocationFix>,
+) -> Result<StatusCode, ApiError> {
+    store_location(&state, &auth.session, fix.into()).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Get the latest v2 fix of every member of a squad
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/locations",
+    tag = "locations",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    responses(
+        (status = 200, description = "Latest fix of every member", body = SquadFixesResponse),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn get_squad_locations_v2(
+    State(state): State<Arc<AppState>>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<SquadFixesResponse>, ApiError> {
+    squad_locations(&state, squad_id).await.map(|r| Json(r.into()))
+}
+
+/// Query for a member's track
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TrackQuery {
+    /// Only fixes recorded after this time
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Query for squad proximity
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProximityQuery {
+    /// Also list, for each member, the others within this many metres
+    pub within_m: Option<f64>,
+    /// Count members whose latest fix is stale (left out by default)
+    #[serde(default)]
+    pub include_stale: bool,
+}
+
+impl Validate for ProximityQuery {
+    fn validate(&mut self, v: &mut Validator) {
+        if self.within_m.is_some_and(|m| !(m.is_finite() && m > 0.0)) {
+            v.field("within_m", |v| v.error("must be a positive number"));
+        }
+    }
+}
+
+/// Upload fixes recorded while the device was offline (requires auth)
+///
+/// Fixes are merged into the member's history by `recorded_at`; the latest
+/// position only moves if the newest fix is newer than the stored one.
+#[utoipa::path(
+    post,
+    path = "/api/v2/locations/batch",
+    tag = "locations",
+    request_body = LocationBatchRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Batch merged", body = LocationBatchResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "No longer a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Empty, oversized or out-of-order batch, invalid coordinates or a fix in the future", body = ApiError),
+    )
+)]
+pub async fn upload_location_batch(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ValidJson(req): ValidJson<LocationBatchRequest>,
+) -> Result<Json<LocationBatchResponse>, ApiError> {
+    validate_batch(&req.fixes)?;
     let session = auth.session;
-    
-    // Get member display name from squad
+    let (display_name, settings) = member_context(&state, &session).await?;
+    let settings = location_settings(&state, &session, settings);
+
+    let fixes: Vec<GeoPoint> = req.fixes.into_iter().map(GeoPoint::from).collect();
+    let outcome = state.location_store.write().await.merge_history(
+        session.squad_id,
+        session.member_id,
+        display_name,
+        fixes,
+        &settings,
+    )?;
+    if let Some(latest) = outcome.latest {
+        sos::track_location(&state, &session.squad_id, &session.member_id, latest);
+    }
+    alerts::check_squad(&state, &session.squad_id).await;
+
+    Ok(Json(LocationBatchResponse {
+        accepted: outcome.accepted,
+        duplicates: outcome.duplicates,
+        latest_updated: outcome.latest.is_some(),
+        flagged: outcome.flagged,
+        quarantined: outcome.quarantined,
+        dropped: outcome.dropped,
+    }))
+}
+
+/// Get a member's recent track, oldest fix first (members only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/members/{member_id}/track",
+    tag = "locations",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("member_id" = Uuid, Path, description = "Member ID"),
+        TrackQuery,
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "The member's stored history", body = MemberTrackResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found or member has no location", body = ApiError),
+    )
+)]
+pub async fn get_member_track(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path((squad_id, member_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<TrackQuery>,
+) -> Result<Json<MemberTrackResponse>, ApiError> {
+    require_member(&state, &auth.session, &squad_id).await?;
+
+    let store = state.location_store.read().await;
+    let (display_name, points) = store
+        .member_history(&squad_id, &member_id, query.since)
+        .ok_or_else(|| ApiError::new(ErrorCode::MemberNotFound, "No location for this member"))?;
+
+    Ok(Json(MemberTrackResponse {
+        member_id,
+        display_name,
+        fixes: points
+            .into_iter()
+            .map(|p| TrackFix {
+                fix: LocationFix {
+                    recorded_at: Some(p.recorded_at),
+                    ..p.location.into()
+                },
+                suspect: p.suspect,
+            })
+            .collect(),
+    }))
+}
+
+/// Distances and bearings between squad members
+///
+/// Measured between the latest (smoothed, where enabled) positions. Members
+/// are listed furthest from the squad's centroid first.
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/proximity",
+    tag = "locations",
+    params(("squad_id" = Uuid, Path, description = "Squad ID"), ProximityQuery),
+    responses(
+        (status = 200, description = "Pairwise distances, nearest neighbours and strays", body = ProximityResponse),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Invalid `within_m`", body = ApiError),
+    )
+)]
+pub async fn get_squad_proximity(
+    State(state): State<Arc<AppState>>,
+    Path(squad_id): Path<Uuid>,
+    Query(mut query): Query<ProximityQuery>,
+) -> Result<Json<ProximityResponse>, ApiError> {
+    validation::validate(&mut query)?;
+    let mut locations = squad_locations(&state, squad_id).await?.locations;
+    if !query.include_stale {
+        locations.retain(|location| !location.is_stale);
+    }
+
+    let measured = proximity::measure(&locations, query.within_m);
+    Ok(Json(ProximityResponse {
+        squad_id,
+        centroid: measured.centroid.map(LocationFix::from),
+        members: measured.members,
+        pairs: measured.pairs,
+        updated_at: Utc::now(),
+    }))
+}
+
+/// List the squad's quarantined fixes (leader only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/quarantine",
+    tag = "locations",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Fixes held back by the plausibility checks", body = QuarantineResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn get_quarantine(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<QuarantineResponse>, ApiError> {
+    require_leader(&state, &auth.session, &squad_id).await?;
+
+    let store = state.location_store.read().await;
+    let fixes = store
+        .squad_quarantine(&squad_id)
+        .into_iter()
+        .filter_map(|(member_id, display_name, point)| {
+            Some(QuarantinedFix {
+                member_id,
+                display_name,
+                fix: LocationFix {
+                    recorded_at: Some(point.recorded_at),
+                    ..point.location.into()
+                },
+                reason: point.suspect?,
+            })
+        })
+        .collect();
+
+    Ok(Json(QuarantineResponse { squad_id, fixes }))
+}
+
+/// Add quarantined fixes to the member's track (leader only, requires auth)
+///
+/// The member's live position is not moved; their next fix is checked
+/// against the released ones.
+#[utoipa::path(
+    post,
+    path = "/api/v2/squads/{squad_id}/quarantine/release",
+    tag = "locations",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = QuarantineReviewRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Number of fixes released", body = QuarantineReviewResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found or no location for the member", body = ApiError),
+        (status = 422, description = "Empty or oversized list of fix times", body = ApiError),
+    )
+)]
+pub async fn release_quarantine(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<QuarantineReviewRequest>,
+) -> Result<Json<QuarantineReviewResponse>, ApiError> {
+    review_quarantine(&state, &auth, &ctx, squad_id, req, QuarantineReview::Release).await
+}
+
+/// Drop quarantined fixes (leader only, requires auth)
+#[utoipa::path(
+    post,
+    path = "/api/v2/squads/{squad_id}/quarantine/discard",
+    tag = "locations",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = QuarantineReviewRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Number of fixes discarded", body = QuarantineReviewResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found or no location for the member", body = ApiError),
+        (status = 422, description = "Empty or oversized list of fix times", body = ApiError),
+    )
+)]
+pub async fn discard_quarantine(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<QuarantineReviewRequest>,
+) -> Result<Json<QuarantineReviewResponse>, ApiError> {
+    review_quarantine(&state, &auth, &ctx, squad_id, req, QuarantineReview::Discard).await
+}
+
+async fn review_quarantine(
+    state: &AppState,
+    auth: &AuthenticatedMember,
+    ctx: &AuditContext,
+    squad_id: Uuid,
+    req: QuarantineReviewRequest,
+    review: QuarantineReview,
+) -> Result<Json<QuarantineReviewResponse>, ApiError> {
+    require_leader(state, &auth.session, &squad_id).await?;
+
+    let fixes = state
+        .location_store
+        .write()
+        .await
+        .review_quarantine(&squad_id, &req.member_id, req.recorded_at.as_deref(), review)
+        .ok_or_else(|| ApiError::new(ErrorCode::MemberNotFound, "No location for this member"))?;
+
+    let action = match review {
+        QuarantineReview::Release => "location.quarantine_release",
+        QuarantineReview::Discard => "location.quarantine_discard",
+    };
+    state.audit.record(
+        ctx,
+        action,
+        Some(squad_id),
+        Some(req.member_id),
+        json!({ "fixes": fixes }),
+    );
+    Ok(Json(QuarantineReviewResponse { squad_id, member_id: req.member_id, fixes }))
+}
+
+/// Reject empty, oversized and out-of-order batches
+fn validate_batch(fixes: &[LocationFix]) -> Result<(), ApiError> {
+    if fixes.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidBatch, "Batch contains no fixes"));
+    }
+    if fixes.len() > MAX_BATCH_FIXES {
+        return Err(ApiError::new(
+            ErrorCode::InvalidBatch,
+            format!("Batch has {} fixes; the limit is {}", fixes.len(), MAX_BATCH_FIXES),
+        )
+        .with_details(json!({ "max_fixes": MAX_BATCH_FIXES })));
+    }
+    if let Some(index) = fixes.iter().position(|fix| fix.recorded_at.is_none()) {
+        return Err(
+            ApiError::new(ErrorCode::InvalidBatch, "Every batched fix needs a recorded_at")
+                .with_details(json!({ "index": index })),
+        );
+    }
+    if let Some(index) = fixes
+        .windows(2)
+        .position(|pair| pair[1].recorded_at <= pair[0].recorded_at)
+    {
+        return Err(ApiError::new(
+            ErrorCode::InvalidBatch,
+            "Fix timestamps must be strictly increasing",
+        )
+        .with_details(json!({ "index": index + 1 })));
+    }
+    Ok(())
+}
+
+/// Record a member's position under the display name they have in the squad
+async fn store_location(
+    state: &AppState,
+    session: &MemberSession,
+    location: GeoPoint,
+) -> Result<(), ApiError> {
+    let (display_name, settings) = member_context(state, session).await?;
+    let settings = location_settings(state, session, settings);
+    let outcome = state.location_store.write().await.update_location(
+        session.squad_id,
+        session.member_id,
+        display_name,
+        location,
+        &settings,
+    )?;
+    // A late or duplicate fix isn't where the member is now
+    if let Some(latest) = outcome.latest {
+        sos::track_location(state, &session.squad_id, &session.member_id, latest);
+    }
+    alerts::check_squad(state, &session.squad_id).await;
+    Ok(())
+}
+
+/// The squad's settings as they apply to the session member's fixes
+fn location_settings(
+    state: &AppState,
+    session: &MemberSession,
+    settings: SquadSettings,
+) -> SquadSettings {
+    if state.sos.is_in_distress(&session.squad_id, &session.member_id) {
+        distress_settings(settings)
+    } else {
+        settings
+    }
+}
+
+/// Settings for a member in distress: every fix is kept, exactly as sent
+pub(crate) fn distress_settings(mut settings: SquadSettings) -> SquadSettings {
+    settings.plausibility.on_suspect = SuspectAction::Flag;
+    settings.smoothing.max_accuracy_m = None;
+    settings
+}
+
+/// The session member's display name and their squad's settings, checking
+/// they are still in the squad
+pub(crate) async fn member_context(
+    state: &AppState,
+    session: &MemberSession,
+) -> Result<(String, SquadSettings), ApiError> {
     let manager = state.squad_manager.read().await;
     let squad = manager
         .get_squad(&session.squad_id)
-        .ok_or((StatusCode::NOT_FOUND, "Squad not found".to_string()))?;
+        .ok_or_else(|| ApiError::new(ErrorCode::SquadNotFound, "Squad not found"))?;
 
     let member = squad
         .members
         .iter()
         .find(|m| m.member_id == session.member_id)
-        .ok_or((StatusCode::FORBIDDEN, "Not a member of this squad".to_string()))?;
+        .ok_or_else(|| ApiError::new(ErrorCode::NotSquadMember, "Not a member of this squad"))?;
 
-    let display_name = member.display_name.clone();
-    drop(manager);
-
-    // Update location
-    let mut store = state.location_store.write().await;
-    store.update_location(session.squad_id, session.member_id, display_name, req.location);
-
-    Ok(StatusCode::OK)
+    Ok((member.display_name.clone(), squad.settings.clone()))
 }
 
-/// Get all member locations for a squad
-pub async fn get_squad_locations(
-    State(state): State<Arc<AppState>>,
-    Path(squad_id): Path<Uuid>,
-) -> Result<Json<SquadLocationsResponse>, (StatusCode, String)> {
+/// Latest location of every member of a squad
+async fn squad_locations(
+    state: &AppState,
+    squad_id: Uuid,
+) -> Result<SquadLocationsResponse, ApiError> {
     // Get squad info
     let manager = state.squad_manager.read().await;
     let squad = manager
         .get_squad(&squad_id)
-        .ok_or((StatusCode::NOT_FOUND, "Squad not found".to_string()))?;
+        .ok_or_else(|| ApiError::new(ErrorCode::SquadNotFound, "Squad not found"))?;
 
     let squad_name = squad.name.clone();
     drop(manager);
@@ -67,10 +540,148 @@ pub async fn get_squad_locations(
     let store = state.location_store.read().await;
     let locations = store.get_squad_locations(&squad_id);
 
-    Ok(Json(SquadLocationsResponse {
+    Ok(SquadLocationsResponse {
         squad_id,
         squad_name,
         locations,
         updated_at: Utc::now(),
-    }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use chrono::Duration;
+    use serde_json::json;
+
+    use super::*;
+    use crate::api::testing::TestApp;
+
+    #[tokio::test]
+    async fn test_invalid_batches_rejected() {
+        let app = TestApp::new();
+        let (squad_id, key) = app.squad().await;
+        let now = Utc::now();
+        let fix = |secs: i64| {
+            let at = now - Duration::seconds(secs);
+            json!({ "lat": 40.0, "lon": -105.0, "recorded_at": at })
+        };
+        let oversized: Vec<_> = (0..=MAX_BATCH_FIXES as i64).rev().map(fix).collect();
+        let mut missing_time = fix(30);
+        missing_time.as_object_mut().unwrap().remove("recorded_at");
+
+        for (fixes, index) in [
+            (vec![], None),
+            (oversized, None),
+            (vec![fix(60), missing_time], Some(1)),
+            (vec![fix(60), fix(30), fix(30)], Some(2)),
+            (vec![fix(30), fix(60)], Some(1)),
+        ] {
+            let body = json!({ "fixes": fixes });
+            let (status, error) =
+                app.request(Method::POST, "/api/v2/locations/batch", Some(&key), Some(body)).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(error["code"], "invalid_batch");
+            if let Some(index) = index {
+                assert_eq!(error["details"]["index"], index);
+            }
+        }
+        let uri = format!("/api/v2/squads/{squad_id}/locations");
+        let (_, locations) = app.request(Method::GET, &uri, None, None).await;
+        assert!(locations["members"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_track_for_members_only_and_dropped_on_leave() {
+        let app = TestApp::new();
+        let (squad_id, leader) = app.squad().await;
+        let scout = app.join(&squad_id, "Scout").await;
+        let scout_id = app.state.session_store.validate(&scout).unwrap().member_id;
+        let fix = json!({ "lat": 40.0, "lon": -105.0 });
+        let (status, _) =
+            app.request(Method::POST, "/api/v2/locations", Some(&scout), Some(fix)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let uri = format!("/api/v2/squads/{squad_id}/members/{scout_id}/track");
+        let (status, _) = app.request(Method::GET, &uri, None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (_, outsider) = app.squad().await;
+        let (status, _) = app.request(Method::GET, &uri, Some(&outsider), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, track) = app.request(Method::GET, &uri, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(track["fixes"].as_array().unwrap().len(), 1);
+
+        let leave = format!("/api/v1/squads/{squad_id}/leave");
+        let body = json!({ "member_id": scout_id });
+        let (status, _) = app.request(Method::POST, &leave, Some(&scout), Some(body)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = app.request(Method::GET, &uri, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_released_and_discarded_by_leader() {
+        let app = TestApp::new();
+        let ctx = AuditContext::system();
+        let mut settings = SquadSettings::default();
+        settings.plausibility.on_suspect = SuspectAction::Quarantine;
+        let (squad, leader_id) = app.state.squad_manager.write().await.create_squad(
+            &ctx,
+            "Alpha".to_string(),
+            "Lead".to_string(),
+            Some(settings),
+            None,
+        );
+        let squad_id = squad.squad_id;
+        let leader = app.state.session_store.create(&ctx, leader_id, squad_id, 3600).api_key;
+        let scout = app.join(&squad_id, "Scout").await;
+        let scout_id = app.state.session_store.validate(&scout).unwrap().member_id;
+
+        // Two jumps of hundreds of kilometres in a minute each
+        let now = Utc::now();
+        let fixes: Vec<_> = [(40.0, 180), (45.0, 120), (50.0, 60)]
+            .into_iter()
+            .map(|(lat, secs)| {
+                let at = now - Duration::seconds(secs);
+                json!({ "lat": lat, "lon": -105.0, "recorded_at": at })
+            })
+            .collect();
+        let body = json!({ "fixes": fixes });
+        let (_, outcome) =
+            app.request(Method::POST, "/api/v2/locations/batch", Some(&scout), Some(body)).await;
+        assert_eq!(outcome["quarantined"], 2);
+        let uri = format!("/api/v2/squads/{squad_id}/quarantine");
+        let (_, quarantine) = app.request(Method::GET, &uri, Some(&leader), None).await;
+        let first = quarantine["fixes"][0]["fix"]["recorded_at"].clone();
+
+        let release = format!("{uri}/release");
+        let discard = format!("{uri}/discard");
+        let one = json!({ "member_id": scout_id, "recorded_at": [first] });
+        let (status, _) =
+            app.request(Method::POST, &release, Some(&scout), Some(one.clone())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let body = json!({ "member_id": scout_id, "recorded_at": [] });
+        let (status, error) = app.request(Method::POST, &release, Some(&leader), Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["details"]["errors"][0]["field"], "recorded_at");
+        let body = json!({ "member_id": leader_id });
+        let (status, error) = app.request(Method::POST, &discard, Some(&leader), Some(body)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "member_not_found");
+
+        let (status, released) =
+            app.request(Method::POST, &release, Some(&leader), Some(one)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(released["fixes"], 1);
+        let track = format!("/api/v2/squads/{squad_id}/members/{scout_id}/track");
+        let (_, track) = app.request(Method::GET, &track, Some(&leader), None).await;
+        assert_eq!(track["fixes"].as_array().unwrap().len(), 2);
+
+        let body = json!({ "member_id": scout_id });
+        let (_, discarded) = app.request(Method::POST, &discard, Some(&leader), Some(body)).await;
+        assert_eq!(discarded["fixes"], 1);
+        let (_, quarantine) = app.request(Method::GET, &uri, Some(&leader), None).await;
+        assert!(quarantine["fixes"].as_array().unwrap().is_empty());
+    }
 }
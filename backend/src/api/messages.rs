@@ -0,0 +1,192 @@
+//! Squad message endpoints (v2, members only)
+//!
+//! Messages are also pushed on the squad stream, so clients only need
+//! `since` to catch up after reconnecting.
+
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::api::error::ApiError;
+use crate::api::extract::ValidJson;
+use crate::api::squads::require_member;
+use crate::models::{MessagesResponse, SendMessageRequest, SquadEvent, SquadMessage};
+use crate::services::audit::AuditContext;
+use crate::services::auth::AuthenticatedMember;
+use crate::AppState;
+
+/// Messages returned when no `limit` is given
+const DEFAULT_LIMIT: usize = 100;
+
+/// Most messages returned per request
+const MAX_LIMIT: usize = 500;
+
+/// Query for listing messages
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MessagesQuery {
+    /// Only messages sent after this instant, oldest first; without it the
+    /// newest messages are returned
+    pub since: Option<DateTime<Utc>>,
+    /// Most messages to return (default 100); `has_more` is set when more
+    /// follow
+    pub limit: Option<usize>,
+}
+
+/// Send a message to the squad (members only, requires auth)
+#[utoipa::path(
+    post,
+    path = "/api/v2/squads/{squad_id}/messages",
+    tag = "messages",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = SendMessageRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Message sent", body = SquadMessage),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Empty or too long text, or invalid location", body = ApiError),
+    )
+)]
+pub async fn send_message(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<SendMessageRequest>,
+) -> Result<Json<SquadMessage>, ApiError> {
+    let member = require_member(&state, &auth.session, &squad_id).await?;
+    let message = state.messages.send(
+        squad_id,
+        member.member_id,
+        member.display_name,
+        req.text,
+        req.location,
+    );
+    state.feed.publish(SquadEvent::Message(message.clone()));
+    Ok(Json(message))
+}
+
+/// Recent messages of the squad, oldest first (members only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/messages",
+    tag = "messages",
+    params(("squad_id" = Uuid, Path, description = "Squad ID"), MessagesQuery),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Messages, oldest first: the first `limit` after `since`, or the newest `limit`", body = MessagesResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn list_messages(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+    Query(query): Query<MessagesQuery>,
+) -> Result<Json<MessagesResponse>, ApiError> {
+    require_member(&state, &auth.session, &squad_id).await?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let (messages, has_more) = state.messages.since(&squad_id, query.since, limit);
+    Ok(Json(MessagesResponse {
+        squad_id,
+        messages,
+        has_more,
+    }))
+}
+
+/// Delete a message (its author or the squad leader, requires auth)
+#[utoipa::path(
+    delete,
+    path = "/api/v2/squads/{squad_id}/messages/{message_id}",
+    tag = "messages",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("message_id" = Uuid, Path, description = "Message ID"),
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Message deleted"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Neither the author nor the squad leader", body = ApiError),
+        (status = 404, description = "Squad or message not found", body = ApiError),
+    )
+)]
+pub async fn delete_message(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path((squad_id, message_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let member = require_member(&state, &auth.session, &squad_id).await?;
+    state.messages.delete(
+        &ctx,
+        &squad_id,
+        &message_id,
+        &member.member_id,
+        member.is_leader,
+    )?;
+    state.feed.publish(SquadEvent::MessageDeleted {
+        squad_id,
+        message_id,
+    });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use crate::api::testing::TestApp;
+
+    #[tokio::test]
+    async fn test_message_rejections() {
+        let app = TestApp::new();
+        let (squad_id, leader) = app.squad().await;
+        let scout = app.join(&squad_id, "Scout").await;
+        let medic = app.join(&squad_id, "Medic").await;
+        let (_, outsider) = app.squad().await;
+        let uri = format!("/api/v2/squads/{squad_id}/messages");
+        let text = |text: &str| json!({ "text": text });
+
+        let (status, _) = app.request(Method::POST, &uri, None, Some(text("Hi"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = app.request(Method::POST, &uri, Some(&outsider), Some(text("Hi"))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = app.request(Method::GET, &uri, Some(&outsider), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        for body in [text("   "), text(&"x".repeat(5000))] {
+            let (status, error) = app.request(Method::POST, &uri, Some(&scout), Some(body)).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(error["code"], "validation_failed");
+            assert_eq!(error["details"]["errors"][0]["field"], "text");
+        }
+        let bad_location =
+            json!({ "text": "Here", "location": {"latitude": 0.0, "longitude": 200.0} });
+        let (status, _) = app.request(Method::POST, &uri, Some(&scout), Some(bad_location)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, message) =
+            app.request(Method::POST, &uri, Some(&scout), Some(text("Hi"))).await;
+        assert_eq!(status, StatusCode::OK);
+        let one = format!("{uri}/{}", message["message_id"].as_str().unwrap());
+        let (status, error) = app.request(Method::DELETE, &one, Some(&medic), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error["code"], "forbidden");
+        let unknown = format!("{uri}/{}", Uuid::new_v4());
+        let (status, error) = app.request(Method::DELETE, &unknown, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "message_not_found");
+    }
+}
@@ -1,7 +1,172 @@
 //! API handlers for Squadz
 
+pub mod admin;
+pub mod alerts;
 pub mod crypto;
 pub mod dashboard;
+pub mod error;
+pub mod extract;
 pub mod health;
 pub mod locations;
+pub mod messages;
+pub mod openapi;
+pub mod routes;
+pub mod sos;
 pub mod squads;
+pub mod stream;
+#[cfg(test)]
+pub mod testing;
+pub mod version;
+pub mod waypoints;
+pub mod webhooks;
+
+use std::sync::Arc;
+
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
+
+use crate::services::auth::{admin_auth_middleware, auth_middleware};
+use crate::AppState;
+use version::ApiVersion;
+
+/// Every API version, each mounted under its `/api/vN` prefix
+pub fn routes(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    ApiVersion::ALL
+        .into_iter()
+        .fold(Router::new(), |router, version| {
+            router.nest(version.prefix(), version_routes(state, version))
+        })
+}
+
+/// Routes of one version: the shared endpoints plus its own location shape
+///
+/// Endpoints added after v1 are only mounted in the versions that have them.
+fn version_routes(state: &Arc<AppState>, version: ApiVersion) -> Router<Arc<AppState>> {
+    let (update_location, read_locations) = match version {
+        ApiVersion::V1 => (
+            post(locations::update_location),
+            get(locations::get_squad_locations),
+        ),
+        ApiVersion::V2 => (
+            post(locations::update_location_v2),
+            get(locations::get_squad_locations_v2),
+        ),
+    };
+
+    // Protected routes (require auth)
+    let mut protected_routes = Router::new().route("/locations", update_location);
+    if version == ApiVersion::V2 {
+        protected_routes = protected_routes
+            .route("/locations/batch", post(locations::upload_location_batch))
+            .route("/squads/:squad_id/quarantine", get(locations::get_quarantine))
+            .route(
+                "/squads/:squad_id/quarantine/release",
+                post(locations::release_quarantine),
+            )
+            .route(
+                "/squads/:squad_id/quarantine/discard",
+                post(locations::discard_quarantine),
+            )
+            .route(
+                "/squads/:squad_id/members/:member_id/track",
+                get(locations::get_member_track),
+            )
+            .route("/squads/:squad_id/alerts", get(alerts::get_alerts))
+            .route("/squads/:squad_id/stream", get(stream::squad_stream))
+            .route(
+                "/squads/:squad_id/messages",
+                get(messages::list_messages).post(messages::send_message),
+            )
+            .route(
+                "/squads/:squad_id/messages/:message_id",
+                delete(messages::delete_message),
+            )
+            .route(
+                "/squads/:squad_id/waypoints",
+                get(waypoints::list_waypoints).post(waypoints::create_waypoint),
+            )
+            .route(
+                "/squads/:squad_id/waypoints/:waypoint_id",
+                put(waypoints::update_waypoint).delete(waypoints::delete_waypoint),
+            )
+            .route("/squads/:squad_id/route", put(routes::set_route).delete(routes::delete_route))
+            .route("/squads/:squad_id/route/gpx", put(routes::import_gpx_route))
+            .route(
+                "/squads/:squad_id/webhooks",
+                get(webhooks::list_webhooks).post(webhooks::create_webhook),
+            )
+            .route("/squads/:squad_id/webhooks/:webhook_id", delete(webhooks::delete_webhook))
+            .route(
+                "/squads/:squad_id/webhooks/:webhook_id/deliveries",
+                get(webhooks::list_deliveries),
+            );
+    }
+    let protected_routes = protected_routes
+        .route("/sos", post(sos::raise_sos))
+        .route("/sos/:sos_id/acknowledge", post(sos::acknowledge_sos))
+        .route("/sos/:sos_id/resolve", post(sos::resolve_sos))
+        .route("/squads/:squad_id/sos", get(sos::list_sos))
+        .route("/squads/:squad_id/leave", post(squads::leave_squad))
+        .route("/squads/:squad_id", delete(squads::delete_squad))
+        .route("/squads/:squad_id/passphrase", put(squads::set_passphrase))
+        .layer(middleware::from_fn_with_state(state.clone(), auth_middleware));
+
+    // Admin routes (dashboard session + CSRF header required)
+    let admin_routes = Router::new()
+        .route("/admin/squads/:squad_id", delete(admin::delete_squad))
+        .route("/admin/squads/:squad_id/members/:member_id", delete(admin::kick_member))
+        .route("/admin/squads/:squad_id/rotate-code", post(admin::rotate_join_code))
+        .route(
+            "/admin/squads/:squad_id/webhooks",
+            get(admin::list_webhooks).post(admin::create_webhook),
+        )
+        .route("/admin/squads/:squad_id/webhooks/:webhook_id", delete(admin::delete_webhook))
+        .route(
+            "/admin/squads/:squad_id/webhooks/:webhook_id/deliveries",
+            get(admin::list_webhook_deliveries),
+        )
+        .route("/admin/members/:member_id/revoke-sessions", post(admin::revoke_member_sessions))
+        .route("/admin/locations/expire-stale", post(admin::expire_stale_locations))
+        .route("/admin/audit", get(admin::query_audit))
+        .route("/admin/audit/export", get(admin::export_audit))
+        .route("/admin/audit/verify", get(admin::verify_audit))
+        .route("/admin/api-versions", get(admin::api_version_usage))
+        .route(
+            "/admin/snapshot",
+            get(admin::export_snapshot)
+                .post(admin::import_snapshot)
+                .layer(DefaultBodyLimit::max(admin::MAX_SNAPSHOT_BYTES)),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), admin_auth_middleware));
+
+    // Public routes (no auth required)
+    let mut public_routes = Router::new();
+    if version == ApiVersion::V2 {
+        public_routes = public_routes
+            .route("/squads/:squad_id/proximity", get(locations::get_squad_proximity))
+            .route("/squads/:squad_id/route", get(routes::get_route))
+            .route("/squads/:squad_id/route/progress", get(routes::get_route_progress));
+    }
+    let public_routes = public_routes
+        .route("/health", get(health::health_check))
+        .route("/openapi.json", get(openapi::openapi_spec))
+        .route("/squads", post(squads::create_squad))
+        .route("/squads", get(squads::list_squads))
+        .route("/squads/:squad_id", get(squads::get_squad))
+        .route("/squads/:squad_id/join", post(squads::join_squad))
+        .route("/squads/:squad_id/locations", read_locations)
+        // Crypto test endpoints (omni-core-lite)
+        .route("/crypto/health", get(crypto::crypto_health))
+        .route("/crypto/echo", post(crypto::crypto_echo))
+        .route("/crypto/encrypt", post(crypto::crypto_encrypt))
+        .route("/crypto/decrypt", post(crypto::crypto_decrypt));
+
+    Router::new()
+        .merge(protected_routes)
+        .merge(admin_routes)
+        .merge(public_routes)
+}
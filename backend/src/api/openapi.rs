@@ -0,0 +1,165 @@
+//! OpenAPI document for the REST API
+//!
+//! Generated from the `#[utoipa::path]` annotations on the handlers and the
+//! `ToSchema` derives on the models, served at `/api/vN/openapi.json`. The
+//! committed `backend/openapi.json` must match; regenerate it with
+//! `squadz-server openapi > backend/openapi.json` after changing the API.
+
+use axum::Json;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use super::{
+    admin, alerts, crypto, health, locations, messages, routes, sos, squads, stream,
+    waypoints, webhooks,
+};
+use crate::services::admin_auth::{CSRF_HEADER, SESSION_COOKIE};
+use crate::models::WebhookPayload;
+use crate::validation::FieldError;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Squadz API",
+        description = "GPS squad tracking backend.\n\n\
+            Endpoints listed under `/api/v1` are also served under `/api/v2`. \
+            Location endpoints differ between versions and are listed for each; \
+            endpoints listed only under `/api/v2` are not available in v1. \
+            Responses from a deprecated version carry `Deprecation`, `Sunset` \
+            and successor `Link` headers."
+    ),
+    paths(
+        health::health_check,
+        squads::create_squad,
+        squads::list_squads,
+        squads::get_squad,
+        squads::delete_squad,
+        squads::join_squad,
+        squads::set_passphrase,
+        squads::leave_squad,
+        stream::squad_stream,
+        locations::update_location,
+        locations::get_squad_locations,
+        locations::update_location_v2,
+        locations::get_squad_locations_v2,
+        locations::upload_location_batch,
+        locations::get_member_track,
+        locations::get_squad_proximity,
+        locations::get_quarantine,
+        locations::release_quarantine,
+        locations::discard_quarantine,
+        alerts::get_alerts,
+        sos::raise_sos,
+        sos::list_sos,
+        sos::acknowledge_sos,
+        sos::resolve_sos,
+        messages::send_message,
+        messages::list_messages,
+        messages::delete_message,
+        waypoints::list_waypoints,
+        waypoints::create_waypoint,
+        waypoints::update_waypoint,
+        waypoints::delete_waypoint,
+        routes::get_route,
+        routes::get_route_progress,
+        routes::set_route,
+        routes::import_gpx_route,
+        routes::delete_route,
+        webhooks::create_webhook,
+        webhooks::list_webhooks,
+        webhooks::delete_webhook,
+        webhooks::list_deliveries,
+        admin::delete_squad,
+        admin::kick_member,
+        admin::rotate_join_code,
+        admin::create_webhook,
+        admin::list_webhooks,
+        admin::delete_webhook,
+        admin::list_webhook_deliveries,
+        admin::revoke_member_sessions,
+        admin::expire_stale_locations,
+        admin::query_audit,
+        admin::export_audit,
+        admin::verify_audit,
+        admin::api_version_usage,
+        admin::export_snapshot,
+        admin::import_snapshot,
+        crypto::crypto_health,
+        crypto::crypto_echo,
+        crypto::crypto_encrypt,
+        crypto::crypto_decrypt,
+    ),
+    components(schemas(FieldError, WebhookPayload)),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "squads", description = "Create, join and manage squads"),
+        (name = "locations", description = "Share and read member positions"),
+        (name = "alerts", description = "Separation alerts for squad leaders"),
+        (name = "sos", description = "Distress calls"),
+        (name = "messages", description = "Squad text messages"),
+        (name = "waypoints", description = "Shared squad markers"),
+        (name = "routes", description = "Planned routes and progress along them"),
+        (name = "webhooks", description = "Signed event deliveries to squad webhooks"),
+        (name = "admin", description = "Dashboard moderation (login cookie + CSRF header)"),
+        (name = "audit", description = "Audit log queries"),
+        (name = "crypto", description = "omni-core-lite crypto test endpoints"),
+        (name = "health", description = "Liveness"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the member API key and dashboard session security schemes
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "admin_session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(SESSION_COOKIE))),
+        );
+        components.add_security_scheme(
+            "csrf_token",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(CSRF_HEADER))),
+        );
+    }
+}
+
+/// The document as pretty-printed JSON, as committed to the repository
+pub fn openapi_json() -> String {
+    ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("OpenAPI document always serializes")
+}
+
+/// GET /api/vN/openapi.json - OpenAPI 3 description of this API
+pub async fn openapi_spec() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails when the committed spec is stale, so CI catches API changes
+    /// that weren't regenerated
+    #[test]
+    fn test_committed_spec_is_current() {
+        let committed = include_str!("../../openapi.json");
+        assert!(
+            committed.trim_end() == openapi_json().trim_end(),
+            "backend/openapi.json is out of date; run `squadz-server openapi > backend/openapi.json`"
+        );
+    }
+
+    #[test]
+    fn test_paths_use_openapi_templates() {
+        let doc = ApiDoc::openapi();
+        assert!(doc.paths.paths.contains_key("/api/v1/squads/{squad_id}/join"));
+        assert!(doc.paths.paths.keys().all(|p| !p.contains("/:")));
+    }
+}
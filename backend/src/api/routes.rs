@@ -0,0 +1,280 @@
+//! Planned route endpoints (v2)
+//!
+//! The leader uploads the route as JSON or as a GPX file; anyone who can see
+//! the squad's locations can read it and follow members' progress along it.
+//! Members who stray from it raise `off_route` alerts.
+
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::api::error::{ApiError, ErrorCode};
+use crate::api::extract::ValidJson;
+use crate::api::squads::require_leader;
+use crate::models::{default_max_deviation_m, PlannedRoute, RouteProgressResponse, RouteRequest};
+use crate::services::alerts;
+use crate::services::audit::AuditContext;
+use crate::services::auth::AuthenticatedMember;
+use crate::services::routes;
+use crate::validation;
+use crate::AppState;
+
+/// Route settings for a GPX import
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GpxImportQuery {
+    /// Route name; defaults to the first track or route name in the file
+    pub name: Option<String>,
+    /// Off-route threshold in metres (default 50)
+    pub max_deviation_m: Option<f64>,
+    pub finish_by: Option<DateTime<Utc>>,
+}
+
+/// The squad's planned route
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/route",
+    tag = "routes",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    responses(
+        (status = 200, description = "The planned route", body = PlannedRoute),
+        (status = 404, description = "Squad not found or has no route", body = ApiError),
+    )
+)]
+pub async fn get_route(
+    State(state): State<Arc<AppState>>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<PlannedRoute>, ApiError> {
+    squad_route(&state, &squad_id).await.map(Json)
+}
+
+/// Members' progress along the squad's planned route
+///
+/// Measured from each member's latest (smoothed, where enabled) position.
+/// Members are listed least progress first.
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/route/progress",
+    tag = "routes",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    responses(
+        (status = 200, description = "Cross-track distance, progress and ETA per member", body = RouteProgressResponse),
+        (status = 404, description = "Squad not found or has no route", body = ApiError),
+    )
+)]
+pub async fn get_route_progress(
+    State(state): State<Arc<AppState>>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<RouteProgressResponse>, ApiError> {
+    let route = squad_route(&state, &squad_id).await?;
+    let locations = state.location_store.read().await.get_squad_locations(&squad_id);
+    let now = Utc::now();
+    Ok(Json(RouteProgressResponse {
+        squad_id,
+        route_id: route.route_id,
+        length_m: route.length_m,
+        finish_by: route.finish_by,
+        members: routes::progress(&route, &locations, &state.route_progress, now),
+        updated_at: now,
+    }))
+}
+
+/// Set or replace the squad's planned route (leader only, requires auth)
+#[utoipa::path(
+    put,
+    path = "/api/v2/squads/{squad_id}/route",
+    tag = "routes",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = RouteRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Route set", body = PlannedRoute),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Invalid name, points or deviation", body = ApiError),
+    )
+)]
+pub async fn set_route(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<RouteRequest>,
+) -> Result<Json<PlannedRoute>, ApiError> {
+    store_route(&state, &auth, &ctx, squad_id, req).await
+}
+
+/// Set or replace the squad's planned route from a GPX file (leader only,
+/// requires auth)
+///
+/// Uses the file's track points, or its route points if it has no track.
+#[utoipa::path(
+    put,
+    path = "/api/v2/squads/{squad_id}/route/gpx",
+    tag = "routes",
+    params(("squad_id" = Uuid, Path, description = "Squad ID"), GpxImportQuery),
+    request_body(content = String, content_type = "application/gpx+xml"),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Route set", body = PlannedRoute),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Unreadable GPX, or invalid points or settings", body = ApiError),
+    )
+)]
+pub async fn import_gpx_route(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    Query(query): Query<GpxImportQuery>,
+    body: String,
+) -> Result<Json<PlannedRoute>, ApiError> {
+    // Before parsing, so only the leader learns what is wrong with a file
+    require_leader(&state, &auth.session, &squad_id).await?;
+    let (name, points) = routes::parse_gpx(&body)?;
+    let mut req = RouteRequest {
+        name: query.name.or(name).unwrap_or_else(|| "Route".to_string()),
+        points,
+        max_deviation_m: query.max_deviation_m.unwrap_or_else(default_max_deviation_m),
+        finish_by: query.finish_by,
+    };
+    validation::validate(&mut req)?;
+    store_route(&state, &auth, &ctx, squad_id, req).await
+}
+
+/// Remove the squad's planned route (leader only, requires auth)
+#[utoipa::path(
+    delete,
+    path = "/api/v2/squads/{squad_id}/route",
+    tag = "routes",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Route removed"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found or has no route", body = ApiError),
+    )
+)]
+pub async fn delete_route(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    require_leader(&state, &auth.session, &squad_id).await?;
+    state
+        .squad_manager
+        .write()
+        .await
+        .clear_route(&ctx, &squad_id, &auth.session.member_id)?;
+    // Clears any off-route alerts
+    alerts::check_squad(&state, &squad_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn squad_route(state: &AppState, squad_id: &Uuid) -> Result<PlannedRoute, ApiError> {
+    let manager = state.squad_manager.read().await;
+    let squad = manager
+        .get_squad(squad_id)
+        .ok_or_else(|| ApiError::new(ErrorCode::SquadNotFound, "Squad not found"))?;
+    squad
+        .route
+        .clone()
+        .ok_or_else(|| ApiError::new(ErrorCode::RouteNotFound, "Squad has no planned route"))
+}
+
+/// Save a validated route and re-check the squad's off-route alerts
+async fn store_route(
+    state: &AppState,
+    auth: &AuthenticatedMember,
+    ctx: &AuditContext,
+    squad_id: Uuid,
+    req: RouteRequest,
+) -> Result<Json<PlannedRoute>, ApiError> {
+    require_leader(state, &auth.session, &squad_id).await?;
+    let route = state
+        .squad_manager
+        .write()
+        .await
+        .set_route(ctx, &squad_id, &auth.session.member_id, req)?;
+    alerts::check_squad(state, &squad_id).await;
+    Ok(Json(route))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::{json, Value};
+
+    use crate::api::testing::TestApp;
+
+    fn route(points: &[(f64, f64)]) -> Value {
+        let points: Vec<_> =
+            points.iter().map(|(lat, lon)| json!({"latitude": lat, "longitude": lon})).collect();
+        json!({ "name": "Ridge", "points": points })
+    }
+
+    #[tokio::test]
+    async fn test_route_rejections() {
+        let app = TestApp::new();
+        let (squad_id, leader) = app.squad().await;
+        let scout = app.join(&squad_id, "Scout").await;
+        let uri = format!("/api/v2/squads/{squad_id}/route");
+        let gpx = format!("{uri}/gpx");
+        let valid = route(&[(40.0, -105.0), (40.01, -105.0)]);
+
+        let (status, error) = app.request(Method::GET, &uri, None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "route_not_found");
+        let progress = format!("{uri}/progress");
+        let (status, _) = app.request(Method::GET, &progress, None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, error) = app.request(Method::DELETE, &uri, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "route_not_found");
+
+        let (status, _) = app.request(Method::PUT, &uri, None, Some(valid.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, error) =
+            app.request(Method::PUT, &uri, Some(&scout), Some(valid.clone())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error["code"], "not_squad_leader");
+        // Leader only, even before the file is read
+        let (status, _) = app.request(Method::PUT, &gpx, Some(&scout), Some(json!("<gpx"))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        for (body, field) in [
+            (route(&[(40.0, -105.0)]), "points"),
+            (route(&[(40.0, -105.0), (95.0, -105.0)]), "points[1].latitude"),
+            (json!({ "name": "", "points": valid["points"] }), "name"),
+            (
+                json!({ "name": "Ridge", "points": valid["points"], "max_deviation_m": -1 }),
+                "max_deviation_m",
+            ),
+        ] {
+            let (status, error) = app.request(Method::PUT, &uri, Some(&leader), Some(body)).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{field}");
+            assert_eq!(error["details"]["errors"][0]["field"], field);
+        }
+        let (status, error) =
+            app.request(Method::PUT, &gpx, Some(&leader), Some(json!("<gpx"))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["code"], "invalid_gpx");
+
+        let (status, _) = app.request(Method::PUT, &uri, Some(&leader), Some(valid)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = app.request(Method::DELETE, &uri, Some(&scout), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}
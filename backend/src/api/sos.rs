@@ -0,0 +1,232 @@
+//! Distress call endpoints
+//!
+//! Offered in every API version with the same [`GeoPoint`] payload. Raising
+//! a call stores the position as a location update that skips quarantine
+//! and the accuracy cutoff, and asks the device to report more often until
+//! the call is resolved. Members follow calls on the squad stream.
+//!
+//! [`GeoPoint`]: crate::models::GeoPoint
+
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, State},
+    Json,
+};
+use chrono::Utc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::api::error::ApiError;
+use crate::api::extract::ValidJson;
+use crate::api::locations::{distress_settings, member_context};
+use crate::api::squads::require_member;
+use crate::models::{DistressCall, SosRequest, SosResponse, SquadEvent, SquadSosResponse};
+use crate::services::alerts;
+use crate::services::audit::AuditContext;
+use crate::services::auth::AuthenticatedMember;
+use crate::services::sos::SOS_UPDATE_INTERVAL_SECS;
+use crate::AppState;
+
+/// Raise a distress call, or renew your open one (requires auth)
+///
+/// The call is pushed to every squad member and repeated until someone
+/// acknowledges it.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sos",
+    tag = "sos",
+    request_body = SosRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Call raised or renewed", body = SosResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "No longer a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Invalid coordinates or message", body = ApiError),
+    )
+)]
+pub async fn raise_sos(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    ValidJson(req): ValidJson<SosRequest>,
+) -> Result<Json<SosResponse>, ApiError> {
+    let session = auth.session;
+    let (display_name, settings) = member_context(&state, &session).await?;
+
+    // The call goes out even if the fix itself can't be stored
+    let location = req.location;
+    let stored = state.location_store.write().await.update_location(
+        session.squad_id,
+        session.member_id,
+        display_name.clone(),
+        location,
+        &distress_settings(settings),
+    );
+    if let Err(e) = stored {
+        warn!(member_id = %session.member_id, "SOS position not stored: {}", e);
+    }
+
+    let call = state.sos.raise(
+        &ctx,
+        session.squad_id,
+        session.member_id,
+        display_name,
+        req,
+        Utc::now(),
+    );
+    state.feed.publish(SquadEvent::Sos(call.clone()));
+    alerts::check_squad(&state, &session.squad_id).await;
+
+    Ok(Json(SosResponse {
+        call,
+        update_interval_secs: SOS_UPDATE_INTERVAL_SECS,
+    }))
+}
+
+/// Open distress calls of a squad (members only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v1/squads/{squad_id}/sos",
+    tag = "sos",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Unresolved calls, oldest first", body = SquadSosResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn list_sos(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<SquadSosResponse>, ApiError> {
+    require_member(&state, &auth.session, &squad_id).await?;
+    Ok(Json(SquadSosResponse {
+        squad_id,
+        calls: state.sos.squad_calls(&squad_id),
+    }))
+}
+
+/// Acknowledge another member's distress call (requires auth)
+///
+/// Stops the call being repeated; it stays open until resolved.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sos/{sos_id}/acknowledge",
+    tag = "sos",
+    params(("sos_id" = Uuid, Path, description = "Distress call ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Call acknowledged", body = DistressCall),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "No longer a member of the squad", body = ApiError),
+        (status = 404, description = "No open call with this ID in your squad", body = ApiError),
+        (status = 409, description = "The call is your own", body = ApiError),
+    )
+)]
+pub async fn acknowledge_sos(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(sos_id): Path<Uuid>,
+) -> Result<Json<DistressCall>, ApiError> {
+    let session = auth.session;
+    require_member(&state, &session, &session.squad_id).await?;
+    let call = state
+        .sos
+        .acknowledge(&ctx, &sos_id, &session.squad_id, session.member_id, Utc::now())?;
+    state.feed.publish(SquadEvent::Sos(call.clone()));
+    Ok(Json(call))
+}
+
+/// Resolve a distress call (the caller or the squad leader, requires auth)
+#[utoipa::path(
+    post,
+    path = "/api/v1/sos/{sos_id}/resolve",
+    tag = "sos",
+    params(("sos_id" = Uuid, Path, description = "Distress call ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Call resolved", body = DistressCall),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Neither the caller nor the squad leader", body = ApiError),
+        (status = 404, description = "No open call with this ID in your squad", body = ApiError),
+    )
+)]
+pub async fn resolve_sos(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(sos_id): Path<Uuid>,
+) -> Result<Json<DistressCall>, ApiError> {
+    let session = auth.session;
+    let member = require_member(&state, &session, &session.squad_id).await?;
+    let call = state.sos.resolve(
+        &ctx,
+        &sos_id,
+        &session.squad_id,
+        session.member_id,
+        member.is_leader,
+        Utc::now(),
+    )?;
+    state.feed.publish(SquadEvent::Sos(call.clone()));
+    Ok(Json(call))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use crate::api::testing::TestApp;
+
+    #[tokio::test]
+    async fn test_sos_rejections() {
+        let app = TestApp::new();
+        let (squad_id, leader) = app.squad().await;
+        let scout = app.join(&squad_id, "Scout").await;
+        let medic = app.join(&squad_id, "Medic").await;
+        let (_, outsider) = app.squad().await;
+        let sos = |latitude: f64| json!({ "location": {"latitude": latitude, "longitude": 0.0} });
+
+        let (status, _) = app.request(Method::POST, "/api/v1/sos", None, Some(sos(1.0))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, error) =
+            app.request(Method::POST, "/api/v1/sos", Some(&scout), Some(sos(91.0))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["code"], "validation_failed");
+
+        let (status, raised) =
+            app.request(Method::POST, "/api/v1/sos", Some(&scout), Some(sos(1.0))).await;
+        assert_eq!(status, StatusCode::OK);
+        let sos_id = raised["call"]["sos_id"].as_str().unwrap();
+        let acknowledge = format!("/api/v1/sos/{sos_id}/acknowledge");
+        let resolve = format!("/api/v1/sos/{sos_id}/resolve");
+
+        let (status, error) = app.request(Method::POST, &acknowledge, Some(&scout), None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(error["code"], "cannot_acknowledge_own_sos");
+        let (status, error) = app.request(Method::POST, &acknowledge, Some(&outsider), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "sos_not_found");
+        let unknown = format!("/api/v1/sos/{}/acknowledge", Uuid::new_v4());
+        let (status, _) = app.request(Method::POST, &unknown, Some(&medic), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, error) = app.request(Method::POST, &resolve, Some(&medic), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error["code"], "forbidden");
+        let listed = format!("/api/v1/squads/{squad_id}/sos");
+        let (status, _) = app.request(Method::GET, &listed, Some(&outsider), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = app.request(Method::POST, &resolve, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = app.request(Method::POST, &resolve, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -2,27 +2,55 @@
 
 use std::sync::Arc;
 use axum::{
-    extract::{Path, State},
+    extract::{Extension, Path, State},
     http::StatusCode,
     Json,
 };
+use chrono::Utc;
 use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::models::{CreateSquadRequest, CreateSquadResponse, JoinSquadRequest, JoinSquadResponse, Squad};
-use crate::services::squad_manager::SquadError;
+use crate::api::error::{ApiError, ErrorCode};
+use crate::api::extract::ValidJson;
+use crate::models::{
+    CreateSquadRequest, CreateSquadResponse, JoinSquadRequest, JoinSquadResponse, Member,
+    SetPassphraseRequest, Squad, WebhookEventKind,
+};
+use crate::services::audit::AuditContext;
+use crate::services::auth::AuthenticatedMember;
+use crate::services::password;
+use crate::services::session::MemberSession;
+use crate::services::squad_manager::{PassphraseCheck, SquadError};
 use crate::AppState;
 
 /// Create a new squad
+#[utoipa::path(
+    post,
+    path = "/api/v1/squads",
+    tag = "squads",
+    request_body = CreateSquadRequest,
+    responses(
+        (status = 200, description = "Squad created; the caller is its leader", body = CreateSquadResponse),
+        (status = 422, description = "Invalid name, settings or passphrase", body = ApiError),
+    )
+)]
 pub async fn create_squad(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<CreateSquadRequest>,
-) -> Result<Json<CreateSquadResponse>, (StatusCode, String)> {
+    ctx: AuditContext,
+    ValidJson(req): ValidJson<CreateSquadRequest>,
+) -> Result<Json<CreateSquadResponse>, ApiError> {
+    let passphrase_hash = hash_passphrase(req.passphrase)?;
+
     let mut manager = state.squad_manager.write().await;
-    let (squad, member_id) = manager.create_squad(req.name, req.leader_name, req.settings);
+    let (squad, member_id) =
+        manager.create_squad(&ctx, req.name, req.leader_name, req.settings, passphrase_hash);
 
-    // Create session for the leader (1 hour TTL)
-    let session = state.session_store.create(member_id, squad.squad_id, 3600);
+    // Create session for the leader
+    let session = state
+        .session_store
+        .create(&ctx, member_id, squad.squad_id, state.config.session_ttl_secs);
 
     Ok(Json(CreateSquadResponse {
         squad_id: squad.squad_id,
@@ -33,6 +61,12 @@ pub async fn create_squad(
 }
 
 /// List all squads (debug endpoint)
+#[utoipa::path(
+    get,
+    path = "/api/v1/squads",
+    tag = "squads",
+    responses((status = 200, description = "All squads", body = Vec<Squad>))
+)]
 pub async fn list_squads(
     State(state): State<Arc<AppState>>,
 ) -> Json<Vec<Squad>> {
@@ -41,95 +75,341 @@ pub async fn list_squads(
 }
 
 /// Get a squad by ID
+#[utoipa::path(
+    get,
+    path = "/api/v1/squads/{squad_id}",
+    tag = "squads",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    responses(
+        (status = 200, description = "The squad", body = Squad),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
 pub async fn get_squad(
     State(state): State<Arc<AppState>>,
     Path(squad_id): Path<Uuid>,
-) -> Result<Json<Squad>, (StatusCode, String)> {
+) -> Result<Json<Squad>, ApiError> {
     let manager = state.squad_manager.read().await;
     manager
         .get_squad(&squad_id)
         .cloned()
         .map(Json)
-        .ok_or((StatusCode::NOT_FOUND, "Squad not found".to_string()))
+        .ok_or_else(|| ApiError::new(ErrorCode::SquadNotFound, "Squad not found"))
 }
 
 /// Delete a squad
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct DeleteSquadRequest {
     pub member_id: Uuid,
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/v1/squads/{squad_id}",
+    tag = "squads",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = DeleteSquadRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Squad and everything tied to it deleted"),
+        (status = 403, description = "Caller is not the leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
 pub async fn delete_squad(
     State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
     Path(squad_id): Path<Uuid>,
     Json(req): Json<DeleteSquadRequest>,
-) -> Result<StatusCode, (StatusCode, String)> {
-    let mut manager = state.squad_manager.write().await;
-    manager
-        .delete_squad(&squad_id, &req.member_id)
-        .map(|_| {
-            // Also clean up locations
-            drop(manager);
-            tokio::spawn(async move {
-                // Would clean up location store here
-            });
-            StatusCode::NO_CONTENT
-        })
-        .map_err(|e| match e {
-            SquadError::SquadNotFound => (StatusCode::NOT_FOUND, e.to_string()),
-            SquadError::NotLeader => (StatusCode::FORBIDDEN, e.to_string()),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-        })
+) -> Result<StatusCode, ApiError> {
+    state
+        .squad_manager
+        .write()
+        .await
+        .delete_squad(&ctx, &squad_id, &req.member_id)?;
+    purge_squad(&state, &ctx, &squad_id).await;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// Join a squad
+#[utoipa::path(
+    post,
+    path = "/api/v1/squads/{squad_id}/join",
+    tag = "squads",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = JoinSquadRequest,
+    responses(
+        (status = 200, description = "Joined; returns the member's API key", body = JoinSquadResponse),
+        (status = 400, description = "Join code belongs to another squad", body = ApiError),
+        (status = 401, description = "Squad requires a passphrase", body = ApiError),
+        (status = 403, description = "Wrong passphrase", body = ApiError),
+        (status = 404, description = "Invalid join code", body = ApiError),
+        (status = 409, description = "Display name taken or squad full", body = ApiError),
+        (status = 422, description = "Invalid join code or display name", body = ApiError),
+        (status = 429, description = "Too many wrong passphrases; try again later", body = ApiError),
+    )
+)]
 pub async fn join_squad(
     State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
     Path(squad_id): Path<Uuid>,
-    Json(req): Json<JoinSquadRequest>,
-) -> Result<Json<JoinSquadResponse>, (StatusCode, String)> {
-    let mut manager = state.squad_manager.write().await;
+    ValidJson(req): ValidJson<JoinSquadRequest>,
+) -> Result<Json<JoinSquadResponse>, ApiError> {
+    let (code_squad_id, hash) = state
+        .squad_manager
+        .read()
+        .await
+        .join_passphrase(&req.join_code, Utc::now())?;
 
     // Verify squad_id matches the join code's squad
-    let code_squad = manager.get_squad_by_code(&req.join_code);
-    if let Some(s) = code_squad {
-        if s.squad_id != squad_id {
-            return Err((StatusCode::BAD_REQUEST, "Join code does not match squad".to_string()));
-        }
+    if code_squad_id != squad_id {
+        return Err(ApiError::new(
+            ErrorCode::JoinCodeMismatch,
+            "Join code does not match squad",
+        ));
     }
 
-    manager
-        .join_squad(&req.join_code, req.display_name)
+    // Argon2 is slow by design: verify on a blocking thread, without the lock
+    let passphrase = req.passphrase.clone();
+    let check = tokio::task::spawn_blocking(move || {
+        PassphraseCheck::verify(hash.as_deref(), passphrase.as_deref())
+    })
+    .await
+    .map_err(|_| ApiError::internal("Failed to verify passphrase"))?;
+
+    let display_name = req.display_name.clone();
+    state
+        .squad_manager
+        .write()
+        .await
+        .join_squad(&ctx, &req.join_code, req.display_name, check)
         .map(|(squad, member_id)| {
-            // Create session for the new member (1 hour TTL)
-            let session = state.session_store.create(member_id, squad.squad_id, 3600);
+            // Create session for the new member
+            let session = state
+                .session_store
+                .create(&ctx, member_id, squad.squad_id, state.config.session_ttl_secs);
+            state.webhooks.emit(
+                squad.squad_id,
+                WebhookEventKind::MemberJoined,
+                json!({ "member_id": member_id, "display_name": display_name }),
+                Utc::now(),
+            );
             Json(JoinSquadResponse { member_id, squad, api_key: session.api_key })
         })
-        .map_err(|e| match e {
-            SquadError::InvalidJoinCode => (StatusCode::NOT_FOUND, e.to_string()),
-            SquadError::NameTaken => (StatusCode::CONFLICT, e.to_string()),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-        })
+        .map_err(ApiError::from)
+}
+
+/// Set or clear the squad join passphrase (leader only, requires auth)
+#[utoipa::path(
+    put,
+    path = "/api/v1/squads/{squad_id}/passphrase",
+    tag = "squads",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = SetPassphraseRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Passphrase set or cleared"),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 422, description = "Passphrase too long", body = ApiError),
+    )
+)]
+pub async fn set_passphrase(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<SetPassphraseRequest>,
+) -> Result<StatusCode, ApiError> {
+    let session = auth.session;
+    if session.squad_id != squad_id {
+        return Err(ApiError::new(ErrorCode::NotSquadMember, "Not a member of this squad"));
+    }
+
+    let passphrase_hash = hash_passphrase(req.passphrase)?;
+
+    let mut manager = state.squad_manager.write().await;
+    manager
+        .set_passphrase(&ctx, &squad_id, &session.member_id, passphrase_hash)
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(ApiError::from)
+}
+
+/// Check the session belongs to the leader of `squad_id`
+pub(crate) async fn require_leader(
+    state: &AppState,
+    session: &MemberSession,
+    squad_id: &Uuid,
+) -> Result<(), ApiError> {
+    if session.squad_id != *squad_id {
+        return Err(ApiError::new(ErrorCode::NotSquadMember, "Not a member of this squad"));
+    }
+    let manager = state.squad_manager.read().await;
+    let squad = manager
+        .get_squad(squad_id)
+        .ok_or_else(|| ApiError::new(ErrorCode::SquadNotFound, "Squad not found"))?;
+    if squad.leader_id != session.member_id {
+        return Err(SquadError::NotLeader.into());
+    }
+    Ok(())
+}
+
+/// Check the session belongs to a current member of `squad_id`
+pub(crate) async fn require_member(
+    state: &AppState,
+    session: &MemberSession,
+    squad_id: &Uuid,
+) -> Result<Member, ApiError> {
+    if session.squad_id != *squad_id {
+        return Err(ApiError::new(ErrorCode::NotSquadMember, "Not a member of this squad"));
+    }
+    let manager = state.squad_manager.read().await;
+    let squad = manager
+        .get_squad(squad_id)
+        .ok_or_else(|| ApiError::new(ErrorCode::SquadNotFound, "Squad not found"))?;
+    squad
+        .members
+        .iter()
+        .find(|m| m.member_id == session.member_id)
+        .cloned()
+        .ok_or_else(|| ApiError::new(ErrorCode::NotSquadMember, "Not a member of this squad"))
+}
+
+/// Clear everything tied to a squad that was just deleted: locations,
+/// sessions, webhooks, messages, distress calls, alerts and route progress
+///
+/// The squad manager drops the squad's join failures along with it.
+pub(crate) async fn purge_squad(state: &AppState, ctx: &AuditContext, squad_id: &Uuid) {
+    state.location_store.write().await.remove_squad(squad_id);
+    state.session_store.revoke_squad(ctx, squad_id);
+    state.webhooks.remove_squad(squad_id);
+    state.messages.remove_squad(squad_id);
+    state.sos.remove_squad(squad_id);
+    state.alerts.remove_squad(squad_id);
+    let route_ids = state
+        .squad_manager
+        .read()
+        .await
+        .list_squads()
+        .iter()
+        .filter_map(|squad| squad.route.as_ref().map(|route| route.route_id))
+        .collect();
+    state.route_progress.retain_routes(&route_ids);
+}
+
+/// Hash a request passphrase; empty passphrases mean "no passphrase"
+fn hash_passphrase(passphrase: Option<String>) -> Result<Option<String>, ApiError> {
+    passphrase
+        .filter(|p| !p.is_empty())
+        .map(|p| password::hash_password(&p))
+        .transpose()
+        .map_err(|_| ApiError::internal("Failed to hash passphrase"))
 }
 
 /// Leave a squad
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LeaveSquadRequest {
     pub member_id: Uuid,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/squads/{squad_id}/leave",
+    tag = "squads",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = LeaveSquadRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Left the squad; a leader leaving deletes it"),
+        (status = 404, description = "Squad or member not found", body = ApiError),
+    )
+)]
 pub async fn leave_squad(
     State(state): State<Arc<AppState>>,
+    ctx: AuditContext,
     Path(squad_id): Path<Uuid>,
     Json(req): Json<LeaveSquadRequest>,
-) -> Result<StatusCode, (StatusCode, String)> {
+) -> Result<StatusCode, ApiError> {
     let mut manager = state.squad_manager.write().await;
-    manager
-        .leave_squad(&squad_id, &req.member_id)
-        .map(|_| StatusCode::NO_CONTENT)
-        .map_err(|e| match e {
-            SquadError::SquadNotFound => (StatusCode::NOT_FOUND, e.to_string()),
-            SquadError::MemberNotFound => (StatusCode::NOT_FOUND, e.to_string()),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-        })
+    let display_name = manager.get_squad(&squad_id).and_then(|squad| {
+        squad
+            .members
+            .iter()
+            .find(|m| m.member_id == req.member_id)
+            .map(|m| m.display_name.clone())
+    });
+    manager.leave_squad(&ctx, &squad_id, &req.member_id)?;
+    // The squad goes with its leader
+    let deleted = manager.get_squad(&squad_id).is_none();
+    drop(manager);
+    if deleted {
+        purge_squad(&state, &ctx, &squad_id).await;
+        return Ok(StatusCode::NO_CONTENT);
+    }
+    state.location_store.write().await.remove_member(&squad_id, &req.member_id);
+    state.webhooks.emit(
+        squad_id,
+        WebhookEventKind::MemberLeft,
+        json!({ "member_id": req.member_id, "display_name": display_name, "reason": "left" }),
+        Utc::now(),
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    use crate::api::testing::TestApp;
+    use crate::services::audit::AuditContext;
+
+    #[tokio::test]
+    async fn test_public_squad_hides_waypoints() {
+        let app = TestApp::new();
+        let (squad_id, key) = app.squad().await;
+        let waypoint = json!({
+            "name": "Camp",
+            "icon": "rally_point",
+            "location": {"latitude": 40.0, "longitude": -105.0},
+        });
+        let uri = format!("/api/v2/squads/{squad_id}/waypoints");
+        let (status, _) = app.request(Method::POST, &uri, Some(&key), Some(waypoint)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let public = format!("/api/v1/squads/{squad_id}");
+        let (status, squad) = app.request(Method::GET, &public, None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(squad["squad_id"], squad_id.to_string());
+        assert!(squad.get("waypoints").is_none());
+        let (_, squads) = app.request(Method::GET, "/api/v2/squads", None, None).await;
+        assert!(squads[0].get("waypoints").is_none());
+
+        // Members still see them
+        let (_, listed) = app.request(Method::GET, &uri, Some(&key), None).await;
+        assert_eq!(listed["waypoints"][0]["name"], "Camp");
+    }
+
+    #[tokio::test]
+    async fn test_leader_leaving_purges_squad() {
+        let app = TestApp::new();
+        let (squad_id, key) = app.squad().await;
+        let leader_id = app.state.session_store.validate(&key).unwrap().member_id;
+        let sos = json!({ "location": {"latitude": 40.0, "longitude": -105.0} });
+        let (status, _) = app.request(Method::POST, "/api/v1/sos", Some(&key), Some(sos)).await;
+        assert_eq!(status, StatusCode::OK);
+        let url = "https://example.com/hook".to_string();
+        let ctx = AuditContext::system();
+        app.state.webhooks.create(&ctx, squad_id, url, vec![]).unwrap();
+
+        let uri = format!("/api/v1/squads/{squad_id}/leave");
+        let body = json!({ "member_id": leader_id });
+        let (status, _) = app.request(Method::POST, &uri, Some(&key), Some(body)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        assert!(app.state.location_store.read().await.get_squad_locations(&squad_id).is_empty());
+        assert!(app.state.sos.squad_calls(&squad_id).is_empty());
+        assert!(app.state.webhooks.list(&squad_id).is_empty());
+        assert!(app.state.session_store.validate(&key).is_none());
+    }
 }
@@ -0,0 +1,198 @@
+//! Live squad stream (v2, members only)
+//!
+//! A WebSocket carrying every [`SquadEvent`] of the member's squad as JSON
+//! text messages; separation alerts go to the leader only. Open distress
+//! calls are sent first on connect, so a member who reconnects doesn't wait
+//! for the next repeat to learn about them. A connection that falls too far
+//! behind gets a `resync` event followed by the open calls again. Missed
+//! messages are fetched with `since` from the messages endpoint.
+//!
+//! When the server shuts down the socket is closed with 1001 (going away).
+//! Membership is checked again every few seconds; once the member leaves, is
+//! kicked, loses their session or the squad is deleted, the socket is closed
+//! with 1008 (policy violation).
+
+use std::sync::Arc;
+use std::time::Duration;
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Extension, Path, State,
+    },
+    response::Response,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::api::error::ApiError;
+use crate::api::squads::require_member;
+use crate::models::SquadEvent;
+use crate::services::auth::AuthenticatedMember;
+use crate::services::session::MemberSession;
+use crate::AppState;
+
+/// How often an open stream checks that its member still belongs to the squad
+const MEMBERSHIP_CHECK: Duration = Duration::from_secs(2);
+
+/// Live squad events over a WebSocket (members only, requires auth)
+///
+/// Each text message is one [`SquadEvent`] as JSON.
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/stream",
+    tag = "squads",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 101, description = "Upgraded; squad events follow as JSON text messages", body = SquadEvent),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn squad_stream(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let member = require_member(&state, &auth.session, &squad_id).await?;
+    let session = auth.session;
+    Ok(ws.on_upgrade(move |socket| forward_events(socket, state, session, member.is_leader)))
+}
+
+async fn forward_events(
+    mut socket: WebSocket,
+    state: Arc<AppState>,
+    session: MemberSession,
+    is_leader: bool,
+) {
+    let squad_id = session.squad_id;
+    // Subscribe before reading the open calls so nothing falls in between
+    let mut events = state.feed.subscribe(squad_id);
+    if send_open_calls(&mut socket, &state, &squad_id).await.is_err() {
+        return;
+    }
+    let shutdown = state.shutdown.clone().requested();
+    tokio::pin!(shutdown);
+    let mut recheck = interval_at(Instant::now() + MEMBERSHIP_CHECK, MEMBERSHIP_CHECK);
+    recheck.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if is_leader || !event.leader_only() => {
+                    if send(&mut socket, &event).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(%squad_id, missed, "Squad stream fell behind");
+                    let resync = SquadEvent::Resync { squad_id, missed };
+                    if send(&mut socket, &resync).await.is_err()
+                        || send_open_calls(&mut socket, &state, &squad_id).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = recheck.tick() => {
+                if !still_member(&state, &session).await {
+                    close(&mut socket, close_code::POLICY, "No longer a member of this squad").await;
+                    break;
+                }
+            }
+            _ = &mut shutdown => {
+                close(&mut socket, close_code::AWAY, "Server shutting down").await;
+                break;
+            }
+        }
+    }
+}
+
+/// Whether the session behind the stream is still live and its member still
+/// in the squad
+async fn still_member(state: &AppState, session: &MemberSession) -> bool {
+    state.session_store.is_active(&session.api_key)
+        && require_member(state, session, &session.squad_id).await.is_ok()
+}
+
+async fn close(socket: &mut WebSocket, code: u16, reason: &'static str) {
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        })))
+        .await;
+}
+
+async fn send_open_calls(
+    socket: &mut WebSocket,
+    state: &AppState,
+    squad_id: &Uuid,
+) -> Result<(), axum::Error> {
+    for call in state.sos.squad_calls(squad_id) {
+        send(socket, &SquadEvent::Sos(call)).await?;
+    }
+    Ok(())
+}
+
+async fn send(socket: &mut WebSocket, event: &SquadEvent) -> Result<(), axum::Error> {
+    match serde_json::to_string(event) {
+        Ok(text) => socket.send(Message::Text(text)).await,
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message as WsMessage};
+
+    use super::*;
+    use crate::api::testing::TestApp;
+    use crate::services::audit::AuditContext;
+
+    #[tokio::test]
+    async fn test_kicked_member_stream_is_closed() {
+        let app = TestApp::new();
+        let (squad_id, _) = app.squad().await;
+        let key = app.join(&squad_id, "Scout").await;
+        let member_id = app.state.session_store.validate(&key).unwrap().member_id;
+        let addr = app.serve().await;
+
+        let mut request = format!("ws://{}/api/v2/squads/{}/stream", addr, squad_id)
+            .into_client_request()
+            .unwrap();
+        request
+            .headers_mut()
+            .insert("authorization", format!("Bearer {}", key).parse().unwrap());
+        let (mut stream, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+        // Kicked without revoking the session, so only the membership check can notice
+        app.state
+            .squad_manager
+            .write()
+            .await
+            .kick_member(&AuditContext::system(), &squad_id, &member_id)
+            .unwrap();
+
+        let message = tokio::time::timeout(MEMBERSHIP_CHECK * 3, stream.next())
+            .await
+            .expect("stream still open after the kick")
+            .unwrap()
+            .unwrap();
+        match message {
+            WsMessage::Close(Some(frame)) => assert_eq!(u16::from(frame.code), close_code::POLICY),
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+    }
+}
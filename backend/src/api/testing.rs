@@ -0,0 +1,98 @@
+//! Helpers for handler tests: a router over fresh state and shortcuts to
+//! create squads and members without going through the join flow
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::services::audit::AuditContext;
+use crate::services::squad_manager::PassphraseCheck;
+use crate::AppState;
+
+pub struct TestApp {
+    pub state: Arc<AppState>,
+    router: Router,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let state = AppState::for_tests();
+        let router = super::routes(&state).with_state(state.clone());
+        Self { state, router }
+    }
+
+    /// Create a squad; returns its ID and the leader's API key
+    pub async fn squad(&self) -> (Uuid, String) {
+        let ctx = AuditContext::system();
+        let (squad, leader_id) = self.state.squad_manager.write().await.create_squad(
+            &ctx,
+            "Alpha".to_string(),
+            "Lead".to_string(),
+            None,
+            None,
+        );
+        let session = self.state.session_store.create(&ctx, leader_id, squad.squad_id, 3600);
+        (squad.squad_id, session.api_key)
+    }
+
+    /// Add a member to the squad; returns their API key
+    pub async fn join(&self, squad_id: &Uuid, display_name: &str) -> String {
+        let ctx = AuditContext::system();
+        let mut manager = self.state.squad_manager.write().await;
+        let join_code = manager.get_squad(squad_id).unwrap().join_code.clone();
+        let (_, member_id) = manager
+            .join_squad(&ctx, &join_code, display_name.to_string(), PassphraseCheck::NotRequired)
+            .unwrap();
+        self.state.session_store.create(&ctx, member_id, *squad_id, 3600).api_key
+    }
+
+    /// Serve the router on a local port for clients that need a real
+    /// connection (WebSockets); returns its address
+    pub async fn serve(&self) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = self.router.clone();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        addr
+    }
+
+    /// Send a request, with a JSON body if given; returns the status and the
+    /// JSON answer (`null` if there is none)
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        api_key: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = api_key {
+            request = request.header("authorization", format!("Bearer {}", key));
+        }
+        let request = match body {
+            Some(body) => request
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, json)
+    }
+}
@@ -0,0 +1,243 @@
+//! API versions
+//!
+//! Each major version is mounted under `/api/vN`. Endpoints whose behavior is
+//! unchanged share one handler across versions; only changed endpoints get a
+//! version-specific handler. Requests are counted per version so we can see
+//! when an old version is safe to retire, and responses from a deprecated
+//! version carry `Deprecation` (RFC 9745), `Sunset` (RFC 8594) and a
+//! successor-version `Link` header.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// A major API version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    V1,
+    /// Flat location payloads with units in the field names
+    V2,
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    /// Path prefix the version is mounted under
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::V1 => "/api/v1",
+            Self::V2 => "/api/v2",
+        }
+    }
+
+    /// Split a request path into its version and the path below the prefix
+    fn split_path(path: &str) -> Option<(Self, &str)> {
+        Self::ALL.into_iter().find_map(|version| {
+            let rest = path.strip_prefix(version.prefix())?;
+            (rest.is_empty() || rest.starts_with('/')).then_some((version, rest))
+        })
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Deprecation schedule for one version
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VersionPolicy {
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+/// Usage counters and deprecation schedule of one version
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VersionUsage {
+    pub version: ApiVersion,
+    pub requests: u64,
+    /// Responses with a 4xx status
+    pub client_errors: u64,
+    /// Responses with a 5xx status
+    pub server_errors: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    client_errors: AtomicU64,
+    server_errors: AtomicU64,
+}
+
+/// Per-version policies and request counters (cheap to clone)
+#[derive(Clone)]
+pub struct ApiVersions {
+    policies: [VersionPolicy; 2],
+    counters: Arc<[Counters; 2]>,
+}
+
+impl ApiVersions {
+    pub fn new() -> Self {
+        Self {
+            policies: [VersionPolicy::default(); 2],
+            counters: Arc::new(Default::default()),
+        }
+    }
+
+    /// Set the deprecation schedule of a version
+    pub fn with_policy(mut self, version: ApiVersion, policy: VersionPolicy) -> Self {
+        self.policies[version.index()] = policy;
+        self
+    }
+
+    pub fn policy(&self, version: ApiVersion) -> VersionPolicy {
+        self.policies[version.index()]
+    }
+
+    fn record(&self, version: ApiVersion, status: StatusCode) {
+        let counters = &self.counters[version.index()];
+        counters.requests.fetch_add(1, Ordering::Relaxed);
+        if status.is_client_error() {
+            counters.client_errors.fetch_add(1, Ordering::Relaxed);
+        } else if status.is_server_error() {
+            counters.server_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counters for every version since startup
+    pub fn usage(&self) -> Vec<VersionUsage> {
+        ApiVersion::ALL
+            .into_iter()
+            .map(|version| {
+                let counters = &self.counters[version.index()];
+                let policy = self.policy(version);
+                VersionUsage {
+                    version,
+                    requests: counters.requests.load(Ordering::Relaxed),
+                    client_errors: counters.client_errors.load(Ordering::Relaxed),
+                    server_errors: counters.server_errors.load(Ordering::Relaxed),
+                    deprecated_at: policy.deprecated_at,
+                    sunset_at: policy.sunset_at,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for ApiVersions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Middleware that counts requests per version and stamps deprecation headers
+pub async fn track_version(
+    State(versions): State<ApiVersions>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some((version, rest)) = ApiVersion::split_path(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let rest = rest.to_string();
+
+    let mut response = next.run(request).await;
+    versions.record(version, response.status());
+    deprecation_headers(versions.policy(version), &rest, response.headers_mut());
+    response
+}
+
+fn deprecation_headers(policy: VersionPolicy, rest: &str, headers: &mut HeaderMap) {
+    let Some(deprecated_at) = policy.deprecated_at else {
+        return;
+    };
+
+    // RFC 9745 structured-field date: `@<unix seconds>`
+    let deprecation = format!("@{}", deprecated_at.timestamp());
+    if let Ok(value) = HeaderValue::from_str(&deprecation) {
+        headers.insert("deprecation", value);
+    }
+    if let Some(sunset_at) = policy.sunset_at {
+        let sunset = sunset_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(value) = HeaderValue::from_str(&sunset) {
+            headers.insert("sunset", value);
+        }
+    }
+
+    let successor = format!(
+        "<{}{}>; rel=\"successor-version\"",
+        ApiVersion::LATEST.prefix(),
+        rest
+    );
+    if let Ok(value) = HeaderValue::from_str(&successor) {
+        headers.append(header::LINK, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use chrono::TimeZone;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_split_path() {
+        assert_eq!(
+            ApiVersion::split_path("/api/v1/squads/abc"),
+            Some((ApiVersion::V1, "/squads/abc"))
+        );
+        assert_eq!(ApiVersion::split_path("/api/v2"), Some((ApiVersion::V2, "")));
+        assert_eq!(ApiVersion::split_path("/api/v10/squads"), None);
+        assert_eq!(ApiVersion::split_path("/dashboard/locations"), None);
+    }
+
+    #[tokio::test]
+    async fn test_deprecated_version_headers_and_counters() {
+        let versions = ApiVersions::new().with_policy(
+            ApiVersion::V1,
+            VersionPolicy {
+                deprecated_at: Some(Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()),
+                sunset_at: Some(Utc.with_ymd_and_hms(2026, 7, 1, 0, 0, 0).unwrap()),
+            },
+        );
+        let app = Router::new()
+            .route("/api/v1/squads", get(|| async { "v1" }))
+            .route("/api/v2/squads", get(|| async { "v2" }))
+            .layer(middleware::from_fn_with_state(versions.clone(), track_version));
+
+        let request = Request::get("/api/v1/squads").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let headers = response.headers();
+        assert_eq!(headers["deprecation"], "@1767225600");
+        assert_eq!(headers["sunset"], "Wed, 01 Jul 2026 00:00:00 GMT");
+        assert_eq!(headers[header::LINK], "</api/v2/squads>; rel=\"successor-version\"");
+
+        let request = Request::get("/api/v2/squads").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert!(response.headers().get("deprecation").is_none());
+
+        let request = Request::get("/api/v2/missing").body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap();
+
+        let usage = versions.usage();
+        assert_eq!(usage[0].requests, 1);
+        assert_eq!(usage[1].requests, 2);
+        assert_eq!(usage[1].client_errors, 1);
+    }
+}
@@ -0,0 +1,266 @@
+//! Waypoint endpoints (v2, members only)
+//!
+//! Any member can drop a waypoint; its creator or the squad leader can change
+//! or delete it. Changes and expiries are pushed on the squad stream.
+
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::api::error::ApiError;
+use crate::api::extract::ValidJson;
+use crate::api::squads::require_member;
+use crate::models::{SquadEvent, Waypoint, WaypointRequest, WaypointsResponse};
+use crate::services::audit::AuditContext;
+use crate::services::auth::AuthenticatedMember;
+use crate::AppState;
+
+/// Waypoints of the squad, oldest first (members only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/waypoints",
+    tag = "waypoints",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Unexpired waypoints, oldest first", body = WaypointsResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn list_waypoints(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<WaypointsResponse>, ApiError> {
+    require_member(&state, &auth.session, &squad_id).await?;
+    let now = Utc::now();
+    let manager = state.squad_manager.read().await;
+    let waypoints = manager
+        .get_squad(&squad_id)
+        .map(|squad| {
+            squad
+                .waypoints
+                .iter()
+                .filter(|waypoint| !waypoint.is_expired(now))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Json(WaypointsResponse {
+        squad_id,
+        waypoints,
+    }))
+}
+
+/// Drop a waypoint on the squad's map (members only, requires auth)
+#[utoipa::path(
+    post,
+    path = "/api/v2/squads/{squad_id}/waypoints",
+    tag = "waypoints",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = WaypointRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Waypoint added", body = Waypoint),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not a member of the squad", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 409, description = "Squad already has the maximum number of waypoints", body = ApiError),
+        (status = 422, description = "Invalid name, location, description or expiry", body = ApiError),
+    )
+)]
+pub async fn create_waypoint(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<WaypointRequest>,
+) -> Result<Json<Waypoint>, ApiError> {
+    let member = require_member(&state, &auth.session, &squad_id).await?;
+    let (waypoint, expired) = state
+        .squad_manager
+        .write()
+        .await
+        .add_waypoint(&ctx, &squad_id, &member, req)?;
+    for waypoint_id in expired {
+        state.feed.publish(SquadEvent::WaypointDeleted { squad_id, waypoint_id });
+    }
+    state.feed.publish(SquadEvent::Waypoint(waypoint.clone()));
+    Ok(Json(waypoint))
+}
+
+/// Replace a waypoint (its creator or the squad leader, requires auth)
+#[utoipa::path(
+    put,
+    path = "/api/v2/squads/{squad_id}/waypoints/{waypoint_id}",
+    tag = "waypoints",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("waypoint_id" = Uuid, Path, description = "Waypoint ID"),
+    ),
+    request_body = WaypointRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Waypoint updated", body = Waypoint),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Neither the creator nor the squad leader", body = ApiError),
+        (status = 404, description = "Squad or waypoint not found", body = ApiError),
+        (status = 422, description = "Invalid name, location, description or expiry", body = ApiError),
+    )
+)]
+pub async fn update_waypoint(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path((squad_id, waypoint_id)): Path<(Uuid, Uuid)>,
+    ValidJson(req): ValidJson<WaypointRequest>,
+) -> Result<Json<Waypoint>, ApiError> {
+    let member = require_member(&state, &auth.session, &squad_id).await?;
+    let waypoint = state
+        .squad_manager
+        .write()
+        .await
+        .update_waypoint(&ctx, &squad_id, &waypoint_id, &member, req)?;
+    state.feed.publish(SquadEvent::Waypoint(waypoint.clone()));
+    Ok(Json(waypoint))
+}
+
+/// Delete a waypoint (its creator or the squad leader, requires auth)
+#[utoipa::path(
+    delete,
+    path = "/api/v2/squads/{squad_id}/waypoints/{waypoint_id}",
+    tag = "waypoints",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("waypoint_id" = Uuid, Path, description = "Waypoint ID"),
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Waypoint deleted"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Neither the creator nor the squad leader", body = ApiError),
+        (status = 404, description = "Squad or waypoint not found", body = ApiError),
+    )
+)]
+pub async fn delete_waypoint(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path((squad_id, waypoint_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let member = require_member(&state, &auth.session, &squad_id).await?;
+    state
+        .squad_manager
+        .write()
+        .await
+        .delete_waypoint(&ctx, &squad_id, &waypoint_id, &member)?;
+    state.feed.publish(SquadEvent::WaypointDeleted {
+        squad_id,
+        waypoint_id,
+    });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use chrono::Duration;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::api::testing::TestApp;
+    use crate::models::{GeoPoint, WaypointIcon};
+
+    fn body(name: &str) -> Value {
+        json!({
+            "name": name,
+            "icon": "hazard",
+            "location": {"latitude": 40.0, "longitude": -105.0},
+        })
+    }
+
+    #[tokio::test]
+    async fn test_only_creator_or_leader_changes_waypoints() {
+        let app = TestApp::new();
+        let (squad_id, leader) = app.squad().await;
+        let scout = app.join(&squad_id, "Scout").await;
+        let medic = app.join(&squad_id, "Medic").await;
+        let (_, outsider) = app.squad().await;
+        let uri = format!("/api/v2/squads/{squad_id}/waypoints");
+
+        let (status, _) = app.request(Method::POST, &uri, None, Some(body("Ice"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = app.request(Method::POST, &uri, Some(&outsider), Some(body("Ice"))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = app.request(Method::GET, &uri, Some(&outsider), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, waypoint) =
+            app.request(Method::POST, &uri, Some(&scout), Some(body("Ice"))).await;
+        assert_eq!(status, StatusCode::OK);
+        let one = format!("{uri}/{}", waypoint["waypoint_id"].as_str().unwrap());
+
+        let (status, error) = app.request(Method::PUT, &one, Some(&medic), Some(body("X"))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error["code"], "forbidden");
+        let (status, _) = app.request(Method::DELETE, &one, Some(&medic), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, renamed) =
+            app.request(Method::PUT, &one, Some(&scout), Some(body("Mud"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(renamed["name"], "Mud");
+        let (status, _) = app.request(Method::DELETE, &one, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = app.request(Method::DELETE, &one, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_expired_waypoints_are_announced_when_pruned() {
+        let app = TestApp::new();
+        let (squad_id, leader) = app.squad().await;
+        let stale = {
+            let mut manager = app.state.squad_manager.write().await;
+            let creator = manager.get_squad(&squad_id).unwrap().members[0].clone();
+            let request = WaypointRequest {
+                name: "Old".to_string(),
+                icon: WaypointIcon::Other,
+                location: GeoPoint::at(40.0, -105.0),
+                description: None,
+                // Past expiries are refused by validation, not by the manager
+                expires_at: Some(Utc::now() - Duration::minutes(1)),
+            };
+            let ctx = AuditContext::system();
+            manager.add_waypoint(&ctx, &squad_id, &creator, request).unwrap().0
+        };
+        let mut events = app.state.feed.subscribe(squad_id);
+
+        let uri = format!("/api/v2/squads/{squad_id}/waypoints");
+        let (status, added) =
+            app.request(Method::POST, &uri, Some(&leader), Some(body("New"))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        match events.try_recv().unwrap() {
+            SquadEvent::WaypointDeleted { waypoint_id, .. } => {
+                assert_eq!(waypoint_id, stale.waypoint_id)
+            }
+            other => panic!("expected waypoint_deleted, got {other:?}"),
+        }
+        match events.try_recv().unwrap() {
+            SquadEvent::Waypoint(waypoint) => {
+                assert_eq!(waypoint.waypoint_id.to_string(), added["waypoint_id"])
+            }
+            other => panic!("expected waypoint, got {other:?}"),
+        }
+        let (_, listed) = app.request(Method::GET, &uri, Some(&leader), None).await;
+        assert_eq!(listed["waypoints"].as_array().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,195 @@
+//! Webhook endpoints (v2, leader only)
+//!
+//! Admins manage the same webhooks from the admin API. The signing secret is
+//! only returned when a webhook is created.
+
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::api::error::ApiError;
+use crate::api::extract::ValidJson;
+use crate::api::squads::require_leader;
+use crate::models::{
+    CreateWebhookRequest, CreateWebhookResponse, WebhookDeliveriesResponse, WebhooksResponse,
+};
+use crate::services::audit::AuditContext;
+use crate::services::auth::AuthenticatedMember;
+use crate::AppState;
+
+/// Register a webhook for the squad's events (leader only, requires auth)
+#[utoipa::path(
+    post,
+    path = "/api/v2/squads/{squad_id}/webhooks",
+    tag = "webhooks",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    request_body = CreateWebhookRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Webhook registered; the secret is not shown again", body = CreateWebhookResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+        (status = 409, description = "Squad already has the maximum number of webhooks", body = ApiError),
+        (status = 422, description = "Invalid URL, or its host is not a public address", body = ApiError),
+    )
+)]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path(squad_id): Path<Uuid>,
+    ValidJson(req): ValidJson<CreateWebhookRequest>,
+) -> Result<Json<CreateWebhookResponse>, ApiError> {
+    require_leader(&state, &auth.session, &squad_id).await?;
+    state.webhooks.check_destination(&req.url).await?;
+    let (webhook, secret) = state.webhooks.create(&ctx, squad_id, req.url, req.events)?;
+    Ok(Json(CreateWebhookResponse { webhook, secret }))
+}
+
+/// List the squad's webhooks (leader only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/webhooks",
+    tag = "webhooks",
+    params(("squad_id" = Uuid, Path, description = "Squad ID")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Registered webhooks, oldest first", body = WebhooksResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad not found", body = ApiError),
+    )
+)]
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path(squad_id): Path<Uuid>,
+) -> Result<Json<WebhooksResponse>, ApiError> {
+    require_leader(&state, &auth.session, &squad_id).await?;
+    Ok(Json(WebhooksResponse {
+        squad_id,
+        webhooks: state.webhooks.list(&squad_id),
+    }))
+}
+
+/// Remove a webhook (leader only, requires auth)
+#[utoipa::path(
+    delete,
+    path = "/api/v2/squads/{squad_id}/webhooks/{webhook_id}",
+    tag = "webhooks",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("webhook_id" = Uuid, Path, description = "Webhook ID"),
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Webhook removed; queued deliveries are dropped"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad or webhook not found", body = ApiError),
+    )
+)]
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    ctx: AuditContext,
+    Path((squad_id, webhook_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    require_leader(&state, &auth.session, &squad_id).await?;
+    state.webhooks.delete(&ctx, &squad_id, &webhook_id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Recent deliveries to a webhook, newest first (leader only, requires auth)
+#[utoipa::path(
+    get,
+    path = "/api/v2/squads/{squad_id}/webhooks/{webhook_id}/deliveries",
+    tag = "webhooks",
+    params(
+        ("squad_id" = Uuid, Path, description = "Squad ID"),
+        ("webhook_id" = Uuid, Path, description = "Webhook ID"),
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Delivery log", body = WebhookDeliveriesResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Caller is not the squad leader", body = ApiError),
+        (status = 404, description = "Squad or webhook not found", body = ApiError),
+    )
+)]
+pub async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthenticatedMember>,
+    Path((squad_id, webhook_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<WebhookDeliveriesResponse>, ApiError> {
+    require_leader(&state, &auth.session, &squad_id).await?;
+    Ok(Json(WebhookDeliveriesResponse {
+        webhook_id,
+        deliveries: state.webhooks.deliveries(&squad_id, &webhook_id)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use crate::api::testing::TestApp;
+    use crate::services::webhooks::MAX_WEBHOOKS_PER_SQUAD;
+
+    #[tokio::test]
+    async fn test_webhook_rejections() {
+        let app = TestApp::new();
+        let (squad_id, leader) = app.squad().await;
+        let scout = app.join(&squad_id, "Scout").await;
+        let uri = format!("/api/v2/squads/{squad_id}/webhooks");
+        // A public address literal, so no lookup is needed
+        let hook = |url: &str| json!({ "url": url });
+        let public = "https://93.184.216.34/hook";
+
+        let (status, _) = app.request(Method::POST, &uri, None, Some(hook(public))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, error) =
+            app.request(Method::POST, &uri, Some(&scout), Some(hook(public))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error["code"], "not_squad_leader");
+        let (status, _) = app.request(Method::GET, &uri, Some(&scout), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        for url in [
+            "ftp://93.184.216.34/hook",
+            "https://user:pw@93.184.216.34/",
+            "hook",
+            "http://10.0.0.1/",
+        ] {
+            let (status, error) =
+                app.request(Method::POST, &uri, Some(&leader), Some(hook(url))).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{url}");
+            assert_eq!(error["details"]["errors"][0]["field"], "url", "{url}");
+        }
+
+        for _ in 0..MAX_WEBHOOKS_PER_SQUAD {
+            let (status, _) =
+                app.request(Method::POST, &uri, Some(&leader), Some(hook(public))).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (status, error) =
+            app.request(Method::POST, &uri, Some(&leader), Some(hook(public))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(error["code"], "too_many_webhooks");
+
+        let unknown = format!("{uri}/{}", Uuid::new_v4());
+        let (status, error) = app.request(Method::DELETE, &unknown, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "webhook_not_found");
+        let deliveries = format!("{unknown}/deliveries");
+        let (status, _) = app.request(Method::GET, &deliveries, Some(&leader), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
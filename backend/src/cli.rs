@@ -0,0 +1,104 @@
+//! Command-line interface for squadz-server
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+
+use crate::config::{Config, ConfigOverrides};
+use crate::services::snapshot::{Snapshot, SnapshotFormat};
+
+#[derive(Debug, Parser)]
+#[command(name = "squadz-server", version, about = "Squadz GPS squad tracking backend")]
+pub struct Cli {
+    /// YAML config file (defaults to $SQUADZ_CONFIG, if set)
+    #[arg(long, short = 'c', value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Validate the configuration, print the effective values and exit
+    #[arg(long)]
+    pub check_config: bool,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Export or import the saved state snapshot (server must be stopped)
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+    /// Print the OpenAPI document and exit
+    Openapi,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotCommand {
+    /// Copy the snapshot at `snapshot_path` to a file, converting its format
+    Export {
+        /// Destination file
+        #[arg(long, short = 'o', value_name = "PATH")]
+        out: PathBuf,
+        #[arg(long, value_enum, default_value_t = SnapshotFormat::Json)]
+        format: SnapshotFormat,
+    },
+    /// Validate a JSON or binary snapshot and install it at `snapshot_path`
+    /// for the next start
+    Import {
+        /// Snapshot file to import
+        #[arg(value_name = "PATH")]
+        file: PathBuf,
+        /// Overwrite an existing snapshot
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl SnapshotCommand {
+    pub fn run(self, config: &Config) -> anyhow::Result<()> {
+        let snapshot_path = config
+            .snapshot_path
+            .as_deref()
+            .context("snapshot_path must be configured (SNAPSHOT_PATH or --snapshot-path)")?;
+
+        match self {
+            SnapshotCommand::Export { out, format } => {
+                let snapshot = Snapshot::load(snapshot_path)
+                    .with_context(|| format!("failed to read snapshot {}", snapshot_path))?
+                    .with_context(|| format!("no snapshot at {}", snapshot_path))?;
+                snapshot
+                    .save_as(&out, format)
+                    .with_context(|| format!("failed to write {}", out.display()))?;
+                println!(
+                    "Exported {} squads, {} locations, {} sessions to {}",
+                    snapshot.squads.len(),
+                    snapshot.locations.len(),
+                    snapshot.sessions.len(),
+                    out.display()
+                );
+            }
+            SnapshotCommand::Import { file, force } => {
+                if !force && std::path::Path::new(snapshot_path).exists() {
+                    anyhow::bail!("{} already exists; pass --force to replace it", snapshot_path);
+                }
+                let snapshot = Snapshot::load(&file)
+                    .with_context(|| format!("failed to read snapshot {}", file.display()))?
+                    .with_context(|| format!("{} does not exist", file.display()))?;
+                snapshot
+                    .save(snapshot_path)
+                    .with_context(|| format!("failed to write {}", snapshot_path))?;
+                println!(
+                    "Imported {} squads, {} locations, {} sessions into {}",
+                    snapshot.squads.len(),
+                    snapshot.locations.len(),
+                    snapshot.sessions.len(),
+                    snapshot_path
+                );
+            }
+        }
+        Ok(())
+    }
+}
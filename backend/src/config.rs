@@ -1,31 +1,932 @@
 //! Configuration for Squadz server
+//!
+//! Layered, later layers winning: built-in defaults, then an optional YAML
+//! file (`--config` or `SQUADZ_CONFIG`), then environment variables, then CLI
+//! flags. The merged result is validated and any bad value fails startup.
 
 use std::env;
+use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+use axum::http::{HeaderName, HeaderValue, Method};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+/// Wildcard entry for the CORS lists
+const CORS_ANY: &str = "*";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub host: String,
     pub port: u16,
+    /// Age after which a member's location is reported as stale
     pub location_ttl_secs: u64,
+    /// How far ahead of the server clock a client fix time may be
+    pub max_clock_skew_secs: u64,
+    /// Lifetime of a member API key
+    pub session_ttl_secs: u64,
     pub max_squad_size: usize,
+    /// Wrong join passphrases a squad accepts per `join_failure_window_secs`
+    /// before refusing joins
+    pub join_failure_limit: usize,
+    /// Window over which wrong join passphrases are counted
+    pub join_failure_window_secs: u64,
+    /// Fixes kept in each member's location history
+    pub location_history_points: usize,
+    /// Age after which history fixes are dropped; members with no newer fix
+    /// are forgotten
+    pub location_history_retention_secs: u64,
+    /// Messages kept per squad
+    pub message_retention: usize,
+    /// Hosts webhooks may reach even though they resolve to loopback, private
+    /// or link-local addresses; empty allows public addresses only
+    pub webhook_allowed_hosts: Vec<String>,
+    /// Username for the admin dashboard
+    pub dashboard_user: String,
+    /// Lifetime of a dashboard login session
+    pub dashboard_session_ttl_secs: u64,
+    /// Failed dashboard logins accepted per
+    /// `dashboard_login_failure_window_secs` before logins are refused
+    pub dashboard_login_failure_limit: usize,
+    /// Window over which failed dashboard logins are counted
+    pub dashboard_login_failure_window_secs: u64,
+    /// Mark dashboard cookies `Secure` (enable when served over HTTPS)
+    pub dashboard_secure_cookie: bool,
+    /// JSON Lines file the audit log is persisted to (in-memory only if unset)
+    pub audit_log_path: Option<String>,
+    /// Origins allowed to call the API cross-origin, or `["*"]` for any
+    pub cors_allowed_origins: Vec<String>,
+    /// Methods allowed cross-origin, or `["*"]` for any
+    pub cors_allowed_methods: Vec<String>,
+    /// Request headers allowed cross-origin, or `["*"]` for any
+    pub cors_allowed_headers: Vec<String>,
+    /// Allow cookies/credentials on cross-origin requests (needs explicit lists)
+    pub cors_allow_credentials: bool,
+    /// PEM certificate chain; serve HTTPS when set together with the key
+    pub tls_cert_path: Option<String>,
+    /// PEM private key for `tls_cert_path`
+    pub tls_key_path: Option<String>,
+    /// How often to check the certificate files for changes
+    pub tls_reload_interval_secs: u64,
+    /// Plain-HTTP port that redirects to HTTPS (disabled if unset)
+    pub tls_redirect_port: Option<u16>,
+    /// File state is saved to on shutdown and restored from on start
+    pub snapshot_path: Option<String>,
+    /// How long shutdown waits for in-flight requests to finish
+    pub shutdown_grace_secs: u64,
+    /// When `/api/v1` was deprecated; adds `Deprecation` headers to its responses
+    pub api_v1_deprecated_at: Option<DateTime<Utc>>,
+    /// When `/api/v1` is expected to be removed (`Sunset` header)
+    pub api_v1_sunset_at: Option<DateTime<Utc>>,
 }
 
-impl Config {
-    pub fn from_env() -> Self {
+impl Default for Config {
+    fn default() -> Self {
         Self {
-            host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-            port: env::var("PORT")
-                .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or(8080),
-            location_ttl_secs: env::var("LOCATION_TTL_SECS")
-                .ok()
-                .and_then(|t| t.parse().ok())
-                .unwrap_or(300), // 5 minutes default
-            max_squad_size: env::var("MAX_SQUAD_SIZE")
-                .ok()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(50),
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            location_ttl_secs: 300, // 5 minutes
+            max_clock_skew_secs: 30,
+            session_ttl_secs: 3600, // 1 hour
+            max_squad_size: 50,
+            join_failure_limit: 5,
+            join_failure_window_secs: 60,
+            location_history_points: 1000,
+            location_history_retention_secs: 24 * 3600, // 1 day
+            message_retention: 500,
+            webhook_allowed_hosts: Vec::new(),
+            dashboard_user: "admin".to_string(),
+            dashboard_session_ttl_secs: 3600, // 1 hour
+            dashboard_login_failure_limit: 5,
+            dashboard_login_failure_window_secs: 300, // 5 minutes
+            dashboard_secure_cookie: false,
+            audit_log_path: None,
+            // Permissive for development; lock down in production configs
+            cors_allowed_origins: vec![CORS_ANY.to_string()],
+            cors_allowed_methods: vec![CORS_ANY.to_string()],
+            cors_allowed_headers: vec![CORS_ANY.to_string()],
+            cors_allow_credentials: false,
+            tls_cert_path: None,
+            tls_key_path: None,
+            tls_reload_interval_secs: 30,
+            tls_redirect_port: None,
+            snapshot_path: None,
+            shutdown_grace_secs: 30,
+            api_v1_deprecated_at: None,
+            api_v1_sunset_at: None,
+        }
+    }
+}
+
+/// Command-line overrides, applied after the file and environment
+#[derive(Debug, Default, clap::Args)]
+pub struct ConfigOverrides {
+    #[arg(long)]
+    pub host: Option<String>,
+    #[arg(long)]
+    pub port: Option<u16>,
+    #[arg(long)]
+    pub location_ttl_secs: Option<u64>,
+    #[arg(long)]
+    pub max_clock_skew_secs: Option<u64>,
+    #[arg(long)]
+    pub session_ttl_secs: Option<u64>,
+    #[arg(long)]
+    pub max_squad_size: Option<usize>,
+    #[arg(long)]
+    pub join_failure_limit: Option<usize>,
+    #[arg(long)]
+    pub join_failure_window_secs: Option<u64>,
+    #[arg(long)]
+    pub location_history_points: Option<usize>,
+    #[arg(long)]
+    pub location_history_retention_secs: Option<u64>,
+    #[arg(long)]
+    pub message_retention: Option<usize>,
+    #[arg(long, value_delimiter = ',')]
+    pub webhook_allowed_hosts: Option<Vec<String>>,
+    #[arg(long)]
+    pub dashboard_user: Option<String>,
+    #[arg(long)]
+    pub dashboard_session_ttl_secs: Option<u64>,
+    #[arg(long)]
+    pub dashboard_login_failure_limit: Option<usize>,
+    #[arg(long)]
+    pub dashboard_login_failure_window_secs: Option<u64>,
+    #[arg(long)]
+    pub dashboard_secure_cookie: Option<bool>,
+    #[arg(long)]
+    pub audit_log_path: Option<String>,
+    #[arg(long, value_delimiter = ',')]
+    pub cors_allowed_origins: Option<Vec<String>>,
+    #[arg(long, value_delimiter = ',')]
+    pub cors_allowed_methods: Option<Vec<String>>,
+    #[arg(long, value_delimiter = ',')]
+    pub cors_allowed_headers: Option<Vec<String>>,
+    #[arg(long)]
+    pub cors_allow_credentials: Option<bool>,
+    #[arg(long)]
+    pub tls_cert_path: Option<String>,
+    #[arg(long)]
+    pub tls_key_path: Option<String>,
+    #[arg(long)]
+    pub tls_reload_interval_secs: Option<u64>,
+    #[arg(long)]
+    pub tls_redirect_port: Option<u16>,
+    #[arg(long)]
+    pub snapshot_path: Option<String>,
+    #[arg(long)]
+    pub shutdown_grace_secs: Option<u64>,
+    #[arg(long)]
+    pub api_v1_deprecated_at: Option<DateTime<Utc>>,
+    #[arg(long)]
+    pub api_v1_sunset_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Invalid config file {path}: {source}")]
+    Yaml {
+        path: String,
+        source: serde_yaml::Error,
+    },
+    #[error("Invalid value {value:?} for {var}: {reason}")]
+    Env {
+        var: &'static str,
+        value: String,
+        reason: String,
+    },
+    #[error("Invalid config value for {field}: {reason}")]
+    Invalid {
+        field: &'static str,
+        reason: String,
+    },
+}
+
+impl Config {
+    /// Build the effective configuration from every layer and validate it
+    pub fn load(
+        file: Option<&Path>,
+        overrides: &ConfigOverrides,
+    ) -> Result<Self, ConfigError> {
+        let env_file = env::var("SQUADZ_CONFIG").ok();
+        let file = file.or(env_file.as_deref().map(Path::new));
+
+        let mut config = match file {
+            Some(path) => Self::from_yaml_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env(|var| env::var(var).ok())?;
+        config.apply_overrides(overrides);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read a YAML config file; keys not present keep their defaults
+    pub fn from_yaml_file(path: &Path) -> Result<Self, ConfigError> {
+        let display = path.display().to_string();
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: display.clone(),
+            source,
+        })?;
+        Self::from_yaml_str(&contents).map_err(|source| ConfigError::Yaml {
+            path: display,
+            source,
+        })
+    }
+
+    pub fn from_yaml_str(contents: &str) -> Result<Self, serde_yaml::Error> {
+        // An empty file is a valid "all defaults" config
+        if contents.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(contents)
+    }
+
+    /// Apply environment variable overrides; unparseable values are errors
+    pub fn apply_env(
+        &mut self,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        if let Some(host) = lookup("HOST") {
+            self.host = host;
+        }
+        env_override(&lookup, "PORT", &mut self.port)?;
+        env_override(&lookup, "LOCATION_TTL_SECS", &mut self.location_ttl_secs)?;
+        env_override(&lookup, "MAX_CLOCK_SKEW_SECS", &mut self.max_clock_skew_secs)?;
+        env_override(&lookup, "SESSION_TTL_SECS", &mut self.session_ttl_secs)?;
+        env_override(&lookup, "MAX_SQUAD_SIZE", &mut self.max_squad_size)?;
+        env_override(&lookup, "JOIN_FAILURE_LIMIT", &mut self.join_failure_limit)?;
+        env_override(
+            &lookup,
+            "JOIN_FAILURE_WINDOW_SECS",
+            &mut self.join_failure_window_secs,
+        )?;
+        env_override(
+            &lookup,
+            "LOCATION_HISTORY_POINTS",
+            &mut self.location_history_points,
+        )?;
+        env_override(
+            &lookup,
+            "LOCATION_HISTORY_RETENTION_SECS",
+            &mut self.location_history_retention_secs,
+        )?;
+        env_override(&lookup, "MESSAGE_RETENTION", &mut self.message_retention)?;
+        env_list_override(&lookup, "WEBHOOK_ALLOWED_HOSTS", &mut self.webhook_allowed_hosts);
+        if let Some(user) = lookup("DASHBOARD_USER") {
+            self.dashboard_user = user;
+        }
+        env_override(
+            &lookup,
+            "DASHBOARD_SESSION_TTL_SECS",
+            &mut self.dashboard_session_ttl_secs,
+        )?;
+        env_override(
+            &lookup,
+            "DASHBOARD_LOGIN_FAILURE_LIMIT",
+            &mut self.dashboard_login_failure_limit,
+        )?;
+        env_override(
+            &lookup,
+            "DASHBOARD_LOGIN_FAILURE_WINDOW_SECS",
+            &mut self.dashboard_login_failure_window_secs,
+        )?;
+        env_override(
+            &lookup,
+            "DASHBOARD_SECURE_COOKIE",
+            &mut self.dashboard_secure_cookie,
+        )?;
+        if let Some(path) = lookup("AUDIT_LOG_PATH") {
+            self.audit_log_path = Some(path);
+        }
+        env_list_override(&lookup, "CORS_ALLOWED_ORIGINS", &mut self.cors_allowed_origins);
+        env_list_override(&lookup, "CORS_ALLOWED_METHODS", &mut self.cors_allowed_methods);
+        env_list_override(&lookup, "CORS_ALLOWED_HEADERS", &mut self.cors_allowed_headers);
+        env_override(
+            &lookup,
+            "CORS_ALLOW_CREDENTIALS",
+            &mut self.cors_allow_credentials,
+        )?;
+        if let Some(path) = lookup("TLS_CERT_PATH") {
+            self.tls_cert_path = Some(path);
+        }
+        if let Some(path) = lookup("TLS_KEY_PATH") {
+            self.tls_key_path = Some(path);
+        }
+        env_override(
+            &lookup,
+            "TLS_RELOAD_INTERVAL_SECS",
+            &mut self.tls_reload_interval_secs,
+        )?;
+        env_option_override(&lookup, "TLS_REDIRECT_PORT", &mut self.tls_redirect_port)?;
+        if let Some(path) = lookup("SNAPSHOT_PATH") {
+            self.snapshot_path = Some(path);
+        }
+        env_override(&lookup, "SHUTDOWN_GRACE_SECS", &mut self.shutdown_grace_secs)?;
+        env_option_override(&lookup, "API_V1_DEPRECATED_AT", &mut self.api_v1_deprecated_at)?;
+        env_option_override(&lookup, "API_V1_SUNSET_AT", &mut self.api_v1_sunset_at)?;
+        Ok(())
+    }
+
+    /// Apply command-line overrides
+    pub fn apply_overrides(&mut self, overrides: &ConfigOverrides) {
+        if let Some(host) = &overrides.host {
+            self.host = host.clone();
+        }
+        if let Some(port) = overrides.port {
+            self.port = port;
+        }
+        if let Some(ttl) = overrides.location_ttl_secs {
+            self.location_ttl_secs = ttl;
+        }
+        if let Some(skew) = overrides.max_clock_skew_secs {
+            self.max_clock_skew_secs = skew;
+        }
+        if let Some(ttl) = overrides.session_ttl_secs {
+            self.session_ttl_secs = ttl;
+        }
+        if let Some(size) = overrides.max_squad_size {
+            self.max_squad_size = size;
+        }
+        if let Some(limit) = overrides.join_failure_limit {
+            self.join_failure_limit = limit;
+        }
+        if let Some(window) = overrides.join_failure_window_secs {
+            self.join_failure_window_secs = window;
+        }
+        if let Some(points) = overrides.location_history_points {
+            self.location_history_points = points;
+        }
+        if let Some(retention) = overrides.location_history_retention_secs {
+            self.location_history_retention_secs = retention;
+        }
+        if let Some(retention) = overrides.message_retention {
+            self.message_retention = retention;
+        }
+        if let Some(hosts) = &overrides.webhook_allowed_hosts {
+            self.webhook_allowed_hosts = hosts.clone();
+        }
+        if let Some(user) = &overrides.dashboard_user {
+            self.dashboard_user = user.clone();
+        }
+        if let Some(ttl) = overrides.dashboard_session_ttl_secs {
+            self.dashboard_session_ttl_secs = ttl;
+        }
+        if let Some(limit) = overrides.dashboard_login_failure_limit {
+            self.dashboard_login_failure_limit = limit;
+        }
+        if let Some(window) = overrides.dashboard_login_failure_window_secs {
+            self.dashboard_login_failure_window_secs = window;
+        }
+        if let Some(secure) = overrides.dashboard_secure_cookie {
+            self.dashboard_secure_cookie = secure;
+        }
+        if let Some(path) = &overrides.audit_log_path {
+            self.audit_log_path = Some(path.clone());
+        }
+        if let Some(origins) = &overrides.cors_allowed_origins {
+            self.cors_allowed_origins = origins.clone();
+        }
+        if let Some(methods) = &overrides.cors_allowed_methods {
+            self.cors_allowed_methods = methods.clone();
+        }
+        if let Some(headers) = &overrides.cors_allowed_headers {
+            self.cors_allowed_headers = headers.clone();
+        }
+        if let Some(credentials) = overrides.cors_allow_credentials {
+            self.cors_allow_credentials = credentials;
+        }
+        if let Some(path) = &overrides.tls_cert_path {
+            self.tls_cert_path = Some(path.clone());
+        }
+        if let Some(path) = &overrides.tls_key_path {
+            self.tls_key_path = Some(path.clone());
+        }
+        if let Some(interval) = overrides.tls_reload_interval_secs {
+            self.tls_reload_interval_secs = interval;
+        }
+        if let Some(port) = overrides.tls_redirect_port {
+            self.tls_redirect_port = Some(port);
+        }
+        if let Some(path) = &overrides.snapshot_path {
+            self.snapshot_path = Some(path.clone());
+        }
+        if let Some(grace) = overrides.shutdown_grace_secs {
+            self.shutdown_grace_secs = grace;
+        }
+        if let Some(at) = overrides.api_v1_deprecated_at {
+            self.api_v1_deprecated_at = Some(at);
+        }
+        if let Some(at) = overrides.api_v1_sunset_at {
+            self.api_v1_sunset_at = Some(at);
+        }
+    }
+
+    /// Reject values the server cannot run with
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.host.trim().is_empty() {
+            return Err(invalid("host", "must not be empty"));
+        }
+        if self.port == 0 {
+            return Err(invalid("port", "must be between 1 and 65535"));
+        }
+        if self.location_ttl_secs == 0 {
+            return Err(invalid("location_ttl_secs", "must be greater than 0"));
+        }
+        if self.max_clock_skew_secs > 3600 {
+            return Err(invalid("max_clock_skew_secs", "must be at most 3600"));
+        }
+        if !(60..=30 * 24 * 3600).contains(&self.session_ttl_secs) {
+            return Err(invalid("session_ttl_secs", "must be between 60 and 2592000 (30 days)"));
+        }
+        if !(2..=10_000).contains(&self.max_squad_size) {
+            return Err(invalid("max_squad_size", "must be between 2 and 10000"));
+        }
+        if !(1..=1000).contains(&self.join_failure_limit) {
+            return Err(invalid("join_failure_limit", "must be between 1 and 1000"));
+        }
+        if !(1..=24 * 3600).contains(&self.join_failure_window_secs) {
+            return Err(invalid(
+                "join_failure_window_secs",
+                "must be between 1 and 86400 (1 day)",
+            ));
+        }
+        if !(1..=100_000).contains(&self.location_history_points) {
+            return Err(invalid("location_history_points", "must be between 1 and 100000"));
+        }
+        if !(60..=30 * 24 * 3600).contains(&self.location_history_retention_secs) {
+            return Err(invalid(
+                "location_history_retention_secs",
+                "must be between 60 and 2592000 (30 days)",
+            ));
+        }
+        if !(1..=100_000).contains(&self.message_retention) {
+            return Err(invalid("message_retention", "must be between 1 and 100000"));
+        }
+        for host in &self.webhook_allowed_hosts {
+            let valid_name = !host.is_empty()
+                && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-'));
+            if !valid_name && host.parse::<std::net::IpAddr>().is_err() {
+                return Err(ConfigError::Invalid {
+                    field: "webhook_allowed_hosts",
+                    reason: format!("{:?} is not a host name or IP address", host),
+                });
+            }
+        }
+        if self.dashboard_user.trim().is_empty() {
+            return Err(invalid("dashboard_user", "must not be empty"));
+        }
+        if !(60..=7 * 24 * 3600).contains(&self.dashboard_session_ttl_secs) {
+            return Err(invalid(
+                "dashboard_session_ttl_secs",
+                "must be between 60 and 604800 (7 days)",
+            ));
+        }
+        if !(1..=1000).contains(&self.dashboard_login_failure_limit) {
+            return Err(invalid("dashboard_login_failure_limit", "must be between 1 and 1000"));
         }
+        if !(1..=24 * 3600).contains(&self.dashboard_login_failure_window_secs) {
+            return Err(invalid(
+                "dashboard_login_failure_window_secs",
+                "must be between 1 and 86400 (1 day)",
+            ));
+        }
+        if self.audit_log_path.as_deref().is_some_and(|p| p.trim().is_empty()) {
+            return Err(invalid("audit_log_path", "must not be empty when set"));
+        }
+        if self.snapshot_path.as_deref().is_some_and(|p| p.trim().is_empty()) {
+            return Err(invalid("snapshot_path", "must not be empty when set"));
+        }
+        if self.shutdown_grace_secs > 3600 {
+            return Err(invalid("shutdown_grace_secs", "must be at most 3600"));
+        }
+        if let Some(sunset_at) = self.api_v1_sunset_at {
+            match self.api_v1_deprecated_at {
+                None => return Err(invalid("api_v1_sunset_at", "requires api_v1_deprecated_at")),
+                Some(deprecated_at) if sunset_at < deprecated_at => {
+                    return Err(invalid("api_v1_sunset_at", "must not be before api_v1_deprecated_at"))
+                }
+                _ => {}
+            }
+        }
+        self.validate_cors()?;
+        self.validate_tls()
+    }
+
+    fn validate_tls(&self) -> Result<(), ConfigError> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(_), None) => {
+                return Err(invalid("tls_key_path", "must be set when tls_cert_path is set"))
+            }
+            (None, Some(_)) => {
+                return Err(invalid("tls_cert_path", "must be set when tls_key_path is set"))
+            }
+            _ => {}
+        }
+        for (field, path) in [
+            ("tls_cert_path", &self.tls_cert_path),
+            ("tls_key_path", &self.tls_key_path),
+        ] {
+            if let Some(path) = path {
+                if !Path::new(path).is_file() {
+                    return Err(ConfigError::Invalid {
+                        field,
+                        reason: format!("{} is not a readable file", path),
+                    });
+                }
+            }
+        }
+        if self.tls_reload_interval_secs == 0 {
+            return Err(invalid("tls_reload_interval_secs", "must be greater than 0"));
+        }
+        if let Some(port) = self.tls_redirect_port {
+            if !self.tls_enabled() {
+                return Err(invalid("tls_redirect_port", "requires tls_cert_path and tls_key_path"));
+            }
+            if port == 0 || port == self.port {
+                return Err(invalid(
+                    "tls_redirect_port",
+                    "must be between 1 and 65535 and differ from port",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the server terminates TLS itself
+    pub fn tls_enabled(&self) -> bool {
+        self.tls_cert_path.is_some() && self.tls_key_path.is_some()
+    }
+
+    fn validate_cors(&self) -> Result<(), ConfigError> {
+        let lists = [
+            ("cors_allowed_origins", &self.cors_allowed_origins),
+            ("cors_allowed_methods", &self.cors_allowed_methods),
+            ("cors_allowed_headers", &self.cors_allowed_headers),
+        ];
+        for (field, list) in lists {
+            if list.is_empty() {
+                return Err(invalid(field, "must list at least one entry (or \"*\")"));
+            }
+            if is_wildcard(list) && list.len() > 1 {
+                return Err(invalid(field, "\"*\" cannot be combined with other entries"));
+            }
+            if self.cors_allow_credentials && is_wildcard(list) {
+                return Err(invalid(
+                    field,
+                    "must be an explicit list when cors_allow_credentials is true",
+                ));
+            }
+        }
+
+        if !is_wildcard(&self.cors_allowed_origins) {
+            for origin in &self.cors_allowed_origins {
+                let valid_scheme = origin.starts_with("http://") || origin.starts_with("https://");
+                let has_path = origin
+                    .split_once("://")
+                    .is_some_and(|(_, rest)| rest.contains('/'));
+                if !valid_scheme || has_path || HeaderValue::from_str(origin).is_err() {
+                    return Err(ConfigError::Invalid {
+                        field: "cors_allowed_origins",
+                        reason: format!("{:?} is not an origin like https://app.example.com", origin),
+                    });
+                }
+            }
+        }
+        if !is_wildcard(&self.cors_allowed_methods) {
+            for method in &self.cors_allowed_methods {
+                if Method::from_bytes(method.as_bytes()).is_err() {
+                    return Err(ConfigError::Invalid {
+                        field: "cors_allowed_methods",
+                        reason: format!("{:?} is not an HTTP method", method),
+                    });
+                }
+            }
+        }
+        if !is_wildcard(&self.cors_allowed_headers) {
+            for name in &self.cors_allowed_headers {
+                if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    return Err(ConfigError::Invalid {
+                        field: "cors_allowed_headers",
+                        reason: format!("{:?} is not a header name", name),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the CORS layer for the configured policy
+    ///
+    /// Expects a validated config.
+    pub fn cors_layer(&self) -> CorsLayer {
+        let origins = if is_wildcard(&self.cors_allowed_origins) {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(
+                self.cors_allowed_origins
+                    .iter()
+                    .map(|o| HeaderValue::from_str(o).expect("validated origin")),
+            )
+        };
+        let methods = if is_wildcard(&self.cors_allowed_methods) {
+            AllowMethods::any()
+        } else {
+            AllowMethods::list(
+                self.cors_allowed_methods
+                    .iter()
+                    .map(|m| Method::from_bytes(m.as_bytes()).expect("validated method")),
+            )
+        };
+        let headers = if is_wildcard(&self.cors_allowed_headers) {
+            AllowHeaders::any()
+        } else {
+            AllowHeaders::list(
+                self.cors_allowed_headers
+                    .iter()
+                    .map(|h| HeaderName::from_bytes(h.as_bytes()).expect("validated header")),
+            )
+        };
+
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .allow_credentials(self.cors_allow_credentials)
+    }
+}
+
+fn is_wildcard(list: &[String]) -> bool {
+    list.iter().any(|entry| entry == CORS_ANY)
+}
+
+fn invalid(field: &'static str, reason: &str) -> ConfigError {
+    ConfigError::Invalid {
+        field,
+        reason: reason.to_string(),
+    }
+}
+
+/// Overwrite `target` with a comma-separated env var, if set
+fn env_list_override(
+    lookup: &impl Fn(&str) -> Option<String>,
+    var: &'static str,
+    target: &mut Vec<String>,
+) {
+    if let Some(value) = lookup(var) {
+        *target = value
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+}
+
+/// Set an optional `target` to the parsed env var, if set
+fn env_option_override<T>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    var: &'static str,
+    target: &mut Option<T>,
+) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    if let Some(value) = lookup(var) {
+        let parsed = value.parse().map_err(|e: T::Err| ConfigError::Env {
+            var,
+            value: value.clone(),
+            reason: e.to_string(),
+        })?;
+        *target = Some(parsed);
+    }
+    Ok(())
+}
+
+/// Overwrite `target` with the parsed env var, if set
+fn env_override<T>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    var: &'static str,
+    target: &mut T,
+) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    if let Some(value) = lookup(var) {
+        *target = value.parse().map_err(|e: T::Err| ConfigError::Env {
+            var,
+            value: value.clone(),
+            reason: e.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |k| vars.get(k).cloned()
+    }
+
+    #[test]
+    fn test_defaults_are_valid() {
+        assert!(Config::default().validate().is_ok());
+        assert_eq!(Config::from_yaml_str("").unwrap().port, 8080);
+    }
+
+    #[test]
+    fn test_layering() {
+        let mut config = Config::from_yaml_str("port: 9000\nmax_squad_size: 20\n").unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.location_ttl_secs, 300);
+
+        config.apply_env(env(&[("PORT", "9100")])).unwrap();
+        assert_eq!(config.port, 9100);
+
+        config.apply_overrides(&ConfigOverrides {
+            port: Some(9200),
+            ..Default::default()
+        });
+        assert_eq!(config.port, 9200);
+        assert_eq!(config.max_squad_size, 20);
+    }
+
+    #[test]
+    fn test_limit_and_retention_validation() {
+        let mut config = Config::from_yaml_str("join_failure_limit: 10
+message_retention: 50
+").unwrap();
+        config.apply_env(env(&[("LOCATION_HISTORY_POINTS", "200")])).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            (config.join_failure_limit, config.message_retention, config.location_history_points),
+            (10, 50, 200)
+        );
+
+        for (field, yaml) in [
+            ("join_failure_limit", "join_failure_limit: 0"),
+            ("join_failure_window_secs", "join_failure_window_secs: 0"),
+            ("dashboard_login_failure_limit", "dashboard_login_failure_limit: 0"),
+            (
+                "dashboard_login_failure_window_secs",
+                "dashboard_login_failure_window_secs: 100000",
+            ),
+            ("location_history_points", "location_history_points: 0"),
+            ("location_history_retention_secs", "location_history_retention_secs: 10"),
+            ("message_retention", "message_retention: 1000000"),
+        ] {
+            let err = Config::from_yaml_str(yaml).unwrap().validate().unwrap_err();
+            assert!(matches!(err, ConfigError::Invalid { field: f, .. } if f == field), "{field}");
+        }
+    }
+
+    #[test]
+    fn test_webhook_allowed_hosts() {
+        let mut config = Config::default();
+        config
+            .apply_env(env(&[("WEBHOOK_ALLOWED_HOSTS", "hooks.internal, 10.0.0.5,::1")]))
+            .unwrap();
+        assert_eq!(config.webhook_allowed_hosts, ["hooks.internal", "10.0.0.5", "::1"]);
+        assert!(config.validate().is_ok());
+
+        config.webhook_allowed_hosts = vec!["http://hooks.internal/".to_string()];
+        let err = config.validate().unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { field: "webhook_allowed_hosts", .. }));
+    }
+
+    #[test]
+    fn test_unknown_yaml_key_rejected() {
+        assert!(Config::from_yaml_str("prot: 9000\n").is_err());
+    }
+
+    #[test]
+    fn test_bad_env_value_rejected() {
+        let mut config = Config::default();
+        let err = config.apply_env(env(&[("PORT", "eighty")])).unwrap_err();
+        assert!(matches!(err, ConfigError::Env { var: "PORT", .. }));
+    }
+
+    #[test]
+    fn test_cors_validation() {
+        let mut config = Config::default();
+        config
+            .apply_env(env(&[
+                ("CORS_ALLOWED_ORIGINS", "https://app.example.com, http://localhost:3000"),
+                ("CORS_ALLOWED_METHODS", "GET,POST"),
+            ]))
+            .unwrap();
+        assert_eq!(config.cors_allowed_origins.len(), 2);
+        assert!(config.validate().is_ok());
+
+        // Credentials need explicit lists everywhere
+        config.cors_allow_credentials = true;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { field: "cors_allowed_headers", .. })
+        ));
+
+        let config = Config {
+            cors_allowed_origins: vec!["https://app.example.com/path".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tls_validation() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("cert.pem");
+        std::fs::write(&cert, "").unwrap();
+        let cert = cert.display().to_string();
+
+        // Cert without key
+        let config = Config {
+            tls_cert_path: Some(cert.clone()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { field: "tls_key_path", .. })
+        ));
+
+        // Both set, key missing on disk
+        let config = Config {
+            tls_cert_path: Some(cert.clone()),
+            tls_key_path: Some(dir.path().join("missing.pem").display().to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { field: "tls_key_path", .. })
+        ));
+
+        // Redirect without TLS
+        let mut config = Config::default();
+        config.apply_env(env(&[("TLS_REDIRECT_PORT", "80")])).unwrap();
+        assert_eq!(config.tls_redirect_port, Some(80));
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { field: "tls_redirect_port", .. })
+        ));
+
+        let config = Config {
+            tls_cert_path: Some(cert.clone()),
+            tls_key_path: Some(cert),
+            tls_redirect_port: Some(8081),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(config.tls_enabled());
+    }
+
+    #[test]
+    fn test_api_deprecation_dates() {
+        let mut config = Config::default();
+        config
+            .apply_env(env(&[("API_V1_SUNSET_AT", "2026-07-01T00:00:00Z")]))
+            .unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { field: "api_v1_sunset_at", .. })
+        ));
+
+        config
+            .apply_env(env(&[("API_V1_DEPRECATED_AT", "2026-01-01T00:00:00Z")]))
+            .unwrap();
+        assert!(config.validate().is_ok());
+
+        let err = config
+            .apply_env(env(&[("API_V1_DEPRECATED_AT", "next tuesday")]))
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Env { var: "API_V1_DEPRECATED_AT", .. }));
+    }
+
+    #[test]
+    fn test_validation() {
+        let config = Config {
+            location_ttl_secs: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { field: "location_ttl_secs", .. })
+        ));
     }
 }
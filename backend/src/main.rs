@@ -2,22 +2,40 @@
 //!
 //! Built on omni-core patterns for secure, real-time location sharing.
 
+use std::path::Path;
 use std::sync::Arc;
-use axum::{Router, routing::{get, post, delete}, middleware};
+use axum::{Router, routing::{get, post}, middleware};
+use clap::Parser;
 use tokio::sync::RwLock;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::trace::TraceLayer;
-use tracing::info;
+use tracing::{info, warn};
 
 mod api;
+mod cli;
 mod config;
 mod models;
 mod services;
+mod shutdown;
+mod tls;
+mod validation;
 
+use api::version::{ApiVersion, ApiVersions, VersionPolicy};
+use cli::Cli;
 use config::Config;
+use services::admin_auth::AdminAuth;
+use services::alerts::{self, AlertStore};
+use services::audit::{AuditContext, AuditLog};
+use services::feed::SquadFeed;
 use services::squad_manager::SquadManager;
 use services::location_store::LocationStore;
+use services::messages::MessageStore;
+use services::routes::RouteTracker;
 use services::session::SessionStore;
+use services::snapshot::Snapshot;
+use services::webhooks::{self, WebhookStore};
+use services::sos::{self, SosStore};
+use shutdown::Shutdown;
 
 /// Application state shared across handlers
 pub struct AppState {
@@ -25,7 +43,41 @@ pub struct AppState {
     pub squad_manager: RwLock<SquadManager>,
     pub location_store: RwLock<LocationStore>,
     pub session_store: SessionStore,
-    pub dashboard_password: String,
+    pub admin_auth: AdminAuth,
+    pub audit: AuditLog,
+    pub api_versions: ApiVersions,
+    pub alerts: AlertStore,
+    pub sos: SosStore,
+    pub feed: SquadFeed,
+    pub webhooks: WebhookStore,
+    pub messages: MessageStore,
+    pub route_progress: RouteTracker,
+    /// Fires when the server starts shutting down, for long-lived connections
+    pub shutdown: Shutdown,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// Empty in-memory state with the default configuration
+    pub fn for_tests() -> Arc<Self> {
+        let audit = AuditLog::new();
+        Arc::new(Self {
+            config: Config::default(),
+            squad_manager: RwLock::new(SquadManager::with_audit(audit.clone())),
+            location_store: RwLock::new(LocationStore::new()),
+            session_store: SessionStore::with_audit(audit.clone()),
+            admin_auth: AdminAuth::new("admin".to_string(), String::new(), 3600),
+            audit: audit.clone(),
+            api_versions: ApiVersions::new(),
+            alerts: AlertStore::new(),
+            sos: SosStore::with_audit(audit.clone()),
+            feed: SquadFeed::new(),
+            webhooks: WebhookStore::with_audit(audit.clone()),
+            messages: MessageStore::with_audit(audit),
+            route_progress: RouteTracker::new(),
+            shutdown: Shutdown::never(),
+        })
+    }
 }
 
 #[tokio::main]
@@ -41,79 +93,261 @@ async fn main() -> anyhow::Result<()> {
 
     // Load configuration
     dotenvy::dotenv().ok();
-    let config = Config::from_env();
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref(), &cli.overrides)?;
+
+    if cli.check_config {
+        print!("{}", serde_yaml::to_string(&config)?);
+        return Ok(());
+    }
+
+    match cli.command {
+        Some(cli::Command::Snapshot(command)) => return command.run(&config),
+        Some(cli::Command::Openapi) => {
+            println!("{}", api::openapi::openapi_json());
+            return Ok(());
+        }
+        None => {}
+    }
 
     info!("Starting Squadz server on {}:{}", config.host, config.port);
 
-    // Get or generate dashboard password
-    let dashboard_password = std::env::var("DASHBOARD_PASSWORD").unwrap_or_else(|_| {
-        let generated: String = (0..12)
-            .map(|_| {
-                let idx = rand::random::<usize>() % 36;
-                if idx < 10 {
-                    (b'0' + idx as u8) as char
-                } else {
-                    (b'a' + (idx - 10) as u8) as char
+    // Admin dashboard credential: a pre-computed Argon2id hash, a plaintext
+    // password hashed at startup, or a generated one kept in a file only its
+    // owner can read. The password itself is never printed or logged.
+    let dashboard_password_hash = match std::env::var("DASHBOARD_PASSWORD_HASH") {
+        Ok(hash) => {
+            services::password::check_hash(&hash).map_err(|e| {
+                anyhow::anyhow!("DASHBOARD_PASSWORD_HASH is not an Argon2 PHC string: {}", e)
+            })?;
+            hash
+        }
+        Err(_) => {
+            let password = match std::env::var("DASHBOARD_PASSWORD") {
+                Ok(password) => password,
+                Err(_) => {
+                    let path = std::env::var("DASHBOARD_PASSWORD_FILE")
+                        .unwrap_or_else(|_| "dashboard-password".to_string());
+                    let (password, generated) =
+                        services::admin_auth::load_or_generate_password(Path::new(&path))
+                            .map_err(|e| {
+                                anyhow::anyhow!("failed to read or create {}: {}", path, e)
+                            })?;
+                    let user = &config.dashboard_user;
+                    if generated {
+                        warn!("No dashboard password set; generated one for '{}' in {}", user, path);
+                    } else {
+                        info!("Using the generated dashboard password for '{}' in {}", user, path);
+                    }
+                    password
                 }
-            })
-            .collect();
-        info!("Generated dashboard password: {}", generated);
-        generated
-    });
-    info!("Dashboard available at / (password protected)");
+            };
+            services::password::hash_password(&password)
+                .map_err(|e| anyhow::anyhow!("failed to hash dashboard password: {}", e))?
+        }
+    };
+    let admin_auth = AdminAuth::new(
+        config.dashboard_user.clone(),
+        dashboard_password_hash,
+        config.dashboard_session_ttl_secs,
+    )
+    .with_login_throttle(
+        config.dashboard_login_failure_limit,
+        config.dashboard_login_failure_window_secs as i64,
+    );
+    info!("Dashboard available at / (login required)");
 
-    // Initialize state
+    // Audit log, persisted if a path is configured
+    let audit = match &config.audit_log_path {
+        Some(path) => {
+            let audit = AuditLog::open(path)
+                .map_err(|e| anyhow::anyhow!("failed to open audit log {}: {}", path, e))?;
+            info!("Audit log at {} ({} existing entries)", path, audit.count());
+            audit
+        }
+        None => {
+            warn!("AUDIT_LOG_PATH not set; audit log is kept in memory only");
+            AuditLog::new()
+        }
+    };
+
+    // Initialize state, restoring the last snapshot if there is one
+    let mut squad_manager =
+        SquadManager::with_audit(audit.clone())
+            .with_max_squad_size(config.max_squad_size)
+            .with_join_throttle(config.join_failure_limit, config.join_failure_window_secs as i64);
+    let mut location_store = LocationStore::with_ttl(config.location_ttl_secs as i64)
+        .with_max_clock_skew(config.max_clock_skew_secs as i64)
+        .with_max_history_points(config.location_history_points)
+        .with_history_retention(config.location_history_retention_secs as i64);
+    let session_store = SessionStore::with_audit(audit.clone());
+    let sos_store = SosStore::with_audit(audit.clone());
+    let webhook_store = WebhookStore::with_audit(audit.clone())
+        .with_allowed_hosts(config.webhook_allowed_hosts.clone());
+    let message_store =
+        MessageStore::with_audit(audit.clone()).with_max_messages(config.message_retention);
+
+    if let Some(path) = &config.snapshot_path {
+        match Snapshot::load(path)
+            .map_err(|e| anyhow::anyhow!("failed to load snapshot {}: {}", path, e))?
+        {
+            Some(snapshot) => {
+                let details = serde_json::json!({
+                    "created_at": snapshot.created_at,
+                    "squads": snapshot.squads.len(),
+                    "locations": snapshot.locations.len(),
+                    "sessions": snapshot.sessions.len(),
+                });
+                snapshot.restore(
+                    &mut squad_manager,
+                    &mut location_store,
+                    &session_store,
+                    &webhook_store,
+                    &sos_store,
+                    &message_store,
+                );
+                info!("Restored snapshot from {}: {}", path, details);
+                audit.record(&AuditContext::system(), "snapshot.restore", None, None, details);
+            }
+            None => info!("No snapshot at {}; starting empty", path),
+        }
+    } else {
+        warn!("SNAPSHOT_PATH not set; state will not survive a restart");
+    }
+
+    let shutdown = Shutdown::listen();
     let state = Arc::new(AppState {
         config: config.clone(),
-        squad_manager: RwLock::new(SquadManager::new()),
-        location_store: RwLock::new(LocationStore::new()),
-        session_store: SessionStore::new(),
-        dashboard_password,
+        squad_manager: RwLock::new(squad_manager),
+        location_store: RwLock::new(location_store),
+        session_store,
+        admin_auth,
+        audit,
+        api_versions: ApiVersions::new().with_policy(
+            ApiVersion::V1,
+            VersionPolicy {
+                deprecated_at: config.api_v1_deprecated_at,
+                sunset_at: config.api_v1_sunset_at,
+            },
+        ),
+        alerts: AlertStore::new(),
+        sos: sos_store,
+        feed: SquadFeed::new(),
+        webhooks: webhook_store,
+        messages: message_store,
+        route_progress: RouteTracker::new(),
+        shutdown: shutdown.clone(),
     });
+    alerts::spawn_sweeper(state.clone());
+    sos::spawn_rebroadcaster(state.clone());
+    webhooks::spawn_dispatcher(state.webhooks.clone())
+        .map_err(|e| anyhow::anyhow!("failed to build the webhook client: {}", e))?;
+    webhooks::spawn_forwarder(state.clone());
 
-    // Protected routes (require auth)
-    let protected_routes = Router::new()
-        .route("/api/v1/locations", post(api::locations::update_location))
-        .route("/api/v1/squads/:squad_id/leave", post(api::squads::leave_squad))
-        .route("/api/v1/squads/:squad_id", delete(api::squads::delete_squad))
-        .layer(middleware::from_fn_with_state(state.clone(), services::auth::auth_middleware));
-
-    // Public routes (no auth required)
-    let public_routes = Router::new()
-        // Dashboard at root
+    // Dashboard pages (the API is mounted per version by `api::routes`)
+    let dashboard_routes = Router::new()
         .route("/", get(api::dashboard::dashboard_page))
-        .route("/api/v1/health", get(api::health::health_check))
-        .route("/api/v1/squads", post(api::squads::create_squad))
-        .route("/api/v1/squads", get(api::squads::list_squads))
-        .route("/api/v1/squads/:squad_id", get(api::squads::get_squad))
-        .route("/api/v1/squads/:squad_id/join", post(api::squads::join_squad))
-        .route("/api/v1/squads/:squad_id/locations", get(api::locations::get_squad_locations))
-        // Crypto test endpoints (omni-core-lite)
-        .route("/api/v1/crypto/health", get(api::crypto::crypto_health))
-        .route("/api/v1/crypto/echo", post(api::crypto::crypto_echo))
-        .route("/api/v1/crypto/encrypt", post(api::crypto::crypto_encrypt))
-        .route("/api/v1/crypto/decrypt", post(api::crypto::crypto_decrypt));
+        .route("/login", post(api::dashboard::login))
+        .route("/logout", post(api::dashboard::logout))
+        .route("/dashboard/locations", get(api::dashboard::dashboard_locations));
 
     // Build router
     let app = Router::new()
-        .merge(protected_routes)
-        .merge(public_routes)
+        .merge(api::routes(&state))
+        .merge(dashboard_routes)
         // Middleware
+        .layer(middleware::from_fn(api::error::error_envelope))
+        .layer(middleware::from_fn_with_state(
+            state.api_versions.clone(),
+            api::version::track_version,
+        ))
         .layer(TraceLayer::new_for_http())
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
-        )
-        .with_state(state);
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(config.cors_layer())
+        .with_state(state.clone());
 
     // Start server
     let addr = format!("{}:{}", config.host, config.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    info!("Squadz server listening on {}", addr);
+    let grace = std::time::Duration::from_secs(config.shutdown_grace_secs);
+
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            if !config.dashboard_secure_cookie {
+                warn!("TLS is enabled but DASHBOARD_SECURE_COOKIE is false");
+            }
+
+            let tls_config = tls::load(cert_path, key_path).await?;
+            tls::spawn_reloader(
+                tls_config.clone(),
+                cert_path.clone(),
+                key_path.clone(),
+                std::time::Duration::from_secs(config.tls_reload_interval_secs),
+            );
+
+            if let Some(redirect_port) = config.tls_redirect_port {
+                let redirect_addr = format!("{}:{}", config.host, redirect_port);
+                let redirect_listener = std::net::TcpListener::bind(&redirect_addr)?;
+                info!("HTTP->HTTPS redirect listening on {}", redirect_addr);
+                tls::spawn_redirect(redirect_listener, config.port, shutdown.clone())?;
+            }
+
+            let listener = std::net::TcpListener::bind(&addr)?;
+            listener.set_nonblocking(true)?;
+            info!("Squadz server listening on https://{}", addr);
+
+            let handle = axum_server::Handle::new();
+            let drain = handle.clone();
+            let requested = shutdown.clone();
+            tokio::spawn(async move {
+                requested.requested().await;
+                drain.graceful_shutdown(Some(grace));
+            });
+
+            axum_server::from_tcp_rustls(listener, tls_config)
+                .handle(handle)
+                .serve(app.into_make_service())
+                .await?;
+        }
+        _ => {
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            info!("Squadz server listening on {}", addr);
+
+            let server = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown.clone().requested());
+            tokio::select! {
+                result = server => result?,
+                _ = shutdown.deadline(grace) => {
+                    warn!("Shutdown grace period elapsed; dropping remaining connections");
+                }
+            }
+        }
+    }
 
-    axum::serve(listener, app).await?;
+    // Flush state so the next start picks up where this one left off
+    if let Some(path) = &config.snapshot_path {
+        let snapshot = Snapshot::capture(
+            &*state.squad_manager.read().await,
+            &*state.location_store.read().await,
+            &state.session_store,
+            &state.webhooks,
+            &state.sos,
+            &state.messages,
+        );
+        let details = serde_json::json!({
+            "squads": snapshot.squads.len(),
+            "locations": snapshot.locations.len(),
+            "sessions": snapshot.sessions.len(),
+        });
+        snapshot
+            .save(path)
+            .map_err(|e| anyhow::anyhow!("failed to write snapshot {}: {}", path, e))?;
+        info!("Wrote snapshot to {}: {}", path, details);
+        state
+            .audit
+            .record(&AuditContext::system(), "snapshot.save", None, None, details);
+    }
+    info!("Squadz server stopped");
 
     Ok(())
 }
@@ -2,10 +2,11 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Geographic coordinates
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
 pub struct GeoPoint {
     pub latitude: f64,
     pub longitude: f64,
@@ -17,10 +18,29 @@ pub struct GeoPoint {
     pub heading: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub speed: Option<f64>,
+    /// When the device took the fix, by the device clock (receive time if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix_time: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl GeoPoint {
+    /// A bare position with no altitude, accuracy, motion or fix time
+    pub fn at(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+            altitude: None,
+            accuracy: None,
+            heading: None,
+            speed: None,
+            fix_time: None,
+        }
+    }
 }
 
 /// A squad member
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Member {
     pub member_id: Uuid,
     pub display_name: String,
@@ -31,7 +51,7 @@ pub struct Member {
 }
 
 /// A squad (group of members sharing locations)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Squad {
     pub squad_id: Uuid,
     pub name: String,
@@ -40,16 +60,36 @@ pub struct Squad {
     pub leader_id: Uuid,
     pub members: Vec<Member>,
     pub settings: SquadSettings,
+    /// Shared markers, oldest first; members read them from the waypoint
+    /// endpoints, as public squad listings must not reveal them
+    #[serde(default, skip_serializing)]
+    #[schema(ignore)]
+    pub waypoints: Vec<Waypoint>,
+    /// Argon2id hash of the optional join passphrase (never sent to clients)
+    #[serde(default, skip_serializing)]
+    #[schema(ignore)]
+    pub passphrase_hash: Option<String>,
+    /// Planned route; served by the route endpoints, too large to inline here
+    #[serde(default, skip_serializing)]
+    #[schema(ignore)]
+    pub route: Option<PlannedRoute>,
 }
 
 /// Squad configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SquadSettings {
     pub is_public: bool,
     pub require_approval: bool,
     pub share_altitude: bool,
     pub share_speed: bool,
     pub location_update_interval_secs: u32,
+    /// Spoof and jump detection; squads created before it use the defaults
+    #[serde(default)]
+    pub plausibility: PlausibilitySettings,
+    #[serde(default)]
+    pub smoothing: SmoothingSettings,
+    #[serde(default)]
+    pub alerts: AlertSettings,
 }
 
 impl Default for SquadSettings {
@@ -60,31 +100,190 @@ impl Default for SquadSettings {
             share_altitude: true,
             share_speed: true,
             location_update_interval_secs: 10,
+            plausibility: PlausibilitySettings::default(),
+            smoothing: SmoothingSettings::default(),
+            alerts: AlertSettings::default(),
+        }
+    }
+}
+
+/// Jitter reduction for member positions
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct SmoothingSettings {
+    /// Run fixes through a Kalman filter and report a `smoothed` position
+    pub enabled: bool,
+    /// Discard fixes whose `accuracy` is worse than this many metres
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_accuracy_m: Option<f64>,
+}
+
+/// Limits a member's fixes are checked against
+///
+/// Teleport jumps (faster than sound) are suspect whatever the limits.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct PlausibilitySettings {
+    /// Fastest believable ground speed between fixes, in m/s
+    pub max_speed_mps: f64,
+    /// Fastest believable climb or descent between fixes, in m/s
+    pub max_vertical_speed_mps: f64,
+    pub on_suspect: SuspectAction,
+}
+
+impl Default for PlausibilitySettings {
+    fn default() -> Self {
+        Self {
+            // Motorway driving with room to spare
+            max_speed_mps: 70.0,
+            max_vertical_speed_mps: 60.0,
+            on_suspect: SuspectAction::Flag,
         }
     }
 }
 
+/// What happens to a fix that fails the plausibility checks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SuspectAction {
+    /// Store it as usual, marked with the reason
+    #[default]
+    Flag,
+    /// Keep it out of the position and track, for the leader to review
+    Quarantine,
+}
+
+/// Why a fix looks spoofed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SuspectReason {
+    /// Moved faster than the squad's `max_speed_mps`
+    Speed,
+    /// Moved faster than any member could, e.g. a dragged fake-GPS pin
+    Teleport,
+    /// Climbed or dropped faster than `max_vertical_speed_mps`
+    Altitude,
+}
+
+/// Separation rules that raise alerts to the leader; unset rules are off
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct AlertSettings {
+    /// Alert when a member is further than this from the squad's centroid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_distance_from_centroid_m: Option<f64>,
+    /// Alert when a member is further than this from the leader
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_distance_from_leader_m: Option<f64>,
+    /// Alert when a member has not moved for this long
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stationary_after_mins: Option<u32>,
+    /// Movement within this radius still counts as not moving
+    pub stationary_radius_m: f64,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            max_distance_from_centroid_m: None,
+            max_distance_from_leader_m: None,
+            stationary_after_mins: None,
+            stationary_radius_m: 50.0,
+        }
+    }
+}
+
+/// A separation rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    FarFromSquad,
+    FarFromLeader,
+    Stationary,
+    /// Further from the squad's planned route than its `max_deviation_m`
+    OffRoute,
+}
+
+/// Whether an alert event starts or ends an alert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Raised,
+    Cleared,
+}
+
+/// A separation alert being raised or cleared
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AlertEvent {
+    /// Shared by the raise and clear events of one alert
+    pub alert_id: Uuid,
+    pub squad_id: Uuid,
+    pub member_id: Uuid,
+    pub display_name: String,
+    pub kind: AlertKind,
+    pub state: AlertState,
+    pub at: DateTime<Utc>,
+    /// Distance from the squad's centroid, the leader or the planned route
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance_m: Option<f64>,
+    /// When a stationary member last moved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stationary_since: Option<DateTime<Utc>>,
+    /// The member's position when the alert was raised
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<LocationFix>,
+}
+
+/// Active alerts and recent alert events of a squad
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlertsResponse {
+    pub squad_id: Uuid,
+    /// Raise events of the alerts still active
+    pub active: Vec<AlertEvent>,
+    /// Raise and clear events, oldest first
+    pub events: Vec<AlertEvent>,
+}
+
 /// A member's location update
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct MemberLocation {
     pub member_id: Uuid,
     pub display_name: String,
     pub location: GeoPoint,
+    /// Same as `fix_time`; kept for older clients
     pub updated_at: DateTime<Utc>,
+    /// When the fix was taken, corrected for the device's clock offset
+    pub fix_time: DateTime<Utc>,
+    /// When the server received the fix
+    pub received_at: DateTime<Utc>,
+    /// `received_at - fix_time`
+    pub latency_ms: i64,
+    /// How far the member's device clock runs ahead of the server (0 if not)
+    pub clock_offset_ms: i64,
+    /// Judged by `fix_time`, so a slow network cannot make a fix look fresh
     pub is_stale: bool,
+    /// Set when the fix failed the squad's plausibility checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspect: Option<SuspectReason>,
+    /// Filtered position, when the squad has smoothing enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothed: Option<GeoPoint>,
 }
 
 /// Request to create a new squad
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateSquadRequest {
     pub name: String,
     pub leader_name: String,
     #[serde(default)]
     pub settings: Option<SquadSettings>,
+    /// Optional passphrase members must supply alongside the join code
+    #[serde(default)]
+    pub passphrase: Option<String>,
 }
 
 /// Response after creating a squad
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CreateSquadResponse {
     pub squad_id: Uuid,
     pub join_code: String,
@@ -94,14 +293,23 @@ pub struct CreateSquadResponse {
 }
 
 /// Request to join a squad
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct JoinSquadRequest {
     pub join_code: String,
     pub display_name: String,
+    #[serde(default)]
+    pub passphrase: Option<String>,
+}
+
+/// Request to set or clear a squad's join passphrase (leader only)
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetPassphraseRequest {
+    /// New passphrase, or `None` to remove the requirement
+    pub passphrase: Option<String>,
 }
 
 /// Response after joining a squad
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct JoinSquadResponse {
     pub member_id: Uuid,
     pub squad: Squad,
@@ -109,19 +317,610 @@ pub struct JoinSquadResponse {
     pub api_key: String,
 }
 
-/// Request to update location
-#[derive(Debug, Deserialize)]
-pub struct UpdateLocationRequest {
+/// Response with all squad member locations
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SquadLocationsResponse {
+    pub squad_id: Uuid,
+    pub squad_name: String,
+    pub locations: Vec<MemberLocation>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A position fix in the v2 wire format
+///
+/// Flat, with the unit in each field name. Internally (and in v1) positions
+/// are [`GeoPoint`]s; the two convert losslessly.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct LocationFix {
+    /// Latitude in degrees
+    pub lat: f64,
+    /// Longitude in degrees
+    pub lon: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude_m: Option<f64>,
+    /// Horizontal accuracy radius
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy_m: Option<f64>,
+    /// Course over ground, clockwise from true north
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading_deg: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_mps: Option<f64>,
+    /// When the device took the fix (receive time if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recorded_at: Option<DateTime<Utc>>,
+}
+
+impl From<GeoPoint> for LocationFix {
+    fn from(point: GeoPoint) -> Self {
+        Self {
+            lat: point.latitude,
+            lon: point.longitude,
+            altitude_m: point.altitude,
+            accuracy_m: point.accuracy,
+            heading_deg: point.heading,
+            speed_mps: point.speed,
+            recorded_at: point.fix_time,
+        }
+    }
+}
+
+impl From<LocationFix> for GeoPoint {
+    fn from(fix: LocationFix) -> Self {
+        Self {
+            latitude: fix.lat,
+            longitude: fix.lon,
+            altitude: fix.altitude_m,
+            accuracy: fix.accuracy_m,
+            heading: fix.heading_deg,
+            speed: fix.speed_mps,
+            fix_time: fix.recorded_at,
+        }
+    }
+}
+
+/// Fixes buffered on the device while it was offline, oldest first
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LocationBatchRequest {
+    /// Every fix needs a `recorded_at`, strictly increasing
+    pub fixes: Vec<LocationFix>,
+}
+
+/// What a batch upload changed
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct LocationBatchResponse {
+    /// Fixes added to the member's history
+    pub accepted: usize,
+    /// Fixes already in the history (e.g. a retried upload)
+    pub duplicates: usize,
+    /// Whether the batch moved the member's latest position
+    pub latest_updated: bool,
+    /// Accepted fixes marked as suspect
+    pub flagged: usize,
+    /// Suspect fixes held back for review instead of accepted
+    pub quarantined: usize,
+    /// Fixes discarded for poor accuracy
+    pub dropped: usize,
+}
+
+/// A fix in a member's track
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrackFix {
+    #[serde(flatten)]
+    pub fix: LocationFix,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspect: Option<SuspectReason>,
+}
+
+/// A member's recent track, oldest fix first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MemberTrackResponse {
     pub member_id: Uuid,
+    pub display_name: String,
+    pub fixes: Vec<TrackFix>,
+}
+
+/// A fix held back by the plausibility checks
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuarantinedFix {
+    pub member_id: Uuid,
+    pub display_name: String,
+    pub fix: LocationFix,
+    pub reason: SuspectReason,
+}
+
+/// Every quarantined fix of a squad, oldest first per member
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuarantineResponse {
     pub squad_id: Uuid,
-    pub location: GeoPoint,
+    pub fixes: Vec<QuarantinedFix>,
 }
 
-/// Response with all squad member locations
-#[derive(Debug, Serialize)]
-pub struct SquadLocationsResponse {
+/// Quarantined fixes of one member a leader releases or discards
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct QuarantineReviewRequest {
+    pub member_id: Uuid,
+    /// `recorded_at` of the fixes to take, as listed in the quarantine; all of
+    /// the member's fixes if omitted
+    #[serde(default)]
+    pub recorded_at: Option<Vec<DateTime<Utc>>>,
+}
+
+/// How many quarantined fixes a review released or discarded
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuarantineReviewResponse {
+    pub squad_id: Uuid,
+    pub member_id: Uuid,
+    pub fixes: usize,
+}
+
+/// A member's latest fix in the v2 wire format
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MemberFix {
+    pub member_id: Uuid,
+    pub display_name: String,
+    /// `recorded_at` is the fix time corrected for the device clock offset
+    pub fix: LocationFix,
+    pub updated_at: DateTime<Utc>,
+    pub received_at: DateTime<Utc>,
+    pub latency_ms: i64,
+    pub clock_offset_ms: i64,
+    pub is_stale: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspect: Option<SuspectReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothed: Option<LocationFix>,
+}
+
+impl From<MemberLocation> for MemberFix {
+    fn from(location: MemberLocation) -> Self {
+        Self {
+            member_id: location.member_id,
+            display_name: location.display_name,
+            fix: LocationFix {
+                recorded_at: Some(location.fix_time),
+                ..location.location.into()
+            },
+            updated_at: location.updated_at,
+            received_at: location.received_at,
+            latency_ms: location.latency_ms,
+            clock_offset_ms: location.clock_offset_ms,
+            is_stale: location.is_stale,
+            suspect: location.suspect,
+            smoothed: location.smoothed.map(LocationFix::from),
+        }
+    }
+}
+
+/// v2 response with every member's latest fix
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SquadFixesResponse {
     pub squad_id: Uuid,
     pub squad_name: String,
-    pub locations: Vec<MemberLocation>,
+    pub members: Vec<MemberFix>,
     pub updated_at: DateTime<Utc>,
 }
+
+impl From<SquadLocationsResponse> for SquadFixesResponse {
+    fn from(response: SquadLocationsResponse) -> Self {
+        Self {
+            squad_id: response.squad_id,
+            squad_name: response.squad_name,
+            members: response.locations.into_iter().map(MemberFix::from).collect(),
+            updated_at: response.updated_at,
+        }
+    }
+}
+
+/// Distance and initial bearing from one member to another
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MemberPair {
+    pub from_member_id: Uuid,
+    pub to_member_id: Uuid,
+    pub distance_m: f64,
+    /// Clockwise from true north, as seen from `from_member_id`
+    pub bearing_deg: f64,
+}
+
+/// Another member as seen from one member
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Neighbour {
+    pub member_id: Uuid,
+    pub display_name: String,
+    pub distance_m: f64,
+    pub bearing_deg: f64,
+}
+
+/// Where one member stands relative to the rest of the squad
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MemberProximity {
+    pub member_id: Uuid,
+    pub display_name: String,
+    /// `None` for a member alone on the map
+    pub nearest: Option<Neighbour>,
+    pub distance_from_centroid_m: f64,
+    /// Members within the requested `within_m`, nearest first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub within: Option<Vec<Neighbour>>,
+}
+
+/// Distances between the members of a squad
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProximityResponse {
+    pub squad_id: Uuid,
+    /// Centre of the located members
+    pub centroid: Option<LocationFix>,
+    /// Furthest from the centroid first, so strays top the list
+    pub members: Vec<MemberProximity>,
+    /// Every pair of members once
+    pub pairs: Vec<MemberPair>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Where a distress call stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DistressState {
+    /// Rebroadcast to the squad until someone acknowledges it
+    Active,
+    /// Someone is responding
+    Acknowledged,
+    Resolved,
+}
+
+/// A member's SOS
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DistressCall {
+    pub sos_id: Uuid,
+    pub squad_id: Uuid,
+    pub member_id: Uuid,
+    pub display_name: String,
+    /// Exact latest position of the member in distress
+    pub location: GeoPoint,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub state: DistressState,
+    pub raised_at: DateTime<Utc>,
+    /// When the call or its location last changed
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledged_by: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_by: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// Request to raise (or update) an SOS
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SosRequest {
+    pub location: GeoPoint,
+    /// Optional note, e.g. the nature of the emergency
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Response after raising an SOS
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SosResponse {
+    pub call: DistressCall,
+    /// How often the device should send its location until the call is resolved
+    pub update_interval_secs: u32,
+}
+
+/// Unresolved distress calls of a squad
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SquadSosResponse {
+    pub squad_id: Uuid,
+    pub calls: Vec<DistressCall>,
+}
+
+/// An event pushed to every member on the squad stream
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SquadEvent {
+    /// A distress call was raised, moved, rebroadcast, acknowledged or resolved
+    Sos(DistressCall),
+    /// A message was posted to the squad
+    Message(SquadMessage),
+    /// A message was deleted by its author or the leader
+    MessageDeleted { squad_id: Uuid, message_id: Uuid },
+    /// A waypoint was added or changed
+    Waypoint(Waypoint),
+    /// A waypoint was deleted or expired
+    WaypointDeleted { squad_id: Uuid, waypoint_id: Uuid },
+    /// A separation alert was raised or cleared; sent to the leader only
+    Alert(AlertEvent),
+    /// The connection fell behind and `missed` events were dropped; the open
+    /// distress calls follow, and anything else should be re-fetched
+    Resync { squad_id: Uuid, missed: u64 },
+}
+
+impl SquadEvent {
+    pub fn squad_id(&self) -> Uuid {
+        match self {
+            SquadEvent::Sos(call) => call.squad_id,
+            SquadEvent::Message(message) => message.squad_id,
+            SquadEvent::MessageDeleted { squad_id, .. } => *squad_id,
+            SquadEvent::Waypoint(waypoint) => waypoint.squad_id,
+            SquadEvent::WaypointDeleted { squad_id, .. } => *squad_id,
+            SquadEvent::Alert(alert) => alert.squad_id,
+            SquadEvent::Resync { squad_id, .. } => *squad_id,
+        }
+    }
+
+    /// Whether only the squad leader receives this event
+    pub fn leader_only(&self) -> bool {
+        matches!(self, SquadEvent::Alert(_))
+    }
+}
+
+/// A text message posted to a squad
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SquadMessage {
+    pub message_id: Uuid,
+    pub squad_id: Uuid,
+    pub member_id: Uuid,
+    pub display_name: String,
+    pub text: String,
+    /// A position the message refers to, e.g. "meet here"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Request to post a message
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SendMessageRequest {
+    pub text: String,
+    #[serde(default)]
+    pub location: Option<GeoPoint>,
+}
+
+/// Messages of a squad, oldest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessagesResponse {
+    pub squad_id: Uuid,
+    pub messages: Vec<SquadMessage>,
+    /// More messages follow; ask again with the last `sent_at` as `since`
+    pub has_more: bool,
+}
+
+/// What a waypoint marks; clients pick its icon from this
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WaypointIcon {
+    RallyPoint,
+    Hazard,
+    Objective,
+    Other,
+}
+
+/// A named marker shared with the whole squad
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Waypoint {
+    pub waypoint_id: Uuid,
+    pub squad_id: Uuid,
+    pub name: String,
+    pub icon: WaypointIcon,
+    pub location: GeoPoint,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_by: Uuid,
+    pub created_by_name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Removed from the squad after this instant; kept until deleted if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Waypoint {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Request to add a waypoint, or replace one
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WaypointRequest {
+    pub name: String,
+    pub icon: WaypointIcon,
+    pub location: GeoPoint,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Waypoints of a squad, oldest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WaypointsResponse {
+    pub squad_id: Uuid,
+    pub waypoints: Vec<Waypoint>,
+}
+
+/// A route the squad is meant to follow
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PlannedRoute {
+    pub route_id: Uuid,
+    pub squad_id: Uuid,
+    pub name: String,
+    /// Vertices from start to finish
+    pub points: Vec<GeoPoint>,
+    /// Distance along the route from start to finish, in metres
+    pub length_m: f64,
+    /// Members further than this from the route are off route
+    pub max_deviation_m: f64,
+    /// Members expected to finish after this are behind schedule
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_by: Option<DateTime<Utc>>,
+    pub uploaded_by: Uuid,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// Request to set a squad's planned route (leader only)
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RouteRequest {
+    pub name: String,
+    /// Vertices from start to finish, at least two
+    pub points: Vec<GeoPoint>,
+    /// Off-route threshold in metres (default 50)
+    #[serde(default = "default_max_deviation_m")]
+    pub max_deviation_m: f64,
+    #[serde(default)]
+    pub finish_by: Option<DateTime<Utc>>,
+}
+
+pub fn default_max_deviation_m() -> f64 {
+    50.0
+}
+
+/// How far one member has come along the planned route
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RouteProgress {
+    pub member_id: Uuid,
+    pub display_name: String,
+    /// Distance from the nearest point of the route, in metres
+    pub cross_track_m: f64,
+    /// Distance along the route to the point nearest the member, in metres
+    pub progress_m: f64,
+    /// `progress_m` as a fraction of the route's length
+    pub progress_ratio: f64,
+    pub remaining_m: f64,
+    /// Last reported ground speed, in metres per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_mps: Option<f64>,
+    /// Expected arrival at the finish at `speed_mps`; unset while not moving
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta: Option<DateTime<Utc>>,
+    pub off_route: bool,
+    /// Expected (or still due) to finish after the route's `finish_by`
+    pub behind_schedule: bool,
+    pub is_stale: bool,
+    pub fix_time: DateTime<Utc>,
+}
+
+/// Progress of every located member along the squad's route
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RouteProgressResponse {
+    pub squad_id: Uuid,
+    pub route_id: Uuid,
+    pub length_m: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_by: Option<DateTime<Utc>>,
+    /// Least progress first, so stragglers top the list
+    pub members: Vec<RouteProgress>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Squad events that can be sent to webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    MemberJoined,
+    /// Left the squad or was removed by an admin
+    MemberLeft,
+    /// A distress call was raised, moved, repeated, acknowledged or resolved
+    Sos,
+    /// A separation alert was raised or cleared
+    Alert,
+    /// A member's latest fix became stale
+    MemberStale,
+}
+
+/// A registered webhook (the signing secret is only shown on creation)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub webhook_id: Uuid,
+    pub squad_id: Uuid,
+    pub url: String,
+    /// Events sent to this webhook; empty means every event
+    pub events: Vec<WebhookEventKind>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn wants(&self, event: WebhookEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Request to register a webhook
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    /// `http` or `https` URL that receives a POST per event
+    pub url: String,
+    /// Events to send; omit or leave empty for every event
+    #[serde(default)]
+    pub events: Vec<WebhookEventKind>,
+}
+
+/// A newly registered webhook with its signing secret
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    /// HMAC-SHA256 key for the `X-Squadz-Signature` header; not shown again
+    pub secret: String,
+}
+
+/// Webhooks of a squad
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhooksResponse {
+    pub squad_id: Uuid,
+    pub webhooks: Vec<Webhook>,
+}
+
+/// Body POSTed to a webhook
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookPayload {
+    /// Same for every retry of one delivery, for de-duplication
+    pub delivery_id: Uuid,
+    pub event: WebhookEventKind,
+    pub squad_id: Uuid,
+    pub at: DateTime<Utc>,
+    /// Event-specific body, e.g. the [`DistressCall`] or [`AlertEvent`]
+    #[schema(value_type = Object)]
+    pub data: serde_json::Value,
+}
+
+/// Where a delivery stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    Delivered,
+    /// Gave up after the last retry
+    Failed,
+}
+
+/// One event's delivery to a webhook
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub delivery_id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEventKind,
+    pub created_at: DateTime<Utc>,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    /// HTTP status of the last attempt, if the endpoint answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// Recent deliveries to a webhook, newest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookDeliveriesResponse {
+    pub webhook_id: Uuid,
+    pub deliveries: Vec<WebhookDelivery>,
+}
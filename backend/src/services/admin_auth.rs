@@ -0,0 +1,265 @@
+//! Dashboard admin authentication for Squadz
+//!
+//! A single admin credential (Argon2id hash) guards the dashboard. Logging in
+//! issues an opaque session token carried in an HttpOnly cookie, plus a CSRF
+//! token that every state-changing dashboard form must echo back. Too many
+//! failed logins in a row lock the login form for a while.
+
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use subtle::ConstantTimeEq;
+
+use super::password;
+
+/// Name of the cookie carrying the admin session token
+pub const SESSION_COOKIE: &str = "squadz_admin";
+/// Name of the cookie carrying the pre-login CSRF token
+pub const LOGIN_CSRF_COOKIE: &str = "squadz_login_csrf";
+/// Header the dashboard's scripts use to send the session CSRF token
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// An authenticated dashboard session
+#[derive(Debug, Clone)]
+pub struct AdminSession {
+    pub username: String,
+    pub token: String,
+    pub csrf_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AdminSession {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+
+    /// Check a submitted CSRF token in constant time
+    pub fn verify_csrf(&self, token: &str) -> bool {
+        constant_time_eq(&self.csrf_token, token)
+    }
+}
+
+/// Admin credential and active dashboard sessions
+#[derive(Clone)]
+pub struct AdminAuth {
+    username: String,
+    password_hash: String,
+    session_ttl_secs: u64,
+    /// Failed logins accepted per `login_failure_window` before the login
+    /// form is refused
+    max_login_failures: usize,
+    login_failure_window: Duration,
+    /// Recent failed logins, oldest first
+    login_failures: Arc<Mutex<VecDeque<DateTime<Utc>>>>,
+    /// Map from session token to session
+    sessions: Arc<RwLock<HashMap<String, AdminSession>>>,
+}
+
+impl AdminAuth {
+    pub fn new(username: String, password_hash: String, session_ttl_secs: u64) -> Self {
+        Self {
+            username,
+            password_hash,
+            session_ttl_secs,
+            max_login_failures: 5,
+            login_failure_window: Duration::seconds(300),
+            login_failures: Arc::default(),
+            sessions: Arc::default(),
+        }
+    }
+
+    /// Refuse logins once `max_failures` failed logins were seen within
+    /// `window_secs`
+    pub fn with_login_throttle(mut self, max_failures: usize, window_secs: i64) -> Self {
+        self.max_login_failures = max_failures;
+        self.login_failure_window = Duration::seconds(window_secs);
+        self
+    }
+
+    pub fn session_ttl_secs(&self) -> u64 {
+        self.session_ttl_secs
+    }
+
+    /// Whether logins are refused after repeated failures
+    pub fn login_throttled(&self, now: DateTime<Utc>) -> bool {
+        let window_start = now - self.login_failure_window;
+        let failures = self.login_failures.lock().unwrap();
+        failures.iter().filter(|at| **at > window_start).count() >= self.max_login_failures
+    }
+
+    /// Check the credential and open a new session on success
+    ///
+    /// Runs Argon2, so call it from a blocking thread. A failure counts
+    /// towards [`AdminAuth::login_throttled`].
+    pub fn login(&self, username: &str, password: &str) -> Option<AdminSession> {
+        // Always run the Argon2 verification so a wrong username costs the same
+        let user_ok = constant_time_eq(&self.username, username);
+        let password_ok = password::verify_password(password, &self.password_hash);
+        if !(user_ok && password_ok) {
+            let now = Utc::now();
+            let window_start = now - self.login_failure_window;
+            let mut failures = self.login_failures.lock().unwrap();
+            while failures.front().is_some_and(|at| *at <= window_start) {
+                failures.pop_front();
+            }
+            failures.push_back(now);
+            return None;
+        }
+
+        let session = AdminSession {
+            username: self.username.clone(),
+            token: generate_token(),
+            csrf_token: generate_token(),
+            expires_at: Utc::now() + Duration::seconds(self.session_ttl_secs as i64),
+        };
+        let mut sessions = self.sessions.write().unwrap();
+        sessions.retain(|_, s| !s.is_expired());
+        sessions.insert(session.token.clone(), session.clone());
+        Some(session)
+    }
+
+    /// Validate a session token and return the session if still live
+    pub fn validate(&self, token: &str) -> Option<AdminSession> {
+        let mut sessions = self.sessions.write().unwrap();
+        match sessions.get(token) {
+            Some(session) if session.is_expired() => {
+                sessions.remove(token);
+                None
+            }
+            Some(session) => Some(session.clone()),
+            None => None,
+        }
+    }
+
+    /// End a session
+    pub fn logout(&self, token: &str) -> bool {
+        self.sessions.write().unwrap().remove(token).is_some()
+    }
+}
+
+/// Read a cookie value from the request headers
+pub fn get_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+/// Generate a random URL-safe token
+pub fn generate_token() -> String {
+    use base64::Engine;
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Read the generated dashboard password kept at `path`, or generate one and
+/// write it there, readable by its owner only
+///
+/// Returns the password and whether it was generated just now. The file is
+/// never overwritten, so the password survives restarts until it is deleted.
+pub fn load_or_generate_password(path: &Path) -> io::Result<(String, bool)> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => {
+            let password = contents.trim().to_string();
+            if password.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "password file is empty"));
+            }
+            return Ok((password, false));
+        }
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        Err(_) => {}
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    let password = generate_token();
+    writeln!(file, "{password}")?;
+    file.sync_all()?;
+    Ok((password, true))
+}
+
+/// Compare two secrets without leaking the position of the first mismatch
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> AdminAuth {
+        let hash = password::hash_password("hunter2").unwrap();
+        AdminAuth::new("admin".to_string(), hash, 3600)
+    }
+
+    #[test]
+    fn test_login_and_logout() {
+        let auth = auth();
+        assert!(auth.login("admin", "wrong").is_none());
+        assert!(auth.login("root", "hunter2").is_none());
+
+        let session = auth.login("admin", "hunter2").unwrap();
+        assert!(auth.validate(&session.token).is_some());
+        assert!(session.verify_csrf(&session.csrf_token));
+        assert!(!session.verify_csrf("forged"));
+
+        assert!(auth.logout(&session.token));
+        assert!(auth.validate(&session.token).is_none());
+    }
+
+    #[test]
+    fn test_failed_logins_throttled() {
+        let auth = auth().with_login_throttle(2, 60);
+        assert!(auth.login("admin", "wrong").is_none());
+        assert!(!auth.login_throttled(Utc::now()));
+        assert!(auth.login("root", "wrong").is_none());
+        assert!(auth.login_throttled(Utc::now()));
+
+        // Failures age out of the window
+        assert!(!auth.login_throttled(Utc::now() + Duration::seconds(61)));
+    }
+
+    #[test]
+    fn test_generated_password_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dashboard-password");
+
+        let (password, generated) = load_or_generate_password(&path).unwrap();
+        assert!(generated);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Reused, not regenerated, on the next start
+        assert_eq!(load_or_generate_password(&path).unwrap(), (password, false));
+    }
+
+    #[test]
+    fn test_expired_session_rejected() {
+        let hash = password::hash_password("hunter2").unwrap();
+        let auth = AdminAuth::new("admin".to_string(), hash, 0);
+        let session = auth.login("admin", "hunter2").unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(auth.validate(&session.token).is_none());
+    }
+}
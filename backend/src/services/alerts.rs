@@ -0,0 +1,447 @@
+//! Separation alerts
+//!
+//! Each squad's [`AlertSettings`] are rules over its members' latest
+//! positions: too far from the squad's centroid, too far from the leader, or
+//! not moving for too long. Squads with a planned route also flag members who
+//! stray from it. Squads are re-evaluated after every location
+//! update and on a timer, so a member who stops reporting still trips the
+//! stationary rule. An alert is raised once when its rule starts firing and
+//! cleared once when it stops; both events go into a bounded per-squad log
+//! and onto the squad feed, which streams them to the leader.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::models::{
+    AlertEvent, AlertKind, AlertSettings, AlertState, MemberLocation, Squad, SquadEvent,
+};
+use crate::services::location_store::LocationStore;
+use crate::services::{geo, proximity, routes};
+use crate::AppState;
+
+/// How often every squad is re-checked, for rules that fire without new fixes
+pub const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Most alert events kept per squad
+pub const MAX_ALERT_EVENTS: usize = 500;
+
+/// A rule firing for a member
+struct Firing {
+    location: MemberLocation,
+    distance_m: Option<f64>,
+    stationary_since: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct SquadAlerts {
+    /// Raise event of every active alert
+    active: HashMap<(Uuid, AlertKind), AlertEvent>,
+    events: VecDeque<AlertEvent>,
+}
+
+/// Alert state of every squad (cheap to clone)
+#[derive(Clone)]
+pub struct AlertStore {
+    squads: Arc<Mutex<HashMap<Uuid, SquadAlerts>>>,
+}
+
+impl AlertStore {
+    pub fn new() -> Self {
+        Self {
+            squads: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Check a squad's rules against its latest positions, raising and
+    /// clearing alerts; returns the events this produced for the caller to
+    /// publish
+    pub fn evaluate(&self, squad: &Squad, locations: &LocationStore, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        let firing = firing_rules(squad, locations, now);
+
+        let mut squads = self.squads.lock().unwrap();
+        let alerts = squads.entry(squad.squad_id).or_default();
+        let mut events = Vec::new();
+
+        let ended: Vec<_> = alerts
+            .active
+            .keys()
+            .filter(|key| !firing.contains_key(key))
+            .copied()
+            .collect();
+        for key in ended {
+            if let Some(raised) = alerts.active.remove(&key) {
+                events.push(AlertEvent {
+                    state: AlertState::Cleared,
+                    at: now,
+                    location: None,
+                    ..raised
+                });
+            }
+        }
+
+        for ((member_id, kind), firing) in firing {
+            if alerts.active.contains_key(&(member_id, kind)) {
+                continue;
+            }
+            let event = AlertEvent {
+                alert_id: Uuid::new_v4(),
+                squad_id: squad.squad_id,
+                member_id,
+                display_name: firing.location.display_name.clone(),
+                kind,
+                state: AlertState::Raised,
+                at: now,
+                distance_m: firing.distance_m,
+                stationary_since: firing.stationary_since,
+                location: Some(firing.location.location.into()),
+            };
+            alerts.active.insert((member_id, kind), event.clone());
+            events.push(event);
+        }
+
+        for event in &events {
+            info!(
+                squad_id = %event.squad_id,
+                member_id = %event.member_id,
+                kind = ?event.kind,
+                state = ?event.state,
+                "Separation alert"
+            );
+            alerts.events.push_back(event.clone());
+            if alerts.events.len() > MAX_ALERT_EVENTS {
+                alerts.events.pop_front();
+            }
+        }
+        events
+    }
+
+    /// Active alerts (their raise events) and the event log after `since`
+    pub fn squad_alerts(
+        &self,
+        squad_id: &Uuid,
+        since: Option<DateTime<Utc>>,
+    ) -> (Vec<AlertEvent>, Vec<AlertEvent>) {
+        let squads = self.squads.lock().unwrap();
+        let Some(alerts) = squads.get(squad_id) else {
+            return Default::default();
+        };
+        let mut active: Vec<_> = alerts.active.values().cloned().collect();
+        active.sort_by_key(|event| event.at);
+        let events = alerts
+            .events
+            .iter()
+            .filter(|event| since.is_none_or(|since| event.at > since))
+            .cloned()
+            .collect();
+        (active, events)
+    }
+
+    /// Forget squads that no longer exist
+    pub fn retain_squads(&self, squad_ids: &HashSet<Uuid>) {
+        self.squads.lock().unwrap().retain(|id, _| squad_ids.contains(id));
+    }
+
+    /// Forget a deleted squad
+    pub fn remove_squad(&self, squad_id: &Uuid) {
+        self.squads.lock().unwrap().remove(squad_id);
+    }
+}
+
+impl Default for AlertStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Re-check a squad after its positions changed
+pub async fn check_squad(state: &AppState, squad_id: &Uuid) {
+    let manager = state.squad_manager.read().await;
+    let Some(squad) = manager.get_squad(squad_id) else {
+        return;
+    };
+    let store = state.location_store.read().await;
+    for event in state.alerts.evaluate(squad, &store, Utc::now()) {
+        state.feed.publish(SquadEvent::Alert(event));
+    }
+    // Follow members along the route between progress requests, so a pass
+    // over the same place resolves to the one they are on
+    if let Some(route) = &squad.route {
+        state.route_progress.follow(route, &store.get_squad_locations(squad_id));
+    }
+}
+
+/// Re-check every squad every [`SWEEP_INTERVAL`], dropping expired waypoints
+/// and location history
+pub fn spawn_sweeper(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            let expired = state.squad_manager.write().await.expire_waypoints(now);
+            for (squad_id, waypoint_id) in expired {
+                state.feed.publish(SquadEvent::WaypointDeleted { squad_id, waypoint_id });
+            }
+            state.location_store.write().await.expire_history(now);
+
+            let manager = state.squad_manager.read().await;
+            let store = state.location_store.read().await;
+            let squads = manager.list_squads();
+            for squad in &squads {
+                for event in state.alerts.evaluate(squad, &store, now) {
+                    state.feed.publish(SquadEvent::Alert(event));
+                }
+            }
+            // Forget the alerts and messages of deleted squads, and progress
+            // along routes that are gone
+            let squad_ids = squads.iter().map(|squad| squad.squad_id).collect();
+            state.alerts.retain_squads(&squad_ids);
+            state.messages.retain_squads(&squad_ids);
+            let route_ids = squads
+                .iter()
+                .filter_map(|squad| squad.route.as_ref().map(|route| route.route_id))
+                .collect();
+            state.route_progress.retain_routes(&route_ids);
+        }
+    });
+}
+
+/// Every (member, rule) currently firing in a squad
+fn firing_rules(
+    squad: &Squad,
+    store: &LocationStore,
+    now: DateTime<Utc>,
+) -> HashMap<(Uuid, AlertKind), Firing> {
+    let rules: &AlertSettings = &squad.settings.alerts;
+    let mut locations = store.get_squad_locations(&squad.squad_id);
+    locations.retain(|location| squad.members.iter().any(|m| m.member_id == location.member_id));
+    let mut firing = HashMap::new();
+
+    if let Some(max) = rules.max_distance_from_centroid_m {
+        // Same distances the proximity endpoint reports; a lone member has
+        // no squad to stray from
+        if locations.len() > 1 {
+            for member in proximity::measure(&locations, None).members {
+                if member.distance_from_centroid_m <= max {
+                    continue;
+                }
+                if let Some(location) = locations.iter().find(|l| l.member_id == member.member_id) {
+                    firing.insert(
+                        (member.member_id, AlertKind::FarFromSquad),
+                        Firing {
+                            location: location.clone(),
+                            distance_m: Some(member.distance_from_centroid_m),
+                            stationary_since: None,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(max) = rules.max_distance_from_leader_m {
+        if let Some(leader) = locations.iter().find(|l| l.member_id == squad.leader_id) {
+            for location in locations.iter().filter(|l| l.member_id != squad.leader_id) {
+                let distance =
+                    geo::distance_m(proximity::position(leader), proximity::position(location));
+                if distance > max {
+                    firing.insert(
+                        (location.member_id, AlertKind::FarFromLeader),
+                        Firing {
+                            location: location.clone(),
+                            distance_m: Some(distance),
+                            stationary_since: None,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(mins) = rules.stationary_after_mins {
+        let threshold = now - Duration::minutes(mins as i64);
+        for location in &locations {
+            let since = store.stationary_since(
+                &squad.squad_id,
+                &location.member_id,
+                rules.stationary_radius_m,
+            );
+            if let Some(since) = since.filter(|since| *since <= threshold) {
+                firing.insert(
+                    (location.member_id, AlertKind::Stationary),
+                    Firing {
+                        location: location.clone(),
+                        distance_m: None,
+                        stationary_since: Some(since),
+                    },
+                );
+            }
+        }
+    }
+
+    if let Some(route) = &squad.route {
+        for location in &locations {
+            // Passes of a route over the same place are equally far
+            let (cross_track_m, _) =
+                routes::locate(&route.points, proximity::position(location), None);
+            if cross_track_m > route.max_deviation_m {
+                firing.insert(
+                    (location.member_id, AlertKind::OffRoute),
+                    Firing {
+                        location: location.clone(),
+                        distance_m: Some(cross_track_m),
+                        stationary_since: None,
+                    },
+                );
+            }
+        }
+    }
+    firing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GeoPoint, SquadSettings};
+    use crate::services::audit::AuditContext;
+    use crate::services::squad_manager::{PassphraseCheck, SquadManager};
+
+    fn fix(latitude: f64, longitude: f64, secs_ago: i64) -> GeoPoint {
+        GeoPoint {
+            accuracy: Some(5.0),
+            fix_time: Some(Utc::now() - Duration::seconds(secs_ago)),
+            ..GeoPoint::at(latitude, longitude)
+        }
+    }
+
+    /// A squad of a leader and two members with the given rules
+    fn squad(alerts: AlertSettings) -> (Squad, Vec<Uuid>) {
+        let ctx = AuditContext::system();
+        let mut manager = SquadManager::new();
+        let settings = SquadSettings {
+            alerts,
+            ..Default::default()
+        };
+        let (squad, leader_id) =
+            manager.create_squad(&ctx, "SAR".to_string(), "Lead".to_string(), Some(settings), None);
+        let mut ids = vec![leader_id];
+        for name in ["Dog", "Medic"] {
+            let (_, member) = manager
+                .join_squad(&ctx, &squad.join_code, name.to_string(), PassphraseCheck::NotRequired)
+                .unwrap();
+            ids.push(member);
+        }
+        (manager.get_squad(&squad.squad_id).unwrap().clone(), ids)
+    }
+
+    fn place(store: &mut LocationStore, squad: &Squad, member_id: Uuid, fixes: Vec<GeoPoint>) {
+        store
+            .merge_history(squad.squad_id, member_id, "M".to_string(), fixes, &squad.settings)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_stray_is_raised_once_then_cleared() {
+        let (squad, ids) = squad(AlertSettings {
+            max_distance_from_centroid_m: Some(500.0),
+            max_distance_from_leader_m: Some(800.0),
+            ..Default::default()
+        });
+        let mut store = LocationStore::new();
+        let alerts = AlertStore::new();
+
+        place(&mut store, &squad, ids[0], vec![fix(45.0, 7.0, 0)]);
+        place(&mut store, &squad, ids[1], vec![fix(45.001, 7.0, 0)]);
+        // ~1.1 km east of the others
+        place(&mut store, &squad, ids[2], vec![fix(45.0, 7.014, 0)]);
+
+        let raised = alerts.evaluate(&squad, &store, Utc::now());
+        let mut kinds: Vec<_> = raised.iter().map(|e| (e.member_id, e.kind)).collect();
+        kinds.sort_by_key(|(_, kind)| *kind as u8);
+        assert_eq!(
+            kinds,
+            vec![(ids[2], AlertKind::FarFromSquad), (ids[2], AlertKind::FarFromLeader)]
+        );
+        assert!(raised.iter().all(|e| e.state == AlertState::Raised));
+
+        // Still out there: nothing new
+        assert!(alerts.evaluate(&squad, &store, Utc::now()).is_empty());
+
+        place(&mut store, &squad, ids[2], vec![fix(45.0005, 7.0, 0)]);
+        let cleared = alerts.evaluate(&squad, &store, Utc::now());
+        assert_eq!(cleared.len(), 2);
+        assert!(cleared.iter().all(|e| e.state == AlertState::Cleared));
+
+        let (active, events) = alerts.squad_alerts(&squad.squad_id, None);
+        assert!(active.is_empty());
+        assert_eq!(events.len(), 4);
+    }
+
+    #[test]
+    fn test_stationary_member() {
+        let (squad, ids) = squad(AlertSettings {
+            stationary_after_mins: Some(10),
+            ..Default::default()
+        });
+        let mut store = LocationStore::new();
+        let alerts = AlertStore::new();
+
+        // Moving until 20 minutes ago, then wandering within a few metres
+        place(
+            &mut store,
+            &squad,
+            ids[1],
+            vec![
+                fix(45.01, 7.0, 1500),
+                fix(45.0, 7.0, 1200),
+                fix(45.0001, 7.0, 600),
+                fix(45.0, 7.0001, 60),
+            ],
+        );
+        // Still moving
+        place(&mut store, &squad, ids[2], vec![fix(45.02, 7.0, 120), fix(45.0, 7.0, 0)]);
+
+        let raised = alerts.evaluate(&squad, &store, Utc::now());
+        assert_eq!(raised.len(), 1);
+        assert_eq!((raised[0].member_id, raised[0].kind), (ids[1], AlertKind::Stationary));
+        let since = raised[0].stationary_since.unwrap();
+        assert!(Utc::now() - since >= Duration::minutes(19));
+    }
+
+    #[test]
+    fn test_off_route_member() {
+        let (mut squad, ids) = squad(AlertSettings::default());
+        let mut store = LocationStore::new();
+        let alerts = AlertStore::new();
+
+        place(&mut store, &squad, ids[1], vec![fix(45.005, 7.0, 0)]);
+        // ~160 m east of the north-bound route
+        place(&mut store, &squad, ids[2], vec![fix(45.005, 7.002, 0)]);
+        assert!(alerts.evaluate(&squad, &store, Utc::now()).is_empty());
+
+        let points = vec![fix(45.0, 7.0, 0), fix(45.01, 7.0, 0)];
+        squad.route = Some(crate::models::PlannedRoute {
+            route_id: Uuid::new_v4(),
+            squad_id: squad.squad_id,
+            name: "Ridge".to_string(),
+            length_m: routes::length_m(&points),
+            points,
+            max_deviation_m: 100.0,
+            finish_by: None,
+            uploaded_by: ids[0],
+            uploaded_at: Utc::now(),
+        });
+        let raised = alerts.evaluate(&squad, &store, Utc::now());
+        assert_eq!(raised.len(), 1);
+        assert_eq!((raised[0].member_id, raised[0].kind), (ids[2], AlertKind::OffRoute));
+        assert!((raised[0].distance_m.unwrap() - 157.0).abs() < 2.0);
+
+        squad.route = None;
+        let cleared = alerts.evaluate(&squad, &store, Utc::now());
+        assert_eq!(cleared[0].state, AlertState::Cleared);
+    }
+}
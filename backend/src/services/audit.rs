@@ -0,0 +1,648 @@
+//! Audit log for Squadz
+//!
+//! Append-only record of every state change: squad lifecycle, membership,
+//! sessions and admin actions. Entries are hash-chained (SHA-256 over the
+//! previous hash and the entry body) so any edit, removal or reordering of
+//! past entries is detectable with [`AuditLog::verify`].
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tower_http::request_id::RequestId;
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::auth::{AuthenticatedAdmin, AuthenticatedMember};
+
+/// Hash used as `prev_hash` for the first entry in the chain
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Who performed an audited action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Actor {
+    /// A squad member authenticated by API key
+    Member(Uuid),
+    /// A dashboard admin
+    Admin(String),
+    /// An unauthenticated caller (e.g. creating or joining a squad)
+    Anonymous,
+    /// The server itself (background cleanup, startup)
+    System,
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Actor::Member(id) => write!(f, "member:{}", id),
+            Actor::Admin(name) => write!(f, "admin:{}", name),
+            Actor::Anonymous => write!(f, "anonymous"),
+            Actor::System => write!(f, "system"),
+        }
+    }
+}
+
+/// Actor and request ID attached to every audit entry
+///
+/// Extracted from the request: the actor comes from the auth extensions, the
+/// request ID from the `x-request-id` header set by the request-ID layer.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor: Actor,
+    pub request_id: Option<String>,
+}
+
+impl AuditContext {
+    /// Context for actions the server takes on its own
+    pub fn system() -> Self {
+        Self {
+            actor: Actor::System,
+            request_id: None,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuditContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let actor = if let Some(admin) = parts.extensions.get::<AuthenticatedAdmin>() {
+            Actor::Admin(admin.session.username.clone())
+        } else if let Some(member) = parts.extensions.get::<AuthenticatedMember>() {
+            Actor::Member(member.session.member_id)
+        } else {
+            Actor::Anonymous
+        };
+
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .map(str::to_string);
+
+        Ok(Self { actor, request_id })
+    }
+}
+
+/// A single audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    /// Position in the chain, starting at 0
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub actor: String,
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squad_id: Option<Uuid>,
+    /// Member the action was applied to, when different from the actor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    #[schema(value_type = Option<Object>)]
+    pub details: serde_json::Value,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// SHA-256 over the previous hash and the entry with `hash` blanked
+    fn compute_hash(&self) -> String {
+        let mut body = self.clone();
+        body.hash = String::new();
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(serde_json::to_vec(&body).expect("audit entries always serialize"));
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Filter for querying the audit log
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub squad_id: Option<Uuid>,
+    pub member_id: Option<Uuid>,
+    /// Exact actor, e.g. `admin:admin` or `member:<uuid>`
+    pub actor: Option<String>,
+    /// Action prefix, e.g. `squad.` or `session.revoke`
+    pub action: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    /// Return only the newest `limit` matches
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.squad_id.is_none_or(|id| entry.squad_id == Some(id))
+            && self.member_id.is_none_or(|id| entry.member_id == Some(id))
+            && self.actor.as_ref().is_none_or(|a| &entry.actor == a)
+            && self
+                .action
+                .as_ref()
+                .is_none_or(|a| entry.action.starts_with(a.as_str()))
+            && self.since.is_none_or(|t| entry.timestamp >= t)
+    }
+}
+
+/// Result of re-checking the hash chain
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub entries: usize,
+    /// Sequence number of the first entry that fails verification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_invalid_seq: Option<u64>,
+}
+
+/// Running check of the hash chain over entries in order
+struct ChainCheck {
+    next_seq: u64,
+    prev_hash: String,
+    entries: usize,
+    first_invalid_seq: Option<u64>,
+}
+
+impl ChainCheck {
+    /// Check a chain whose first entry has `seq` and links to `prev_hash`
+    fn starting_at(seq: u64, prev_hash: String) -> Self {
+        Self {
+            next_seq: seq,
+            prev_hash,
+            entries: 0,
+            first_invalid_seq: None,
+        }
+    }
+
+    fn push(&mut self, entry: &AuditEntry) {
+        self.entries += 1;
+        if self.first_invalid_seq.is_some() {
+            return;
+        }
+        let valid = entry.seq == self.next_seq
+            && entry.prev_hash == self.prev_hash
+            && entry.hash == entry.compute_hash();
+        if !valid {
+            self.first_invalid_seq = Some(entry.seq);
+            return;
+        }
+        self.next_seq += 1;
+        self.prev_hash.clone_from(&entry.hash);
+    }
+
+    fn finish(self) -> ChainVerification {
+        ChainVerification {
+            valid: self.first_invalid_seq.is_none(),
+            entries: self.entries,
+            first_invalid_seq: self.first_invalid_seq,
+        }
+    }
+}
+
+/// Newest entries kept in memory by a log without a file
+const MAX_RECENT: usize = 10_000;
+
+struct AuditInner {
+    /// Sequence number of the next entry
+    next_seq: u64,
+    /// Hash of the newest entry, which the next one links to
+    last_hash: String,
+    /// Newest entries of an in-memory log, at most [`MAX_RECENT`]
+    recent: VecDeque<AuditEntry>,
+    /// Hash the oldest entry in `recent` links to
+    recent_prev_hash: String,
+    /// JSON Lines file every entry is appended to; queries read it back
+    file: Option<(PathBuf, File)>,
+}
+
+impl Default for AuditInner {
+    fn default() -> Self {
+        Self {
+            next_seq: 0,
+            last_hash: GENESIS_HASH.to_string(),
+            recent: VecDeque::new(),
+            recent_prev_hash: GENESIS_HASH.to_string(),
+            file: None,
+        }
+    }
+}
+
+/// Where queries read entries from, captured under the lock
+enum Source {
+    Memory(Vec<AuditEntry>, ChainCheck),
+    /// The first `len` bytes of the file, all whole entries
+    File(PathBuf, u64),
+}
+
+/// Shared, append-only audit log
+///
+/// Without a file only the newest [`MAX_RECENT`] entries are kept. With one,
+/// nothing is kept in memory beyond the chain's tail; queries, exports and
+/// verification read the file.
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    inner: Arc<Mutex<AuditInner>>,
+}
+
+impl AuditLog {
+    /// In-memory audit log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Audit log persisted as JSON Lines at `path`
+    ///
+    /// Existing entries are read through once so the hash chain continues
+    /// across restarts. An incomplete final line, left by a crash mid-write,
+    /// is cut off with a warning; a broken chain is logged but does not stop
+    /// the server.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut check = ChainCheck::starting_at(0, GENESIS_HASH.to_string());
+        let mut last: Option<AuditEntry> = None;
+        let mut missing_newline = false;
+
+        let mut offset = 0;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            let is_last = reader.fill_buf()?.is_empty();
+            if !line.iter().all(u8::is_ascii_whitespace) {
+                match serde_json::from_slice::<AuditEntry>(&line) {
+                    Ok(entry) => {
+                        check.push(&entry);
+                        last = Some(entry);
+                        // A whole entry whose newline never made it to disk
+                        missing_newline = line.last() != Some(&b'\n');
+                    }
+                    Err(e) if is_last => {
+                        warn!(
+                            "Dropping incomplete last line of audit log {} ({} bytes): {}",
+                            path.display(),
+                            read,
+                            e
+                        );
+                        file.set_len(offset)?;
+                        break;
+                    }
+                    Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+                }
+            }
+            offset += read as u64;
+        }
+        if missing_newline {
+            writeln!(file)?;
+        }
+
+        let verification = check.finish();
+        if !verification.valid {
+            warn!(
+                "Audit log {} fails hash chain verification at seq {:?}",
+                path.display(),
+                verification.first_invalid_seq
+            );
+        }
+        let (next_seq, last_hash) = last
+            .map(|last| (last.seq + 1, last.hash))
+            .unwrap_or_else(|| (0, GENESIS_HASH.to_string()));
+        Ok(Self {
+            inner: Arc::new(Mutex::new(AuditInner {
+                next_seq,
+                last_hash,
+                file: Some((path.to_path_buf(), file)),
+                ..Default::default()
+            })),
+        })
+    }
+
+    /// Append an entry to the log
+    pub fn record(
+        &self,
+        ctx: &AuditContext,
+        action: &str,
+        squad_id: Option<Uuid>,
+        member_id: Option<Uuid>,
+        details: serde_json::Value,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        let seq = inner.next_seq;
+        let mut entry = AuditEntry {
+            seq,
+            timestamp: Utc::now(),
+            request_id: ctx.request_id.clone(),
+            actor: ctx.actor.to_string(),
+            action: action.to_string(),
+            squad_id,
+            member_id,
+            details,
+            prev_hash: inner.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        info!(
+            target: "squadz::audit",
+            seq,
+            action,
+            actor = %entry.actor,
+            squad_id = ?squad_id,
+            member_id = ?member_id,
+            request_id = ?entry.request_id,
+        );
+
+        inner.next_seq += 1;
+        inner.last_hash.clone_from(&entry.hash);
+        match inner.file.as_mut() {
+            Some((_, file)) => {
+                let line = serde_json::to_string(&entry).expect("audit entries always serialize");
+                if let Err(e) = writeln!(file, "{}", line) {
+                    error!("Failed to persist audit entry {}: {}", seq, e);
+                }
+            }
+            None => {
+                inner.recent.push_back(entry);
+                if inner.recent.len() > MAX_RECENT {
+                    if let Some(oldest) = inner.recent.pop_front() {
+                        inner.recent_prev_hash = oldest.hash;
+                    }
+                }
+            }
+        }
+    }
+
+    /// What to read entries from; the file is read without holding the lock
+    fn source(&self) -> io::Result<Source> {
+        let inner = self.inner.lock().unwrap();
+        Ok(match &inner.file {
+            Some((path, file)) => Source::File(path.clone(), file.metadata()?.len()),
+            None => {
+                let first_seq = inner.next_seq - inner.recent.len() as u64;
+                let check = ChainCheck::starting_at(first_seq, inner.recent_prev_hash.clone());
+                Source::Memory(inner.recent.iter().cloned().collect(), check)
+            }
+        })
+    }
+
+    /// Call `f` with every entry, oldest first
+    fn for_each(&self, mut f: impl FnMut(AuditEntry)) -> io::Result<()> {
+        match self.source()? {
+            Source::Memory(entries, _) => entries.into_iter().for_each(f),
+            Source::File(path, len) => {
+                for line in BufReader::new(File::open(path)?.take(len)).lines() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let entry = serde_json::from_str(&line)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    f(entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Entries matching a filter, oldest first
+    pub fn query(&self, query: &AuditQuery) -> io::Result<Vec<AuditEntry>> {
+        let mut matching = VecDeque::new();
+        self.for_each(|entry| {
+            if !query.matches(&entry) {
+                return;
+            }
+            // Keep the newest `limit` entries
+            if query.limit.is_some_and(|limit| matching.len() >= limit) {
+                matching.pop_front();
+            }
+            if query.limit != Some(0) {
+                matching.push_back(entry);
+            }
+        })?;
+        Ok(matching.into())
+    }
+
+    /// Export entries matching a filter as JSON Lines
+    pub fn export_jsonl(&self, query: &AuditQuery) -> io::Result<String> {
+        Ok(self
+            .query(query)?
+            .iter()
+            .map(|e| serde_json::to_string(e).expect("audit entries always serialize") + "\n")
+            .collect())
+    }
+
+    /// Re-check every hash and chain link
+    ///
+    /// An in-memory log that has dropped old entries checks those it kept.
+    pub fn verify(&self) -> io::Result<ChainVerification> {
+        if let Source::Memory(entries, mut check) = self.source()? {
+            entries.iter().for_each(|entry| check.push(entry));
+            return Ok(check.finish());
+        }
+        let mut check = ChainCheck::starting_at(0, GENESIS_HASH.to_string());
+        self.for_each(|entry| check.push(&entry))?;
+        Ok(check.finish())
+    }
+
+    /// Number of entries ever recorded (for metrics)
+    pub fn count(&self) -> usize {
+        self.inner.lock().unwrap().next_seq as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> AuditContext {
+        AuditContext {
+            actor: Actor::Admin("admin".to_string()),
+            request_id: Some("req-1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_chain_verifies() {
+        let log = AuditLog::new();
+        let squad_id = Uuid::new_v4();
+        log.record(&ctx(), "squad.create", Some(squad_id), None, serde_json::Value::Null);
+        log.record(&ctx(), "squad.join", Some(squad_id), Some(Uuid::new_v4()), serde_json::Value::Null);
+
+        let result = log.verify().unwrap();
+        assert!(result.valid);
+        assert_eq!(result.entries, 2);
+
+        let entries = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(entries[0].actor, "admin:admin");
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let log = AuditLog::new();
+        log.record(&ctx(), "squad.create", None, None, serde_json::Value::Null);
+        log.record(&ctx(), "squad.delete", None, None, serde_json::Value::Null);
+
+        log.inner.lock().unwrap().recent[0].actor = "anonymous".to_string();
+
+        let result = log.verify().unwrap();
+        assert!(!result.valid);
+        assert_eq!(result.first_invalid_seq, Some(0));
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let log = AuditLog::new();
+        let squad_id = Uuid::new_v4();
+        log.record(&ctx(), "squad.create", Some(squad_id), None, serde_json::Value::Null);
+        log.record(&ctx(), "session.create", Some(squad_id), None, serde_json::Value::Null);
+        log.record(&ctx(), "squad.create", Some(Uuid::new_v4()), None, serde_json::Value::Null);
+
+        let query = AuditQuery {
+            squad_id: Some(squad_id),
+            action: Some("squad.".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&query).unwrap().len(), 1);
+
+        let export = log.export_jsonl(&AuditQuery::default()).unwrap();
+        assert_eq!(export.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_memory_keeps_newest_entries() {
+        let log = AuditLog::new();
+        for _ in 0..MAX_RECENT + 5 {
+            log.record(&ctx(), "squad.create", None, None, serde_json::Value::Null);
+        }
+
+        assert_eq!(log.count(), MAX_RECENT + 5);
+        let limited = AuditQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let newest = log.query(&limited).unwrap();
+        assert_eq!(newest.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![10_003, 10_004]);
+        let result = log.verify().unwrap();
+        assert!(result.valid);
+        assert_eq!(result.entries, MAX_RECENT);
+    }
+
+    #[test]
+    fn test_file_log_served_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let squad_id = Uuid::new_v4();
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.create", Some(squad_id), None, serde_json::Value::Null);
+        drop(log);
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.delete", Some(squad_id), None, serde_json::Value::Null);
+        assert!(log.inner.lock().unwrap().recent.is_empty());
+
+        let query = AuditQuery {
+            squad_id: Some(squad_id),
+            ..Default::default()
+        };
+        let actions: Vec<_> = log.query(&query).unwrap().into_iter().map(|e| e.action).collect();
+        assert_eq!(actions, vec!["squad.create", "squad.delete"]);
+        assert_eq!(log.export_jsonl(&query).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_reopen_continues_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.create", None, None, serde_json::Value::Null);
+        drop(log);
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.delete", None, None, serde_json::Value::Null);
+        assert_eq!(log.count(), 2);
+        assert!(log.verify().unwrap().valid);
+    }
+
+    #[test]
+    fn test_open_truncates_incomplete_last_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.create", None, None, serde_json::Value::Null);
+        drop(log);
+        let complete = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(br#"{"seq":1,"timestamp":"20"#)
+            .unwrap();
+
+        let log = AuditLog::open(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), complete);
+        log.record(&ctx(), "squad.delete", None, None, serde_json::Value::Null);
+        drop(log);
+
+        let log = AuditLog::open(&path).unwrap();
+        assert_eq!(log.count(), 2);
+        assert!(log.verify().unwrap().valid);
+    }
+
+    #[test]
+    fn test_open_keeps_entry_missing_its_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.create", None, None, serde_json::Value::Null);
+        drop(log);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.trim_end()).unwrap();
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.delete", None, None, serde_json::Value::Null);
+        drop(log);
+        let log = AuditLog::open(&path).unwrap();
+        assert_eq!(log.count(), 2);
+        assert!(log.verify().unwrap().valid);
+    }
+
+    #[test]
+    fn test_open_loads_broken_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(&ctx(), "squad.create", None, None, serde_json::Value::Null);
+        drop(log);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.replace("admin:admin", "anonymous")).unwrap();
+
+        let log = AuditLog::open(&path).unwrap();
+        assert_eq!(log.count(), 1);
+        assert_eq!(log.verify().unwrap().first_invalid_seq, Some(0));
+    }
+}
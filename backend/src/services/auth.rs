@@ -4,13 +4,15 @@
 use axum::{
     body::Body,
     extract::State,
-    http::{Request, StatusCode},
+    http::{Method, Request},
     middleware::Next,
     response::Response,
 };
 use std::sync::Arc;
 
+use crate::api::error::{ApiError, ErrorCode};
 use crate::AppState;
+use super::admin_auth::{self, AdminSession, CSRF_HEADER, SESSION_COOKIE};
 use super::session::MemberSession;
 
 /// Extension to store validated session in request
@@ -19,6 +21,12 @@ pub struct AuthenticatedMember {
     pub session: MemberSession,
 }
 
+/// Extension to store a validated dashboard admin session in request
+#[derive(Clone)]
+pub struct AuthenticatedAdmin {
+    pub session: AdminSession,
+}
+
 /// Extract API key from Authorization header
 fn extract_api_key(request: &Request<Body>) -> Option<String> {
     request
@@ -34,13 +42,15 @@ pub async fn auth_middleware(
     State(state): State<Arc<AppState>>,
     mut request: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
-    let api_key = extract_api_key(&request).ok_or(StatusCode::UNAUTHORIZED)?;
+) -> Result<Response, ApiError> {
+    let api_key = extract_api_key(&request).ok_or_else(|| {
+        ApiError::new(ErrorCode::Unauthorized, "Missing Authorization: Bearer API key")
+    })?;
 
     let session = state
         .session_store
         .validate(&api_key)
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(|| ApiError::new(ErrorCode::Unauthorized, "Invalid or expired API key"))?;
 
     // Add authenticated member to request extensions
     request.extensions_mut().insert(AuthenticatedMember { session });
@@ -48,17 +58,32 @@ pub async fn auth_middleware(
     Ok(next.run(request).await)
 }
 
-/// Optional auth - doesn't fail if no auth, just doesn't add session
-pub async fn optional_auth_middleware(
+/// Admin auth middleware - requires a dashboard session cookie, and the
+/// session's CSRF token in the `X-CSRF-Token` header for anything but GET
+pub async fn admin_auth_middleware(
     State(state): State<Arc<AppState>>,
     mut request: Request<Body>,
     next: Next,
-) -> Response {
-    if let Some(api_key) = extract_api_key(&request) {
-        if let Some(session) = state.session_store.validate(&api_key) {
-            request.extensions_mut().insert(AuthenticatedMember { session });
+) -> Result<Response, ApiError> {
+    let session = admin_auth::get_cookie(request.headers(), SESSION_COOKIE)
+        .and_then(|token| state.admin_auth.validate(token))
+        .ok_or_else(|| ApiError::new(ErrorCode::Unauthorized, "Dashboard login required"))?;
+
+    if request.method() != Method::GET {
+        let csrf_ok = request
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|token| session.verify_csrf(token));
+        if !csrf_ok {
+            return Err(ApiError::new(
+                ErrorCode::InvalidCsrfToken,
+                "Missing or invalid X-CSRF-Token header",
+            ));
         }
     }
 
-    next.run(request).await
+    request.extensions_mut().insert(AuthenticatedAdmin { session });
+
+    Ok(next.run(request).await)
 }
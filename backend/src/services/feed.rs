@@ -0,0 +1,129 @@
+//! Live squad feed
+//!
+//! Events every member of a squad should see as they happen go through a
+//! broadcast channel of [`SquadEvent`]s per squad, so a busy squad can't push
+//! another squad's streams behind. A channel is made on the first subscriber
+//! and dropped once nobody listens.
+//!
+//! The webhook forwarder must not miss events, so it takes a [`queue`] that
+//! is filled as events are published instead of subscribing.
+//!
+//! [`queue`]: SquadFeed::queue
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+use crate::models::SquadEvent;
+
+/// Events buffered for a slow subscriber before it misses some
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Publisher of squad events (cheap to clone)
+#[derive(Clone, Default)]
+pub struct SquadFeed {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    channels: HashMap<Uuid, broadcast::Sender<SquadEvent>>,
+    queue: Option<mpsc::UnboundedSender<SquadEvent>>,
+}
+
+impl SquadFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn publish(&self, event: SquadEvent) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(queue) = &inner.queue {
+            let _ = queue.send(event.clone());
+        }
+        let squad_id = event.squad_id();
+        if let Some(sender) = inner.channels.get(&squad_id) {
+            // No receivers left; nobody is watching live
+            if sender.send(event).is_err() {
+                inner.channels.remove(&squad_id);
+            }
+        }
+    }
+
+    /// Live events of one squad
+    pub fn subscribe(&self, squad_id: Uuid) -> broadcast::Receiver<SquadEvent> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.channels.retain(|_, sender| sender.receiver_count() > 0);
+        inner
+            .channels
+            .entry(squad_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Every event of every squad published from now on, none dropped
+    ///
+    /// Meant for one consumer; taking a new queue ends the previous one.
+    pub fn queue(&self) -> mpsc::UnboundedReceiver<SquadEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.inner.lock().unwrap().queue = Some(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resync(squad_id: Uuid) -> SquadEvent {
+        SquadEvent::Resync { squad_id, missed: 0 }
+    }
+
+    #[test]
+    fn test_subscribers_see_only_their_squad() {
+        let feed = SquadFeed::new();
+        let (alpha, bravo) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut events = feed.subscribe(alpha);
+
+        feed.publish(resync(bravo));
+        feed.publish(resync(alpha));
+
+        assert_eq!(events.try_recv().unwrap().squad_id(), alpha);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_queue_keeps_what_subscribers_miss() {
+        let feed = SquadFeed::new();
+        let squad_id = Uuid::new_v4();
+        let mut events = feed.subscribe(squad_id);
+        let mut queue = feed.queue();
+
+        for _ in 0..CHANNEL_CAPACITY * 2 {
+            feed.publish(resync(squad_id));
+        }
+
+        assert!(matches!(
+            events.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(_))
+        ));
+        let mut queued = 0;
+        while queue.try_recv().is_ok() {
+            queued += 1;
+        }
+        assert_eq!(queued, CHANNEL_CAPACITY * 2);
+    }
+
+    #[test]
+    fn test_channel_dropped_without_subscribers() {
+        let feed = SquadFeed::new();
+        let squad_id = Uuid::new_v4();
+        drop(feed.subscribe(squad_id));
+
+        feed.publish(resync(squad_id));
+
+        assert!(feed.inner.lock().unwrap().channels.is_empty());
+    }
+}
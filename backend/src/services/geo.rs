@@ -0,0 +1,124 @@
+//! Geodesy helpers
+//!
+//! Positions are WGS84 degrees. Distances use the haversine formula on a
+//! sphere, which is within 0.5% of the ellipsoid everywhere on Earth; that is
+//! well inside GPS error at the ranges squads cover.
+
+use crate::models::GeoPoint;
+
+/// Mean Earth radius (IUGG), in metres
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Great-circle distance between two points, in metres
+pub fn distance_m(a: &GeoPoint, b: &GeoPoint) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.longitude - a.longitude).to_radians();
+
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    // Clamped so rounding cannot push asin out of its domain for antipodes
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+/// Initial great-circle bearing from `a` to `b`, clockwise from true north
+/// in `[0, 360)`
+pub fn bearing_deg(a: &GeoPoint, b: &GeoPoint) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let dlon = (b.longitude - a.longitude).to_radians();
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Geographic centre of `points` as (latitude, longitude), averaging on the
+/// sphere so groups straddling the antimeridian come out right
+pub fn centroid<'a>(points: impl IntoIterator<Item = &'a GeoPoint>) -> Option<(f64, f64)> {
+    let (mut x, mut y, mut z, mut n) = (0.0, 0.0, 0.0, 0usize);
+    for point in points {
+        let (lat, lon) = (point.latitude.to_radians(), point.longitude.to_radians());
+        x += lat.cos() * lon.cos();
+        y += lat.cos() * lon.sin();
+        z += lat.sin();
+        n += 1;
+    }
+    if n == 0 {
+        return None;
+    }
+    let (x, y, z) = (x / n as f64, y / n as f64, z / n as f64);
+    Some((z.atan2(x.hypot(y)).to_degrees(), y.atan2(x).to_degrees()))
+}
+
+/// Offset of `point` from `origin` in metres, as (east, north)
+///
+/// An equirectangular projection: accurate to well under a metre within a
+/// few kilometres of `origin`, which is all smoothing and route matching need.
+pub fn to_local(origin: &GeoPoint, point: &GeoPoint) -> (f64, f64) {
+    let mut dlon = point.longitude - origin.longitude;
+    if dlon > 180.0 {
+        dlon -= 360.0;
+    } else if dlon < -180.0 {
+        dlon += 360.0;
+    }
+    let east = dlon.to_radians() * EARTH_RADIUS_M * origin.latitude.to_radians().cos();
+    let north = (point.latitude - origin.latitude).to_radians() * EARTH_RADIUS_M;
+    (east, north)
+}
+
+/// Inverse of [`to_local`]: the (latitude, longitude) `east`/`north` metres
+/// from `origin`
+pub fn from_local(origin: &GeoPoint, east: f64, north: f64) -> (f64, f64) {
+    let latitude = origin.latitude + (north / EARTH_RADIUS_M).to_degrees();
+    let cos_lat = origin.latitude.to_radians().cos().max(1e-9);
+    let longitude = origin.longitude + (east / (EARTH_RADIUS_M * cos_lat)).to_degrees();
+    let longitude = (longitude + 180.0).rem_euclid(360.0) - 180.0;
+    (latitude, longitude)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        // One degree of latitude is ~111.2 km on the mean sphere
+        let d = distance_m(&GeoPoint::at(0.0, 0.0), &GeoPoint::at(1.0, 0.0));
+        assert!((d - 111_195.0).abs() < 1.0, "{d}");
+
+        // London to Paris, ~343.5 km
+        let d = distance_m(&GeoPoint::at(51.5074, -0.1278), &GeoPoint::at(48.8566, 2.3522));
+        assert!((d - 343_500.0).abs() < 1_000.0, "{d}");
+
+        // Across the antimeridian is the short way round
+        let d = distance_m(&GeoPoint::at(0.0, 179.5), &GeoPoint::at(0.0, -179.5));
+        assert!((d - 111_195.0).abs() < 1.0, "{d}");
+
+        assert_eq!(distance_m(&GeoPoint::at(45.0, 7.0), &GeoPoint::at(45.0, 7.0)), 0.0);
+    }
+
+    #[test]
+    fn test_bearing_and_centroid() {
+        let origin = GeoPoint::at(45.0, 7.0);
+        assert!((bearing_deg(&origin, &GeoPoint::at(46.0, 7.0)) - 0.0).abs() < 1e-9);
+        assert!((bearing_deg(&origin, &GeoPoint::at(45.0, 8.0)) - 89.65).abs() < 0.01);
+        assert!((bearing_deg(&origin, &GeoPoint::at(44.0, 7.0)) - 180.0).abs() < 1e-9);
+        assert!((bearing_deg(&origin, &GeoPoint::at(45.0, 6.0)) - 270.35).abs() < 0.01);
+
+        let (lat, lon) = centroid(&[GeoPoint::at(10.0, 179.0), GeoPoint::at(-10.0, -179.0)]).unwrap();
+        assert!(lat.abs() < 1e-9 && (lon.abs() - 180.0).abs() < 1e-9, "{lat} {lon}");
+        assert!(centroid(&[]).is_none());
+    }
+
+    #[test]
+    fn test_local_projection_round_trip() {
+        let origin = GeoPoint::at(45.0, 7.0);
+        let target = GeoPoint::at(45.01, 7.02);
+        let (east, north) = to_local(&origin, &target);
+        assert!((east.hypot(north) - distance_m(&origin, &target)).abs() < 1.0);
+
+        let (lat, lon) = from_local(&origin, east, north);
+        assert!((lat - 45.01).abs() < 1e-9 && (lon - 7.02).abs() < 1e-9);
+
+        let (east, _) = to_local(&GeoPoint::at(0.0, 179.9), &GeoPoint::at(0.0, -179.9));
+        assert!((east - 22_239.0).abs() < 1.0, "{east}");
+    }
+}
@@ -1,10 +1,50 @@
 //! Location storage service
+//!
+//! Keeps each member's latest position plus a bounded history of recent
+//! fixes. Batches recorded while a device was offline are merged into the
+//! history by their recording time.
+//!
+//! Fix times come from the device clock when the client supplies them. Fixes
+//! too far in the future are rejected, and a device whose clock runs ahead
+//! has its fix times shifted back by the observed offset.
+//!
+//! Every fix is checked for plausibility against the member's last trusted
+//! fix (see [`plausibility`]). Depending on the squad's settings a suspect
+//! fix is stored with a flag, or quarantined: kept aside for the leader to
+//! review and left out of the position and track. The leader either releases
+//! a quarantined fix into the track or discards it.
+//!
+//! Squads can also discard fixes below an accuracy threshold and have the
+//! remaining trusted fixes smoothed by a [`Kalman`] filter; the smoothed position
+//! is reported next to the raw one.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use chrono::{DateTime, Utc, Duration};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::models::{GeoPoint, MemberLocation};
+use crate::models::{GeoPoint, MemberLocation, SquadSettings, SuspectAction, SuspectReason};
+use crate::services::{geo, plausibility};
+use crate::services::smoothing::Kalman;
+
+/// Most quarantined fixes kept per member
+pub const MAX_QUARANTINED_POINTS: usize = 200;
+
+/// Number of recent uploads the clock offset estimate looks back over
+const CLOCK_SAMPLES: usize = 20;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LocationError {
+    #[error("Fix time is {ahead_secs}s ahead of the server clock (at most {max_skew_secs}s allowed)")]
+    FixInFuture {
+        /// Position of the offending fix in the upload
+        index: usize,
+        ahead_secs: i64,
+        max_skew_secs: i64,
+    },
+}
 
 /// Stores member locations with TTL
 pub struct LocationStore {
@@ -12,48 +52,424 @@ pub struct LocationStore {
     locations: HashMap<Uuid, HashMap<Uuid, StoredLocation>>,
     /// TTL for locations in seconds
     ttl_secs: i64,
+    /// How far ahead of the server clock a device fix time may be
+    max_clock_skew: Duration,
+    /// Most fixes kept in each member's history
+    max_history_points: usize,
+    /// Age after which history fixes are dropped
+    history_retention: Duration,
 }
 
 struct StoredLocation {
     member_id: Uuid,
     display_name: String,
     location: GeoPoint,
-    updated_at: DateTime<Utc>,
+    /// When the latest fix was taken, corrected for the device clock
+    fix_time: DateTime<Utc>,
+    /// When the latest fix was received
+    received_at: DateTime<Utc>,
+    /// Why the latest fix is suspect, if it is
+    suspect: Option<SuspectReason>,
+    /// Recent fixes ordered by `recorded_at`, the latest one included
+    history: VecDeque<TrackPoint>,
+    /// Suspect fixes held back from `history`, in arrival order
+    quarantine: VecDeque<TrackPoint>,
+    /// Smoothing state, while the squad has smoothing enabled
+    filter: Option<Kalman>,
+    /// `received_at - device fix time` of recent uploads, in milliseconds
+    clock_samples: VecDeque<i64>,
 }
 
-impl LocationStore {
-    pub fn new() -> Self {
+impl StoredLocation {
+    /// A member with no fix yet; the first one recorded always becomes latest
+    fn empty(member_id: Uuid, display_name: String, location: GeoPoint) -> Self {
         Self {
-            locations: HashMap::new(),
-            ttl_secs: 300, // 5 minutes default
+            member_id,
+            display_name,
+            location,
+            fix_time: DateTime::<Utc>::MIN_UTC,
+            received_at: DateTime::<Utc>::MIN_UTC,
+            suspect: None,
+            history: VecDeque::new(),
+            quarantine: VecDeque::new(),
+            filter: None,
+            clock_samples: VecDeque::new(),
+        }
+    }
+
+    /// Insert a fix in time order; `false` if the same device fix is already held
+    fn insert_history(&mut self, point: TrackPoint, max_points: usize) -> bool {
+        // Compare raw device times: corrected times shift with the offset estimate
+        if let Some(device_time) = point.location.fix_time {
+            if self.history.iter().any(|p| p.location.fix_time == Some(device_time)) {
+                return false;
+            }
+        }
+
+        let index = self.history.partition_point(|p| p.recorded_at <= point.recorded_at);
+        self.history.insert(index, point);
+        if self.history.len() > max_points {
+            self.history.pop_front();
+        }
+        true
+    }
+
+    /// The newest unflagged fix taken before `recorded_at`
+    ///
+    /// Flagged fixes are skipped so one spoofed jump doesn't also make the
+    /// return to the real position look like a jump.
+    fn trusted_before(&self, recorded_at: DateTime<Utc>) -> Option<&TrackPoint> {
+        self.history
+            .iter()
+            .rev()
+            .find(|p| p.recorded_at < recorded_at && p.suspect.is_none())
+    }
+
+    fn quarantine(&mut self, point: TrackPoint) {
+        self.quarantine.push_back(point);
+        if self.quarantine.len() > MAX_QUARANTINED_POINTS {
+            self.quarantine.pop_front();
         }
     }
 
+    fn observe_clock(&mut self, device_time: DateTime<Utc>, received_at: DateTime<Utc>) {
+        self.clock_samples
+            .push_back((received_at - device_time).num_milliseconds());
+        if self.clock_samples.len() > CLOCK_SAMPLES {
+            self.clock_samples.pop_front();
+        }
+    }
+
+    /// How far the device clock runs ahead of the server
+    ///
+    /// Delivery can only add latency, so a negative minimum apparent latency
+    /// means the device clock is ahead by at least that much. A clock running
+    /// behind is indistinguishable from a slow network and is left alone.
+    fn clock_ahead(&self) -> Duration {
+        let min_latency = self.clock_samples.iter().copied().min().unwrap_or(0);
+        Duration::milliseconds(-min_latency.min(0))
+    }
+}
+
+/// A fix in a member's history
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct TrackPoint {
+    /// The fix as sent, `fix_time` by the device clock
+    pub location: GeoPoint,
+    /// Fix time corrected for the device clock offset
+    pub recorded_at: DateTime<Utc>,
+    /// Why the fix failed the plausibility checks, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspect: Option<SuspectReason>,
+}
+
+/// What a leader decided about quarantined fixes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuarantineReview {
+    /// Add the fixes to the track as trusted
+    Release,
+    /// Drop the fixes
+    Discard,
+}
+
+/// What merging a batch into the history changed
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BatchOutcome {
+    pub accepted: usize,
+    pub duplicates: usize,
+    /// The member's new latest fix, if the batch advanced it
+    pub latest: Option<GeoPoint>,
+    /// Accepted fixes stored with a suspect flag
+    pub flagged: usize,
+    /// Suspect fixes quarantined instead of accepted
+    pub quarantined: usize,
+    /// Fixes discarded for poor accuracy
+    pub dropped: usize,
+}
+
+/// A stored location as written to snapshots
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LocationRecord {
+    pub squad_id: Uuid,
+    pub member_id: Uuid,
+    pub display_name: String,
+    pub location: GeoPoint,
+    /// Fix time of `location`
+    pub updated_at: DateTime<Utc>,
+    /// Snapshots from before receive times were kept have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub received_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspect: Option<SuspectReason>,
+    /// Snapshots from before history was kept have none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<TrackPoint>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quarantine: Vec<TrackPoint>,
+}
+
+impl LocationStore {
+    pub fn new() -> Self {
+        Self::with_ttl(300) // 5 minutes default
+    }
+
     pub fn with_ttl(ttl_secs: i64) -> Self {
         Self {
             locations: HashMap::new(),
             ttl_secs,
+            max_clock_skew: Duration::seconds(30),
+            max_history_points: 1000,
+            history_retention: Duration::days(1),
         }
     }
 
-    /// Update a member's location
+    /// Set how many fixes are kept in each member's history
+    pub fn with_max_history_points(mut self, max_history_points: usize) -> Self {
+        self.max_history_points = max_history_points;
+        self
+    }
+
+    /// Set how long history fixes are kept
+    pub fn with_history_retention(mut self, secs: i64) -> Self {
+        self.history_retention = Duration::seconds(secs);
+        self
+    }
+
+    /// Set how far ahead of the server clock a fix time may be
+    pub fn with_max_clock_skew(mut self, secs: i64) -> Self {
+        self.max_clock_skew = Duration::seconds(secs);
+        self
+    }
+
+    /// Update a member's location, checked and smoothed per the squad's settings
+    ///
+    /// A fix older than the member's latest one only goes into the history;
+    /// `latest` in the outcome says whether it moved.
     pub fn update_location(
         &mut self,
         squad_id: Uuid,
         member_id: Uuid,
         display_name: String,
         location: GeoPoint,
-    ) {
-        let squad_locations = self.locations.entry(squad_id).or_default();
-        squad_locations.insert(
-            member_id,
-            StoredLocation {
-                member_id,
-                display_name,
+        settings: &SquadSettings,
+    ) -> Result<BatchOutcome, LocationError> {
+        self.record(squad_id, member_id, display_name, vec![location], settings)
+    }
+
+    /// Merge fixes recorded while a member was offline into their history
+    ///
+    /// `fixes` must be ordered by `fix_time`. Fixes already in the history
+    /// are skipped, so a retried upload is harmless. The latest position only
+    /// moves if the newest fix is newer than the one already held, so a late
+    /// batch never overrides a live update.
+    pub fn merge_history(
+        &mut self,
+        squad_id: Uuid,
+        member_id: Uuid,
+        display_name: String,
+        fixes: Vec<GeoPoint>,
+        settings: &SquadSettings,
+    ) -> Result<BatchOutcome, LocationError> {
+        self.record(squad_id, member_id, display_name, fixes, settings)
+    }
+
+    fn record(
+        &mut self,
+        squad_id: Uuid,
+        member_id: Uuid,
+        display_name: String,
+        fixes: Vec<GeoPoint>,
+        settings: &SquadSettings,
+    ) -> Result<BatchOutcome, LocationError> {
+        let limits = &settings.plausibility;
+        let received_at = Utc::now();
+        let limit = received_at + self.max_clock_skew;
+        for (index, fix) in fixes.iter().enumerate() {
+            if let Some(fix_time) = fix.fix_time.filter(|t| *t > limit) {
+                return Err(LocationError::FixInFuture {
+                    index,
+                    ahead_secs: (fix_time - received_at).num_seconds(),
+                    max_skew_secs: self.max_clock_skew.num_seconds(),
+                });
+            }
+        }
+        let newest_device_time = fixes.last().and_then(|fix| fix.fix_time);
+
+        let mut outcome = BatchOutcome::default();
+        let received = fixes.len();
+        let fixes: Vec<GeoPoint> = match settings.smoothing.max_accuracy_m {
+            Some(max) => fixes
+                .into_iter()
+                .filter(|fix| fix.accuracy.is_none_or(|accuracy| accuracy <= max))
+                .collect(),
+            None => fixes,
+        };
+        outcome.dropped = received - fixes.len();
+        let Some(&first) = fixes.first() else {
+            return Ok(outcome);
+        };
+
+        let max_history_points = self.max_history_points;
+        let stored = self
+            .locations
+            .entry(squad_id)
+            .or_default()
+            .entry(member_id)
+            .or_insert_with(|| StoredLocation::empty(member_id, display_name.clone(), first));
+        stored.display_name = display_name;
+
+        // Only the newest fix of an upload says anything about the clock;
+        // older ones were buffered for an unknown time
+        if let Some(device_time) = newest_device_time {
+            stored.observe_clock(device_time, received_at);
+        }
+        let ahead = stored.clock_ahead();
+        // Clamped as the offset is only estimated to the millisecond
+        let corrected = |fix: &GeoPoint| {
+            fix.fix_time
+                .map_or(received_at, |t| (t - ahead).min(received_at))
+        };
+
+        if !settings.smoothing.enabled {
+            stored.filter = None;
+        }
+        let mut newest: Option<TrackPoint> = None;
+        for location in fixes {
+            let recorded_at = corrected(&location);
+            let suspect = stored
+                .trusted_before(recorded_at)
+                .and_then(|previous| plausibility::check(previous, &location, recorded_at, limits));
+            let point = TrackPoint {
                 location,
-                updated_at: Utc::now(),
-            },
-        );
+                recorded_at,
+                suspect,
+            };
+
+            if let Some(reason) = suspect {
+                warn!(%squad_id, %member_id, ?reason, "Suspect location fix");
+                if limits.on_suspect == SuspectAction::Quarantine {
+                    stored.quarantine(point);
+                    outcome.quarantined += 1;
+                    continue;
+                }
+            }
+            if !stored.insert_history(point, max_history_points) {
+                outcome.duplicates += 1;
+                continue;
+            }
+            outcome.accepted += 1;
+            if suspect.is_some() {
+                outcome.flagged += 1;
+            } else if settings.smoothing.enabled {
+                match &mut stored.filter {
+                    Some(filter) => filter.update(&location, recorded_at),
+                    None => stored.filter = Some(Kalman::new(&location, recorded_at)),
+                }
+            }
+            if newest.is_none_or(|n| recorded_at > n.recorded_at) {
+                newest = Some(point);
+            }
+        }
+
+        if let Some(newest) = newest.filter(|n| n.recorded_at > stored.fix_time) {
+            stored.location = newest.location;
+            stored.fix_time = newest.recorded_at;
+            stored.received_at = received_at;
+            stored.suspect = newest.suspect;
+            outcome.latest = Some(newest.location);
+        }
+        Ok(outcome)
+    }
+
+    /// A member's display name and history, optionally only fixes after `since`
+    pub fn member_history(
+        &self,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+        since: Option<DateTime<Utc>>,
+    ) -> Option<(String, Vec<TrackPoint>)> {
+        let stored = self.locations.get(squad_id)?.get(member_id)?;
+        let points = stored
+            .history
+            .iter()
+            .filter(|p| since.is_none_or(|since| p.recorded_at > since))
+            .copied()
+            .collect();
+        Some((stored.display_name.clone(), points))
+    }
+
+    /// When the member's current stop began
+    ///
+    /// The oldest fix of the unbroken run of trusted fixes, ending with the
+    /// latest, that all lie within `radius_m` of the latest. A device that
+    /// has gone quiet counts as still not moving.
+    pub fn stationary_since(
+        &self,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+        radius_m: f64,
+    ) -> Option<DateTime<Utc>> {
+        let stored = self.locations.get(squad_id)?.get(member_id)?;
+        let mut since = stored.fix_time;
+        for point in stored
+            .history
+            .iter()
+            .rev()
+            .filter(|p| p.suspect.is_none() && p.recorded_at <= stored.fix_time)
+        {
+            if geo::distance_m(&point.location, &stored.location) > radius_m {
+                break;
+            }
+            since = point.recorded_at;
+        }
+        Some(since)
+    }
+
+    /// Every quarantined fix of a squad with the member's ID and display name
+    pub fn squad_quarantine(&self, squad_id: &Uuid) -> Vec<(Uuid, String, TrackPoint)> {
+        self.locations
+            .get(squad_id)
+            .map(|squad_locs| {
+                squad_locs
+                    .values()
+                    .flat_map(|loc| {
+                        loc.quarantine
+                            .iter()
+                            .map(|p| (loc.member_id, loc.display_name.clone(), *p))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Release or discard a member's quarantined fixes
+    ///
+    /// Takes the fixes recorded at one of `recorded_at`, or all of them when
+    /// it is `None`. Released fixes join the history as trusted ones, so the
+    /// next fix is checked against them, but the latest position is left to
+    /// the member's next fix. Returns how many fixes were taken, or `None` if
+    /// the store holds nothing for the member.
+    pub fn review_quarantine(
+        &mut self,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+        recorded_at: Option<&[DateTime<Utc>]>,
+        review: QuarantineReview,
+    ) -> Option<usize> {
+        let max_history_points = self.max_history_points;
+        let stored = self.locations.get_mut(squad_id)?.get_mut(member_id)?;
+        let (taken, kept): (Vec<TrackPoint>, Vec<TrackPoint>) =
+            stored.quarantine.drain(..).partition(|p| {
+                recorded_at.is_none_or(|times| times.contains(&p.recorded_at))
+            });
+        stored.quarantine = kept.into();
+        if review == QuarantineReview::Release {
+            for point in &taken {
+                let point = TrackPoint { suspect: None, ..*point };
+                stored.insert_history(point, max_history_points);
+            }
+        }
+        Some(taken.len())
     }
 
     /// Get all locations for a squad
@@ -70,8 +486,14 @@ impl LocationStore {
                         member_id: loc.member_id,
                         display_name: loc.display_name.clone(),
                         location: loc.location,
-                        updated_at: loc.updated_at,
-                        is_stale: loc.updated_at < stale_threshold,
+                        updated_at: loc.fix_time,
+                        fix_time: loc.fix_time,
+                        received_at: loc.received_at,
+                        latency_ms: (loc.received_at - loc.fix_time).num_milliseconds(),
+                        clock_offset_ms: loc.clock_ahead().num_milliseconds(),
+                        is_stale: loc.fix_time < stale_threshold,
+                        suspect: loc.suspect,
+                        smoothed: loc.filter.as_ref().map(Kalman::estimate),
                     })
                     .collect()
             })
@@ -90,16 +512,368 @@ impl LocationStore {
         self.locations.remove(squad_id);
     }
 
-    /// Clean up stale locations (call periodically)
-    pub fn cleanup_stale(&mut self) {
-        let now = Utc::now();
-        let stale_threshold = now - Duration::seconds(self.ttl_secs * 2);
+    /// Drop every location that is already stale; returns how many were removed
+    pub fn expire_stale(&mut self) -> usize {
+        let stale_threshold = Utc::now() - Duration::seconds(self.ttl_secs);
+        let mut removed = 0;
 
         for squad_locs in self.locations.values_mut() {
-            squad_locs.retain(|_, loc| loc.updated_at > stale_threshold);
+            let before = squad_locs.len();
+            squad_locs.retain(|_, loc| loc.fix_time >= stale_threshold);
+            removed += before - squad_locs.len();
         }
 
-        // Remove empty squads
         self.locations.retain(|_, locs| !locs.is_empty());
+        removed
+    }
+
+    /// Every stored location, stale ones included
+    pub fn snapshot(&self) -> Vec<LocationRecord> {
+        self.locations
+            .iter()
+            .flat_map(|(squad_id, squad_locs)| {
+                squad_locs.values().map(|loc| LocationRecord {
+                    squad_id: *squad_id,
+                    member_id: loc.member_id,
+                    display_name: loc.display_name.clone(),
+                    location: loc.location,
+                    updated_at: loc.fix_time,
+                    received_at: Some(loc.received_at),
+                    suspect: loc.suspect,
+                    history: loc.history.iter().copied().collect(),
+                    quarantine: loc.quarantine.iter().copied().collect(),
+                })
+            })
+            .collect()
+    }
+
+    /// Replace every stored location, keeping the original timestamps
+    pub fn restore(&mut self, records: Vec<LocationRecord>) {
+        self.locations.clear();
+        for record in records {
+            let mut stored =
+                StoredLocation::empty(record.member_id, record.display_name, record.location);
+            stored.fix_time = record.updated_at;
+            stored.received_at = record.received_at.unwrap_or(record.updated_at);
+            stored.suspect = record.suspect;
+            stored.quarantine = record.quarantine.into();
+            if record.history.is_empty() {
+                stored.insert_history(
+                    TrackPoint {
+                        location: record.location,
+                        recorded_at: record.updated_at,
+                        suspect: record.suspect,
+                    },
+                    self.max_history_points,
+                );
+            }
+            for point in record.history {
+                stored.insert_history(point, self.max_history_points);
+            }
+            self.locations
+                .entry(record.squad_id)
+                .or_default()
+                .insert(record.member_id, stored);
+        }
+    }
+
+    /// Drop history and quarantined fixes older than the retention, and
+    /// members whose latest fix is; returns how many members were removed
+    pub fn expire_history(&mut self, now: DateTime<Utc>) -> usize {
+        let cutoff = now - self.history_retention;
+        let mut removed = 0;
+
+        for squad_locs in self.locations.values_mut() {
+            let before = squad_locs.len();
+            squad_locs.retain(|_, loc| loc.fix_time >= cutoff);
+            removed += before - squad_locs.len();
+            for loc in squad_locs.values_mut() {
+                loc.history.retain(|p| p.recorded_at >= cutoff);
+                loc.quarantine.retain(|p| p.recorded_at >= cutoff);
+            }
+        }
+
+        self.locations.retain(|_, locs| !locs.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PlausibilitySettings, SmoothingSettings};
+
+    /// A fix taken `secs` seconds from now by the device clock
+    fn fix(lat: f64, secs: i64) -> GeoPoint {
+        GeoPoint {
+            fix_time: Some(Utc::now() + Duration::seconds(secs)),
+            ..GeoPoint::at(lat, 0.0)
+        }
+    }
+
+    fn settings() -> SquadSettings {
+        SquadSettings::default()
+    }
+
+    #[test]
+    fn test_late_batch_fills_history_without_moving_latest() {
+        let mut store = LocationStore::new();
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+        store
+            .update_location(squad_id, member_id, "Scout".to_string(), fix(50.0, 0), &settings())
+            .unwrap();
+
+        // Recorded in the canyon, delivered after the live update above
+        let batch = vec![fix(49.997, -1800), fix(49.998, -1200), fix(49.999, -600)];
+        let outcome = store
+            .merge_history(squad_id, member_id, "Scout".to_string(), batch.clone(), &settings())
+            .unwrap();
+        assert_eq!((outcome.accepted, outcome.duplicates), (3, 0));
+        assert!(outcome.latest.is_none());
+        assert_eq!(store.get_squad_locations(&squad_id)[0].location.latitude, 50.0);
+
+        let (_, history) = store.member_history(&squad_id, &member_id, None).unwrap();
+        let lats: Vec<f64> = history.iter().map(|p| p.location.latitude).collect();
+        assert_eq!(lats, vec![49.997, 49.998, 49.999, 50.0]);
+
+        // Retrying the same upload is a no-op
+        let outcome = store
+            .merge_history(squad_id, member_id, "Scout".to_string(), batch, &settings())
+            .unwrap();
+        assert_eq!(outcome.duplicates, 3);
+
+        // So is a live update that arrives out of order
+        let late = fix(49.996, -60);
+        let outcome = store
+            .update_location(squad_id, member_id, "Scout".to_string(), late, &settings())
+            .unwrap();
+        assert!(outcome.latest.is_none());
+        assert_eq!(store.get_squad_locations(&squad_id)[0].location.latitude, 50.0);
+    }
+
+    #[test]
+    fn test_newer_batch_advances_latest() {
+        let mut store = LocationStore::new();
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let outcome = store
+            .merge_history(
+                squad_id,
+                member_id,
+                "Scout".to_string(),
+                vec![fix(1.0, -300), fix(1.001, -60)],
+                &settings(),
+            )
+            .unwrap();
+        assert_eq!((outcome.accepted, outcome.duplicates), (2, 0));
+        assert_eq!(outcome.latest.map(|fix| fix.latitude), Some(1.001));
+        let latest = &store.get_squad_locations(&squad_id)[0];
+        assert_eq!(latest.location.latitude, 1.001);
+        assert!(latest.latency_ms >= 60_000);
+    }
+
+    #[test]
+    fn test_old_history_expires() {
+        let mut store = LocationStore::new().with_history_retention(3600);
+        let squad_id = Uuid::new_v4();
+        let (gone, moving) = (Uuid::new_v4(), Uuid::new_v4());
+        store
+            .update_location(squad_id, gone, "Gone".to_string(), fix(1.0, -7200), &settings())
+            .unwrap();
+        let batch = vec![fix(2.0, -5400), fix(2.001, -10)];
+        store
+            .merge_history(squad_id, moving, "Moving".to_string(), batch, &settings())
+            .unwrap();
+
+        assert_eq!(store.expire_history(Utc::now()), 1);
+        let locations = store.get_squad_locations(&squad_id);
+        assert_eq!(locations.len(), 1);
+        let (_, history) = store.member_history(&squad_id, &moving, None).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].location.latitude, 2.001);
+    }
+
+    #[test]
+    fn test_fix_time_drives_staleness_and_latency() {
+        let mut store = LocationStore::with_ttl(60);
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // Taken two minutes ago, delivered just now over a slow link
+        store
+            .update_location(squad_id, member_id, "Scout".to_string(), fix(1.0, -120), &settings())
+            .unwrap();
+        let latest = &store.get_squad_locations(&squad_id)[0];
+        assert!(latest.is_stale);
+        assert!(latest.latency_ms >= 120_000);
+        assert_eq!(latest.clock_offset_ms, 0);
+    }
+
+    #[test]
+    fn test_clock_running_ahead_is_corrected_or_rejected() {
+        let mut store = LocationStore::new().with_max_clock_skew(30);
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        store
+            .update_location(squad_id, member_id, "Scout".to_string(), fix(1.0, 10), &settings())
+            .unwrap();
+        let latest = &store.get_squad_locations(&squad_id)[0];
+        assert!((9_000..=10_000).contains(&latest.clock_offset_ms));
+        assert!(latest.fix_time <= latest.received_at);
+        assert!(latest.latency_ms >= 0);
+
+        let err = store
+            .merge_history(
+                squad_id,
+                member_id,
+                "Scout".to_string(),
+                vec![fix(1.0, 0), fix(1.0, 120)],
+                &settings(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, LocationError::FixInFuture { index: 1, .. }));
+    }
+
+    #[test]
+    fn test_suspect_fixes_are_flagged_or_quarantined() {
+        let mut store = LocationStore::new();
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+        store
+            .update_location(squad_id, member_id, "Scout".to_string(), fix(45.0, -120), &settings())
+            .unwrap();
+
+        // Dragged ~55 km in a minute, then back where the member really is
+        store
+            .update_location(squad_id, member_id, "Scout".to_string(), fix(45.5, -60), &settings())
+            .unwrap();
+        assert_eq!(
+            store.get_squad_locations(&squad_id)[0].suspect,
+            Some(SuspectReason::Teleport)
+        );
+        store
+            .update_location(squad_id, member_id, "Scout".to_string(), fix(45.001, 0), &settings())
+            .unwrap();
+        assert_eq!(store.get_squad_locations(&squad_id)[0].suspect, None);
+
+        let quarantine = SquadSettings {
+            plausibility: PlausibilitySettings {
+                on_suspect: SuspectAction::Quarantine,
+                ..Default::default()
+            },
+            ..settings()
+        };
+        let outcome = store
+            .merge_history(
+                squad_id,
+                member_id,
+                "Scout".to_string(),
+                vec![fix(45.5, -30), fix(45.501, -20)],
+                &quarantine,
+            )
+            .unwrap();
+        assert_eq!((outcome.accepted, outcome.quarantined), (0, 2));
+        assert!(outcome.latest.is_none());
+        let latest = &store.get_squad_locations(&squad_id)[0];
+        assert_eq!(latest.location.latitude, 45.001);
+        assert_eq!(store.squad_quarantine(&squad_id).len(), 2);
+
+        let (_, history) = store.member_history(&squad_id, &member_id, None).unwrap();
+        let flagged: Vec<_> = history.iter().map(|p| p.suspect).collect();
+        assert_eq!(flagged, vec![None, Some(SuspectReason::Teleport), None]);
+    }
+
+    #[test]
+    fn test_quarantined_fixes_released_or_discarded() {
+        let mut store = LocationStore::new();
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let quarantine = SquadSettings {
+            plausibility: PlausibilitySettings {
+                on_suspect: SuspectAction::Quarantine,
+                ..Default::default()
+            },
+            ..settings()
+        };
+        store
+            .merge_history(
+                squad_id,
+                member_id,
+                "Scout".to_string(),
+                vec![fix(45.0, -90), fix(45.5, -60), fix(46.0, -30)],
+                &quarantine,
+            )
+            .unwrap();
+        let held: Vec<_> =
+            store.squad_quarantine(&squad_id).into_iter().map(|(_, _, p)| p).collect();
+        assert_eq!(held.len(), 2);
+
+        let release = [held[0].recorded_at];
+        let taken = store.review_quarantine(
+            &squad_id,
+            &member_id,
+            Some(&release),
+            QuarantineReview::Release,
+        );
+        assert_eq!(taken, Some(1));
+        let (_, history) = store.member_history(&squad_id, &member_id, None).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[1].location.latitude, history[1].suspect), (45.5, None));
+        // The live position stays put
+        assert_eq!(store.get_squad_locations(&squad_id)[0].location.latitude, 45.0);
+
+        let taken =
+            store.review_quarantine(&squad_id, &member_id, None, QuarantineReview::Discard);
+        assert_eq!(taken, Some(1));
+        assert!(store.squad_quarantine(&squad_id).is_empty());
+        assert_eq!(store.member_history(&squad_id, &member_id, None).unwrap().1.len(), 2);
+        assert_eq!(
+            store.review_quarantine(&squad_id, &Uuid::new_v4(), None, QuarantineReview::Discard),
+            None
+        );
+    }
+
+    #[test]
+    fn test_inaccurate_fixes_dropped_and_rest_smoothed() {
+        let mut store = LocationStore::new();
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let smoothing = SquadSettings {
+            smoothing: SmoothingSettings {
+                enabled: true,
+                max_accuracy_m: Some(50.0),
+            },
+            ..settings()
+        };
+        let with_accuracy = |lat, secs, accuracy| GeoPoint {
+            accuracy: Some(accuracy),
+            ..fix(lat, secs)
+        };
+
+        let outcome = store
+            .merge_history(
+                squad_id,
+                member_id,
+                "Scout".to_string(),
+                vec![
+                    with_accuracy(45.0, -30, 10.0),
+                    with_accuracy(45.01, -20, 800.0),
+                    with_accuracy(45.0002, -10, 10.0),
+                    with_accuracy(45.02, -5, 900.0),
+                ],
+                &smoothing,
+            )
+            .unwrap();
+        assert_eq!(outcome.accepted, 2);
+        assert_eq!(outcome.dropped, 2);
+        // The newest stored fix, not the dropped last one in the batch
+        assert_eq!(outcome.latest.map(|fix| fix.latitude), Some(45.0002));
+
+        let latest = &store.get_squad_locations(&squad_id)[0];
+        assert_eq!(latest.location.latitude, 45.0002);
+        let smoothed = latest.smoothed.unwrap();
+        assert!(smoothed.latitude > 45.0 && smoothed.latitude <= 45.0002);
+
+        // Turning smoothing off stops reporting it
+        store
+            .update_location(squad_id, member_id, "Scout".to_string(), fix(45.0003, 0), &settings())
+            .unwrap();
+        assert!(store.get_squad_locations(&squad_id)[0].smoothed.is_none());
     }
 }
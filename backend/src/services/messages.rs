@@ -0,0 +1,248 @@
+//! Squad messages
+//!
+//! Each squad has one bounded log of text messages, optionally pinned to a
+//! position. Members poll it with `since` or follow it on the squad stream.
+//! Deleted messages are removed outright; stream subscribers are told which
+//! one went.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::models::{GeoPoint, SquadMessage};
+use crate::services::audit::{AuditContext, AuditLog};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MessageError {
+    #[error("Message not found")]
+    NotFound,
+    #[error("Only the author or the squad leader can delete a message")]
+    NotAllowed,
+}
+
+/// Message logs of every squad (cheap to clone)
+#[derive(Clone)]
+pub struct MessageStore {
+    squads: Arc<Mutex<HashMap<Uuid, VecDeque<SquadMessage>>>>,
+    /// Messages kept per squad; the oldest are dropped first
+    max_messages: usize,
+    audit: AuditLog,
+}
+
+impl MessageStore {
+    pub fn new() -> Self {
+        Self::with_audit(AuditLog::new())
+    }
+
+    pub fn with_audit(audit: AuditLog) -> Self {
+        Self {
+            squads: Arc::new(Mutex::new(HashMap::new())),
+            max_messages: 500,
+            audit,
+        }
+    }
+
+    /// Set how many messages each squad keeps
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    /// Append a message to the squad's log
+    pub fn send(
+        &self,
+        squad_id: Uuid,
+        member_id: Uuid,
+        display_name: String,
+        text: String,
+        location: Option<GeoPoint>,
+    ) -> SquadMessage {
+        let mut squads = self.squads.lock().unwrap();
+        let log = squads.entry(squad_id).or_default();
+        // Strictly increasing, so paging with `since` never skips a message
+        // sent in the same instant (or after the clock stepped back)
+        let now = Utc::now();
+        let sent_at = log
+            .back()
+            .map_or(now, |last| now.max(last.sent_at + Duration::microseconds(1)));
+        let message = SquadMessage {
+            message_id: Uuid::new_v4(),
+            squad_id,
+            member_id,
+            display_name,
+            text,
+            location,
+            sent_at,
+        };
+        log.push_back(message.clone());
+        if log.len() > self.max_messages {
+            log.pop_front();
+        }
+        message
+    }
+
+    /// A page of messages, oldest first: the first `limit` sent after
+    /// `since`, or the newest `limit` without it. The flag is set when more
+    /// messages follow the page.
+    pub fn since(
+        &self,
+        squad_id: &Uuid,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> (Vec<SquadMessage>, bool) {
+        let squads = self.squads.lock().unwrap();
+        let Some(log) = squads.get(squad_id) else {
+            return (Vec::new(), false);
+        };
+        match since {
+            Some(since) => {
+                let mut newer = log.iter().filter(|message| message.sent_at > since);
+                let page: Vec<_> = newer.by_ref().take(limit).cloned().collect();
+                (page, newer.next().is_some())
+            }
+            None => (log.iter().skip(log.len().saturating_sub(limit)).cloned().collect(), false),
+        }
+    }
+
+    /// Delete a message; only its author or (with `is_leader`) the squad
+    /// leader may
+    pub fn delete(
+        &self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        message_id: &Uuid,
+        member_id: &Uuid,
+        is_leader: bool,
+    ) -> Result<SquadMessage, MessageError> {
+        let mut squads = self.squads.lock().unwrap();
+        let log = squads.get_mut(squad_id).ok_or(MessageError::NotFound)?;
+        let index = log
+            .iter()
+            .position(|message| message.message_id == *message_id)
+            .ok_or(MessageError::NotFound)?;
+        if log[index].member_id != *member_id && !is_leader {
+            return Err(MessageError::NotAllowed);
+        }
+        let message = log.remove(index).ok_or(MessageError::NotFound)?;
+        drop(squads);
+
+        self.audit.record(
+            ctx,
+            "message.delete",
+            Some(*squad_id),
+            Some(*member_id),
+            json!({ "message_id": message_id, "author_id": message.member_id }),
+        );
+        Ok(message)
+    }
+
+    /// Forget squads that no longer exist
+    pub fn retain_squads(&self, squad_ids: &HashSet<Uuid>) {
+        self.squads.lock().unwrap().retain(|id, _| squad_ids.contains(id));
+    }
+
+    /// Forget a deleted squad's messages
+    pub fn remove_squad(&self, squad_id: &Uuid) {
+        self.squads.lock().unwrap().remove(squad_id);
+    }
+
+    /// Every squad's messages, for snapshots
+    pub fn snapshot(&self) -> Vec<SquadMessage> {
+        self.squads.lock().unwrap().values().flatten().cloned().collect()
+    }
+
+    /// Replace every squad's messages, keeping the newest of each squad
+    pub fn restore(&self, mut messages: Vec<SquadMessage>) {
+        messages.sort_by_key(|message| message.sent_at);
+        let mut squads: HashMap<Uuid, VecDeque<SquadMessage>> = HashMap::new();
+        for message in messages {
+            let log = squads.entry(message.squad_id).or_default();
+            log.push_back(message);
+            if log.len() > self.max_messages {
+                log.pop_front();
+            }
+        }
+        *self.squads.lock().unwrap() = squads;
+    }
+}
+
+impl Default for MessageStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_is_bounded_and_paged() {
+        let store = MessageStore::new().with_max_messages(20);
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+        for i in 0..25 {
+            store.send(squad_id, member_id, "A".into(), format!("#{i}"), None);
+        }
+
+        let (all, has_more) = store.since(&squad_id, None, usize::MAX);
+        assert_eq!(all.len(), 20);
+        assert_eq!(all[0].text, "#5");
+        assert!(!has_more);
+
+        let (last, _) = store.since(&squad_id, None, 2);
+        assert_eq!(last.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), ["#23", "#24"]);
+
+        // Sent within the same instant, yet still paged one by one
+        let (after, has_more) = store.since(&squad_id, Some(all[all.len() - 2].sent_at), 10);
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].text, "#24");
+        assert!(!has_more);
+        assert!(store.since(&Uuid::new_v4(), None, 10).0.is_empty());
+    }
+
+    #[test]
+    fn test_catch_up_after_gap() {
+        let store = MessageStore::new();
+        let (squad_id, member_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let seen = store.send(squad_id, member_id, "A".into(), "seen".into(), None);
+        for i in 0..5 {
+            store.send(squad_id, member_id, "A".into(), format!("#{i}"), None);
+        }
+
+        // More arrived than fit in one page: the page continues right after
+        // the last message seen, and paging on from it loses nothing
+        let (page, has_more) = store.since(&squad_id, Some(seen.sent_at), 2);
+        assert_eq!(page.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), ["#0", "#1"]);
+        assert!(has_more);
+        let (page, has_more) = store.since(&squad_id, Some(page[1].sent_at), 2);
+        assert_eq!(page.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), ["#2", "#3"]);
+        assert!(has_more);
+        let (page, has_more) = store.since(&squad_id, Some(page[1].sent_at), 2);
+        assert_eq!(page.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), ["#4"]);
+        assert!(!has_more);
+    }
+
+    #[test]
+    fn test_delete_by_author_or_leader() {
+        let ctx = AuditContext::system();
+        let store = MessageStore::new();
+        let (squad_id, author, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let first = store.send(squad_id, author, "A".into(), "one".into(), None);
+        let second = store.send(squad_id, author, "A".into(), "two".into(), None);
+
+        assert!(matches!(
+            store.delete(&ctx, &squad_id, &first.message_id, &other, false),
+            Err(MessageError::NotAllowed)
+        ));
+        store.delete(&ctx, &squad_id, &first.message_id, &author, false).unwrap();
+        store.delete(&ctx, &squad_id, &second.message_id, &other, true).unwrap();
+        assert!(matches!(
+            store.delete(&ctx, &squad_id, &first.message_id, &author, false),
+            Err(MessageError::NotFound)
+        ));
+        assert!(store.since(&squad_id, None, 10).0.is_empty());
+    }
+}
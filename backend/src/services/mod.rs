@@ -1,6 +1,20 @@
 //! Services for Squadz
 
+pub mod admin_auth;
+pub mod alerts;
+pub mod audit;
 pub mod auth;
+pub mod feed;
+pub mod geo;
 pub mod location_store;
+pub mod messages;
+pub mod password;
+pub mod plausibility;
+pub mod proximity;
+pub mod routes;
 pub mod session;
+pub mod smoothing;
+pub mod snapshot;
+pub mod sos;
 pub mod squad_manager;
+pub mod webhooks;
@@ -0,0 +1,136 @@
+//! Spoof and jump detection
+//!
+//! Each new fix is compared with the member's last trusted fix before it.
+//! Fake-GPS apps give themselves away by moving the member faster than the
+//! squad allows, by jumping across the map, or by swinging the altitude. The
+//! horizontal accuracy of both fixes is subtracted from the distance first,
+//! so ordinary GPS wander is never mistaken for movement.
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{GeoPoint, PlausibilitySettings, SuspectReason};
+use crate::services::geo;
+use crate::services::location_store::TrackPoint;
+
+/// Faster than sound; no squad member moves like this
+pub const TELEPORT_SPEED_MPS: f64 = 343.0;
+
+/// Accuracy assumed for fixes that do not report one
+pub const DEFAULT_ACCURACY_M: f64 = 20.0;
+
+/// Most accuracy credited to one fix, so a spoofer cannot report a huge
+/// radius to hide a jump
+const MAX_ACCURACY_SLACK_M: f64 = 500.0;
+
+/// Vertical GPS error, which is typically worse than horizontal
+const ALTITUDE_NOISE_M: f64 = 50.0;
+
+/// Shortest interval used for speeds, so near-simultaneous fixes don't divide
+/// by zero
+const MIN_INTERVAL_SECS: f64 = 1.0;
+
+/// Why `location`, taken at `recorded_at`, cannot follow `previous`, if it can't
+pub fn check(
+    previous: &TrackPoint,
+    location: &GeoPoint,
+    recorded_at: DateTime<Utc>,
+    limits: &PlausibilitySettings,
+) -> Option<SuspectReason> {
+    let secs = ((recorded_at - previous.recorded_at).num_milliseconds() as f64 / 1000.0)
+        .abs()
+        .max(MIN_INTERVAL_SECS);
+
+    let slack = accuracy_slack(&previous.location) + accuracy_slack(location);
+    let moved = (geo::distance_m(&previous.location, location) - slack).max(0.0);
+    let speed = moved / secs;
+    if speed > TELEPORT_SPEED_MPS {
+        return Some(SuspectReason::Teleport);
+    }
+    if speed > limits.max_speed_mps {
+        return Some(SuspectReason::Speed);
+    }
+
+    if let (Some(from), Some(to)) = (previous.location.altitude, location.altitude) {
+        let climbed = ((to - from).abs() - ALTITUDE_NOISE_M).max(0.0);
+        if climbed / secs > limits.max_vertical_speed_mps {
+            return Some(SuspectReason::Altitude);
+        }
+    }
+    None
+}
+
+fn accuracy_slack(location: &GeoPoint) -> f64 {
+    location
+        .accuracy
+        .unwrap_or(DEFAULT_ACCURACY_M)
+        .min(MAX_ACCURACY_SLACK_M)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn point(latitude: f64, altitude: Option<f64>, accuracy: Option<f64>) -> GeoPoint {
+        GeoPoint {
+            altitude,
+            accuracy,
+            ..GeoPoint::at(latitude, 0.0)
+        }
+    }
+
+    /// `location` taken `secs` after `previous`, on foot (5 m/s limit)
+    fn check_after(previous: GeoPoint, location: GeoPoint, secs: i64) -> Option<SuspectReason> {
+        let start = Utc::now();
+        let limits = PlausibilitySettings {
+            max_speed_mps: 5.0,
+            max_vertical_speed_mps: 2.0,
+            ..Default::default()
+        };
+        let previous = TrackPoint {
+            location: previous,
+            recorded_at: start,
+            suspect: None,
+        };
+        check(&previous, &location, start + Duration::seconds(secs), &limits)
+    }
+
+    #[test]
+    fn test_walking_pace_passes() {
+        // ~111 m in 60 s
+        assert_eq!(check_after(point(45.0, None, None), point(45.001, None, None), 60), None);
+        // GPS wander between two fixes a second apart
+        assert_eq!(check_after(point(45.0, None, Some(30.0)), point(45.0004, None, Some(30.0)), 1), None);
+    }
+
+    #[test]
+    fn test_speed_and_teleport() {
+        // ~1.1 km in 60 s: a bike, not a runner
+        assert_eq!(
+            check_after(point(45.0, None, None), point(45.01, None, None), 60),
+            Some(SuspectReason::Speed)
+        );
+        // ~11 km in 10 s
+        assert_eq!(
+            check_after(point(45.0, None, None), point(45.1, None, None), 10),
+            Some(SuspectReason::Teleport)
+        );
+        // A huge reported accuracy does not hide the jump
+        assert_eq!(
+            check_after(point(45.0, None, Some(1e5)), point(45.1, None, Some(1e5)), 10),
+            Some(SuspectReason::Teleport)
+        );
+    }
+
+    #[test]
+    fn test_altitude_swing() {
+        assert_eq!(
+            check_after(point(45.0, Some(500.0), None), point(45.0, Some(530.0), None), 5),
+            None
+        );
+        assert_eq!(
+            check_after(point(45.0, Some(500.0), None), point(45.0, Some(900.0), None), 60),
+            Some(SuspectReason::Altitude)
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Distances between squad members
+//!
+//! Works on the latest [`MemberLocation`]s, using each member's smoothed
+//! position when the squad has smoothing enabled. Squads are small, so every
+//! pair is measured directly.
+
+use crate::models::{GeoPoint, MemberLocation, MemberPair, MemberProximity, Neighbour};
+use crate::services::geo;
+
+/// Pairwise distances, centroid and per-member neighbours of a squad
+#[derive(Debug)]
+pub struct Proximity {
+    pub centroid: Option<GeoPoint>,
+    /// Furthest from the centroid first
+    pub members: Vec<MemberProximity>,
+    pub pairs: Vec<MemberPair>,
+}
+
+/// The position proximity is measured from
+pub fn position(location: &MemberLocation) -> &GeoPoint {
+    location.smoothed.as_ref().unwrap_or(&location.location)
+}
+
+/// Measure every pair of `locations`; `within_m` also lists, for each member,
+/// the others within that distance
+pub fn measure(locations: &[MemberLocation], within_m: Option<f64>) -> Proximity {
+    let positions: Vec<&GeoPoint> = locations.iter().map(position).collect();
+    let centroid = geo::centroid(positions.iter().copied()).map(|(latitude, longitude)| GeoPoint {
+        latitude,
+        longitude,
+        altitude: None,
+        accuracy: None,
+        heading: None,
+        speed: None,
+        fix_time: None,
+    });
+
+    let mut neighbours: Vec<Vec<Neighbour>> = vec![Vec::new(); locations.len()];
+    let mut pairs = Vec::new();
+    for (i, a) in locations.iter().enumerate() {
+        for (j, b) in locations.iter().enumerate().skip(i + 1) {
+            let distance_m = geo::distance_m(positions[i], positions[j]);
+            let bearing_deg = geo::bearing_deg(positions[i], positions[j]);
+            pairs.push(MemberPair {
+                from_member_id: a.member_id,
+                to_member_id: b.member_id,
+                distance_m,
+                bearing_deg,
+            });
+            neighbours[i].push(Neighbour {
+                member_id: b.member_id,
+                display_name: b.display_name.clone(),
+                distance_m,
+                bearing_deg,
+            });
+            neighbours[j].push(Neighbour {
+                member_id: a.member_id,
+                display_name: a.display_name.clone(),
+                distance_m,
+                bearing_deg: geo::bearing_deg(positions[j], positions[i]),
+            });
+        }
+    }
+
+    let mut members: Vec<MemberProximity> = locations
+        .iter()
+        .zip(neighbours)
+        .zip(&positions)
+        .map(|((location, mut others), position)| {
+            others.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+            MemberProximity {
+                member_id: location.member_id,
+                display_name: location.display_name.clone(),
+                nearest: others.first().cloned(),
+                distance_from_centroid_m: centroid
+                    .as_ref()
+                    .map_or(0.0, |centroid| geo::distance_m(centroid, position)),
+                within: within_m.map(|max| {
+                    others.into_iter().filter(|n| n.distance_m <= max).collect()
+                }),
+            }
+        })
+        .collect();
+    members.sort_by(|a, b| b.distance_from_centroid_m.total_cmp(&a.distance_from_centroid_m));
+
+    Proximity {
+        centroid,
+        members,
+        pairs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn member(name: &str, latitude: f64, longitude: f64) -> MemberLocation {
+        let now = Utc::now();
+        MemberLocation {
+            member_id: Uuid::new_v4(),
+            display_name: name.to_string(),
+            location: GeoPoint::at(latitude, longitude),
+            updated_at: now,
+            fix_time: now,
+            received_at: now,
+            latency_ms: 0,
+            clock_offset_ms: 0,
+            is_stale: false,
+            suspect: None,
+            smoothed: None,
+        }
+    }
+
+    #[test]
+    fn test_stray_nearest_and_within() {
+        // Two members ~111 m apart, a third ~1.1 km east of them
+        let squad = vec![
+            member("Alpha", 45.0, 7.0),
+            member("Bravo", 45.001, 7.0),
+            member("Stray", 45.0005, 7.014),
+        ];
+        let proximity = measure(&squad, Some(200.0));
+        assert_eq!(proximity.pairs.len(), 3);
+        assert_eq!(proximity.members[0].display_name, "Stray");
+
+        let alpha = proximity.members.iter().find(|m| m.display_name == "Alpha").unwrap();
+        let nearest = alpha.nearest.as_ref().unwrap();
+        assert_eq!(nearest.display_name, "Bravo");
+        assert!((nearest.distance_m - 111.2).abs() < 0.5);
+        assert!(nearest.bearing_deg < 1e-6);
+        assert_eq!(alpha.within.as_ref().unwrap().len(), 1);
+
+        let stray = &proximity.members[0];
+        assert!(stray.within.as_ref().unwrap().is_empty());
+        let back = stray.nearest.as_ref().unwrap().bearing_deg;
+        assert!((back - 270.0).abs() < 5.0, "{back}");
+    }
+
+    #[test]
+    fn test_lone_member() {
+        let proximity = measure(&[member("Solo", 45.0, 7.0)], None);
+        assert!(proximity.pairs.is_empty());
+        assert!(proximity.members[0].nearest.is_none());
+        assert!(proximity.members[0].within.is_none());
+        assert!(proximity.members[0].distance_from_centroid_m < 1e-6);
+        assert!(measure(&[], None).centroid.is_none());
+    }
+}
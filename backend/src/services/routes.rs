@@ -0,0 +1,368 @@
+//! Planned routes
+//!
+//! Members are matched to the nearest point of the route, segment by segment,
+//! in the local projection of each segment's start. Route segments are short
+//! next to the kilometres that projection stays accurate over; a very long
+//! straight leg still matches, only less precisely.
+//!
+//! Where a route passes the same place twice (out and back, or a loop that
+//! crosses itself) the position alone is ambiguous, so each member's last
+//! progress is kept in a [`RouteTracker`] and the pass they are on is the one
+//! that carries on from it. Only new fixes move that progress; reading it
+//! changes nothing.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::models::{GeoPoint, MemberLocation, PlannedRoute, RouteProgress};
+use crate::services::{geo, proximity};
+
+/// Most vertices a route may have
+pub const MAX_ROUTE_POINTS: usize = 10_000;
+
+/// Below this speed a member counts as stopped and gets no ETA
+pub const MIN_MOVING_SPEED_MPS: f64 = 0.3;
+
+/// Legs passing within this much of the nearest one count as the same place,
+/// and a member may slip back this far on one without turning around
+const SAME_PLACE_M: f64 = 15.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouteError {
+    #[error("Invalid GPX: {0}")]
+    InvalidGpx(String),
+}
+
+/// Length of a polyline, in metres
+pub fn length_m(points: &[GeoPoint]) -> f64 {
+    points.windows(2).map(|leg| geo::distance_m(&leg[0], &leg[1])).sum()
+}
+
+/// Where `position` lies relative to a route of at least two points, as
+/// (cross-track distance, distance along the route) in metres
+///
+/// The nearest leg wins. Where several pass about as near, the one carrying
+/// on from `previous_m` (the member's last progress) is taken, or the
+/// earliest without it.
+pub fn locate(points: &[GeoPoint], position: &GeoPoint, previous_m: Option<f64>) -> (f64, f64) {
+    let mut matches = Vec::with_capacity(points.len().saturating_sub(1));
+    let mut start_m = 0.0;
+    for leg in points.windows(2) {
+        let (from, to) = (&leg[0], &leg[1]);
+        let leg_m = geo::distance_m(from, to);
+        let (ex, ny) = geo::to_local(from, to);
+        let (px, py) = geo::to_local(from, position);
+        let len2 = ex * ex + ny * ny;
+        let t = if len2 > 0.0 {
+            ((px * ex + py * ny) / len2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        matches.push(((px - t * ex).hypot(py - t * ny), start_m + t * leg_m));
+        start_m += leg_m;
+    }
+
+    let nearest_m = matches.iter().map(|(cross_m, _)| *cross_m).fold(f64::INFINITY, f64::min);
+    let mut candidates = matches
+        .into_iter()
+        .filter(|(cross_m, _)| *cross_m <= nearest_m + SAME_PLACE_M);
+    let Some(previous_m) = previous_m else {
+        return candidates.next().unwrap_or((f64::INFINITY, 0.0));
+    };
+    // Members mostly keep going: the nearest pass ahead, else the nearest
+    // one behind
+    candidates
+        .min_by(|a, b| {
+            let ahead = |along_m: f64| along_m >= previous_m - SAME_PLACE_M;
+            ahead(b.1)
+                .cmp(&ahead(a.1))
+                .then((a.1 - previous_m).abs().total_cmp(&(b.1 - previous_m).abs()))
+        })
+        .unwrap_or((f64::INFINITY, 0.0))
+}
+
+/// Each member's last progress along their squad's current route (cheap to
+/// clone)
+#[derive(Clone, Default)]
+pub struct RouteTracker {
+    /// Keyed by (route, member)
+    progress: Arc<Mutex<HashMap<(Uuid, Uuid), f64>>>,
+}
+
+impl RouteTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locate each member on the route from their last recorded progress,
+    /// without recording anything; returns (cross-track, along) per
+    /// location, in order
+    pub fn locate_members(
+        &self,
+        route: &PlannedRoute,
+        locations: &[MemberLocation],
+    ) -> Vec<(f64, f64)> {
+        let progress = self.progress.lock().unwrap();
+        locations
+            .iter()
+            .map(|location| {
+                let previous_m = progress.get(&(route.route_id, location.member_id)).copied();
+                locate(&route.points, proximity::position(location), previous_m)
+            })
+            .collect()
+    }
+
+    /// Locate each member's new position and record it as their progress
+    pub fn follow(&self, route: &PlannedRoute, locations: &[MemberLocation]) {
+        let located = self.locate_members(route, locations);
+        let mut progress = self.progress.lock().unwrap();
+        for (location, (_, along_m)) in locations.iter().zip(located) {
+            progress.insert((route.route_id, location.member_id), along_m);
+        }
+    }
+
+    /// Forget progress along routes that were replaced or removed
+    pub fn retain_routes(&self, route_ids: &HashSet<Uuid>) {
+        self.progress.lock().unwrap().retain(|(route_id, _), _| route_ids.contains(route_id));
+    }
+}
+
+/// Progress of each member along `route`, least progress first
+pub fn progress(
+    route: &PlannedRoute,
+    locations: &[MemberLocation],
+    tracker: &RouteTracker,
+    now: DateTime<Utc>,
+) -> Vec<RouteProgress> {
+    let located = tracker.locate_members(route, locations);
+    let mut members: Vec<RouteProgress> = locations
+        .iter()
+        .zip(located)
+        .map(|(location, (cross_track_m, progress_m))| {
+            let remaining_m = (route.length_m - progress_m).max(0.0);
+            let speed_mps = location.location.speed;
+            let eta = speed_mps
+                .filter(|speed| *speed >= MIN_MOVING_SPEED_MPS)
+                .map(|speed| now + Duration::milliseconds((remaining_m / speed * 1000.0) as i64));
+            let behind_schedule = route.finish_by.is_some_and(|finish_by| {
+                remaining_m > route.max_deviation_m
+                    && eta.map_or(now > finish_by, |eta| eta > finish_by)
+            });
+            RouteProgress {
+                member_id: location.member_id,
+                display_name: location.display_name.clone(),
+                cross_track_m,
+                progress_m,
+                progress_ratio: if route.length_m > 0.0 {
+                    progress_m / route.length_m
+                } else {
+                    1.0
+                },
+                remaining_m,
+                speed_mps,
+                eta,
+                off_route: cross_track_m > route.max_deviation_m,
+                behind_schedule,
+                is_stale: location.is_stale,
+                fix_time: location.fix_time,
+            }
+        })
+        .collect();
+    members.sort_by(|a, b| a.progress_m.total_cmp(&b.progress_m));
+    members
+}
+
+/// Track points of a GPX file, or its route points if it has no track, with
+/// the first track or route name found
+pub fn parse_gpx(xml: &str) -> Result<(Option<String>, Vec<GeoPoint>), RouteError> {
+    let doc = roxmltree::Document::parse(xml).map_err(|e| RouteError::InvalidGpx(e.to_string()))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "gpx" {
+        return Err(RouteError::InvalidGpx("root element is not <gpx>".into()));
+    }
+
+    let named = |tag: &'static str| root.descendants().filter(move |n| n.tag_name().name() == tag);
+    let mut points = Vec::new();
+    for tag in ["trkpt", "rtept"] {
+        for node in named(tag) {
+            let coordinate = |attribute: &str| {
+                node.attribute(attribute)
+                    .and_then(|value| value.trim().parse::<f64>().ok())
+                    .ok_or_else(|| {
+                        RouteError::InvalidGpx(format!("<{tag}> without a numeric {attribute}"))
+                    })
+            };
+            let altitude = node
+                .children()
+                .find(|child| child.tag_name().name() == "ele")
+                .and_then(|ele| ele.text())
+                .and_then(|text| text.trim().parse().ok());
+            points.push(GeoPoint {
+                latitude: coordinate("lat")?,
+                longitude: coordinate("lon")?,
+                altitude,
+                accuracy: None,
+                heading: None,
+                speed: None,
+                fix_time: None,
+            });
+        }
+        if !points.is_empty() {
+            break;
+        }
+    }
+
+    let name = named("trk")
+        .chain(named("rte"))
+        .chain(named("metadata"))
+        .find_map(|parent| {
+            parent
+                .children()
+                .find(|child| child.tag_name().name() == "name")
+                .and_then(|name| name.text())
+                .map(str::to_string)
+        });
+    Ok((name, points))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn located(latitude: f64, longitude: f64, speed: Option<f64>) -> MemberLocation {
+        let now = Utc::now();
+        MemberLocation {
+            member_id: Uuid::new_v4(),
+            display_name: "A".into(),
+            location: GeoPoint {
+                speed,
+                ..GeoPoint::at(latitude, longitude)
+            },
+            updated_at: now,
+            fix_time: now,
+            received_at: now,
+            latency_ms: 0,
+            clock_offset_ms: 0,
+            is_stale: false,
+            suspect: None,
+            smoothed: None,
+        }
+    }
+
+    #[test]
+    fn test_locate_and_progress() {
+        // An L: ~1112 m north, then ~786 m east
+        let points =
+            vec![GeoPoint::at(45.0, 7.0), GeoPoint::at(45.01, 7.0), GeoPoint::at(45.01, 7.01)];
+        let length = length_m(&points);
+        assert!((length - 1898.0).abs() < 2.0, "{length}");
+
+        // 100 m east of the first leg's midpoint
+        let east_lon = 7.0 + 100.0 / (111_195.0 * 45f64.to_radians().cos());
+        let (cross, along) = locate(&points, &GeoPoint::at(45.005, east_lon), None);
+        assert!((cross - 100.0).abs() < 0.5, "{cross}");
+        assert!((along - 556.0).abs() < 1.0, "{along}");
+
+        // Past the end sticks to the finish
+        let (_, along) = locate(&points, &GeoPoint::at(45.01, 7.02), None);
+        assert!((along - length).abs() < 1e-6);
+
+        let now = Utc::now();
+        let route = PlannedRoute {
+            route_id: Uuid::new_v4(),
+            squad_id: Uuid::new_v4(),
+            name: "Loop".into(),
+            length_m: length,
+            points,
+            max_deviation_m: 50.0,
+            finish_by: Some(now + Duration::minutes(10)),
+            uploaded_by: Uuid::new_v4(),
+            uploaded_at: now,
+        };
+        let members = progress(
+            &route,
+            &[located(45.0, 7.0, Some(1.0)), located(45.005, east_lon, Some(0.0))],
+            &RouteTracker::new(),
+            now,
+        );
+        // Least progress first; 1898 m at 1 m/s misses the 10 minute target
+        assert!(members[0].progress_m < 1.0 && !members[0].off_route);
+        assert!(members[0].behind_schedule);
+        assert!((members[0].eta.unwrap() - now - Duration::seconds(1898)).num_seconds().abs() <= 2);
+        assert!(members[1].off_route && members[1].eta.is_none() && !members[1].behind_schedule);
+    }
+
+    #[test]
+    fn test_out_and_back() {
+        // ~1112 m north and back the same way
+        let points =
+            vec![GeoPoint::at(45.0, 7.0), GeoPoint::at(45.01, 7.0), GeoPoint::at(45.0, 7.0)];
+        let now = Utc::now();
+        let route = PlannedRoute {
+            route_id: Uuid::new_v4(),
+            squad_id: Uuid::new_v4(),
+            name: "Out and back".into(),
+            length_m: length_m(&points),
+            points,
+            max_deviation_m: 50.0,
+            finish_by: None,
+            uploaded_by: Uuid::new_v4(),
+            uploaded_at: now,
+        };
+
+        // Without history the outbound pass is assumed
+        let (_, along) = locate(&route.points, &GeoPoint::at(45.002, 7.0), None);
+        assert!((along - 222.0).abs() < 1.0, "{along}");
+
+        // A member walking out, round the turn and back past the same spots
+        let tracker = RouteTracker::new();
+        let mut member = located(45.0, 7.0, None);
+        let mut track = Vec::new();
+        for latitude in [45.0, 45.002, 45.006, 45.0099, 45.01, 45.0099, 45.006, 45.002] {
+            member.location = GeoPoint::at(latitude, 7.0);
+            tracker.follow(&route, &[member.clone()]);
+            track.push(progress(&route, &[member.clone()], &tracker, now)[0].progress_m);
+        }
+        assert!(track.windows(2).all(|pair| pair[1] > pair[0]), "{track:?}");
+        assert!((track[7] - (route.length_m - 222.0)).abs() < 1.0, "{track:?}");
+
+        // Standing still keeps to the pass the member is on
+        tracker.follow(&route, &[member.clone()]);
+        let again = progress(&route, &[member.clone()], &tracker, now)[0].progress_m;
+        assert!((again - track[7]).abs() < 1.0, "{again}");
+
+        // Reading progress records nothing: a fresh tracker read twice at the
+        // return pass stays on the outbound one
+        let reader = RouteTracker::new();
+        for _ in 0..2 {
+            let read = progress(&route, &[member.clone()], &reader, now)[0].progress_m;
+            assert!((read - 222.0).abs() < 1.0, "{read}");
+        }
+    }
+
+    #[test]
+    fn test_parse_gpx() {
+        let gpx = r#"<?xml version="1.0"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+              <rte><name>Ignored</name><rtept lat="1" lon="1"/></rte>
+              <trk><name>Ridge</name>
+                <trkseg><trkpt lat="45.0" lon="7.0"><ele>1200.5</ele></trkpt></trkseg>
+                <trkseg><trkpt lat="45.01" lon="7.0"/></trkseg>
+              </trk>
+            </gpx>"#;
+        let (name, points) = parse_gpx(gpx).unwrap();
+        assert_eq!(name.as_deref(), Some("Ridge"));
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].altitude, Some(1200.5));
+
+        let (_, points) = parse_gpx(r#"<gpx><rte><rtept lat="1" lon="2"/></rte></gpx>"#).unwrap();
+        assert_eq!((points[0].latitude, points[0].longitude), (1.0, 2.0));
+        assert!(parse_gpx(r#"<gpx><trk><trkseg><trkpt lat="x" lon="2"/></trkseg></trk></gpx>"#).is_err());
+        assert!(parse_gpx("<kml/>").is_err());
+        assert!(parse_gpx("not xml").is_err());
+    }
+}
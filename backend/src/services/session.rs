@@ -5,10 +5,13 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+use super::audit::{AuditContext, AuditLog};
+
 /// A member session tied to a squad
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct MemberSession {
     pub session_id: Uuid,
     pub member_id: Uuid,
@@ -60,6 +63,7 @@ fn generate_api_key() -> String {
 pub struct SessionStore {
     /// Map from API key to session
     sessions: Arc<RwLock<HashMap<String, MemberSession>>>,
+    audit: AuditLog,
 }
 
 impl SessionStore {
@@ -67,11 +71,32 @@ impl SessionStore {
         Self::default()
     }
 
+    pub fn with_audit(audit: AuditLog) -> Self {
+        Self {
+            sessions: Arc::default(),
+            audit,
+        }
+    }
+
     /// Create a new session for a member
-    pub fn create(&self, member_id: Uuid, squad_id: Uuid, ttl_secs: u64) -> MemberSession {
+    pub fn create(
+        &self,
+        ctx: &AuditContext,
+        member_id: Uuid,
+        squad_id: Uuid,
+        ttl_secs: u64,
+    ) -> MemberSession {
         let session = MemberSession::new(member_id, squad_id, ttl_secs);
         let mut sessions = self.sessions.write().unwrap();
         sessions.insert(session.api_key.clone(), session.clone());
+
+        self.audit.record(
+            ctx,
+            "session.create",
+            Some(squad_id),
+            Some(member_id),
+            serde_json::json!({ "session_id": session.session_id, "expires_at": session.expires_at }),
+        );
         session
     }
 
@@ -89,18 +114,65 @@ impl SessionStore {
         None
     }
 
+    /// Whether an API key still names an unexpired session, without
+    /// counting as activity
+    pub fn is_active(&self, api_key: &str) -> bool {
+        self.sessions
+            .read()
+            .unwrap()
+            .get(api_key)
+            .is_some_and(|s| !s.is_expired())
+    }
+
     /// Revoke a session
-    pub fn revoke(&self, api_key: &str) -> bool {
+    pub fn revoke(&self, ctx: &AuditContext, api_key: &str) -> bool {
         let mut sessions = self.sessions.write().unwrap();
-        sessions.remove(api_key).is_some()
+        let Some(session) = sessions.remove(api_key) else {
+            return false;
+        };
+
+        self.audit.record(
+            ctx,
+            "session.revoke",
+            Some(session.squad_id),
+            Some(session.member_id),
+            serde_json::json!({ "session_id": session.session_id }),
+        );
+        true
     }
 
     /// Revoke all sessions for a member
-    pub fn revoke_member(&self, member_id: &Uuid) -> usize {
+    pub fn revoke_member(&self, ctx: &AuditContext, member_id: &Uuid) -> usize {
         let mut sessions = self.sessions.write().unwrap();
         let before = sessions.len();
         sessions.retain(|_, s| &s.member_id != member_id);
-        before - sessions.len()
+        let removed = before - sessions.len();
+
+        self.audit.record(
+            ctx,
+            "session.revoke_member",
+            None,
+            Some(*member_id),
+            serde_json::json!({ "revoked": removed }),
+        );
+        removed
+    }
+
+    /// Revoke all sessions for a squad
+    pub fn revoke_squad(&self, ctx: &AuditContext, squad_id: &Uuid) -> usize {
+        let mut sessions = self.sessions.write().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, s| &s.squad_id != squad_id);
+        let removed = before - sessions.len();
+
+        self.audit.record(
+            ctx,
+            "session.revoke_squad",
+            Some(*squad_id),
+            None,
+            serde_json::json!({ "revoked": removed }),
+        );
+        removed
     }
 
     /// Cleanup expired sessions
@@ -108,7 +180,38 @@ impl SessionStore {
         let mut sessions = self.sessions.write().unwrap();
         let before = sessions.len();
         sessions.retain(|_, s| !s.is_expired());
-        before - sessions.len()
+        let removed = before - sessions.len();
+
+        if removed > 0 {
+            self.audit.record(
+                &AuditContext::system(),
+                "session.expire",
+                None,
+                None,
+                serde_json::json!({ "expired": removed }),
+            );
+        }
+        removed
+    }
+
+    /// All unexpired sessions
+    pub fn snapshot(&self) -> Vec<MemberSession> {
+        self.sessions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|s| !s.is_expired())
+            .cloned()
+            .collect()
+    }
+
+    /// Replace every session, dropping any that expired in the meantime
+    pub fn restore(&self, sessions: Vec<MemberSession>) {
+        *self.sessions.write().unwrap() = sessions
+            .into_iter()
+            .filter(|s| !s.is_expired())
+            .map(|s| (s.api_key.clone(), s))
+            .collect();
     }
 
     /// Get session count (for metrics)
@@ -139,7 +242,7 @@ mod tests {
         let member_id = Uuid::new_v4();
         let squad_id = Uuid::new_v4();
 
-        let session = store.create(member_id, squad_id, 3600);
+        let session = store.create(&AuditContext::system(), member_id, squad_id, 3600);
         
         let validated = store.validate(&session.api_key);
         assert!(validated.is_some());
@@ -155,9 +258,9 @@ mod tests {
         let member_id = Uuid::new_v4();
         let squad_id = Uuid::new_v4();
 
-        let session = store.create(member_id, squad_id, 3600);
+        let session = store.create(&AuditContext::system(), member_id, squad_id, 3600);
         
-        assert!(store.revoke(&session.api_key));
+        assert!(store.revoke(&AuditContext::system(), &session.api_key));
         assert!(store.validate(&session.api_key).is_none());
     }
 }
@@ -0,0 +1,237 @@
+//! Track smoothing
+//!
+//! A constant-velocity Kalman filter over east/north metres around the
+//! member's first fix. Each fix's `accuracy` sets how far it is trusted, and
+//! `speed` with `heading` (when the device reports both) feed the velocity,
+//! so a stationary phone stops wandering while a moving one is not dragged
+//! behind. With isotropic noise the two axes are independent, so each is its
+//! own two-state filter.
+
+use chrono::{DateTime, Utc};
+
+use crate::models::GeoPoint;
+use crate::services::geo;
+use crate::services::plausibility::DEFAULT_ACCURACY_M;
+
+/// How sharply members are expected to speed up, turn or stop, in m/s²
+const ACCELERATION_NOISE_MPS2: f64 = 1.0;
+
+/// Error of device-reported speed, in m/s
+const SPEED_NOISE_MPS: f64 = 1.0;
+
+/// Velocity uncertainty of a fresh filter without a reported speed, in m/s
+const UNKNOWN_SPEED_MPS: f64 = 10.0;
+
+/// A gap after which the old estimate says nothing; the filter restarts
+const RESET_AFTER_SECS: f64 = 300.0;
+
+/// Position and velocity along one axis with their covariance
+#[derive(Debug, Clone, Copy)]
+struct Axis {
+    position: f64,
+    velocity: f64,
+    /// Covariance `[[pp, pv], [pv, vv]]`
+    pp: f64,
+    pv: f64,
+    vv: f64,
+}
+
+impl Axis {
+    fn new(position: f64, variance: f64, velocity: Option<f64>) -> Self {
+        Self {
+            position,
+            velocity: velocity.unwrap_or(0.0),
+            pp: variance,
+            pv: 0.0,
+            vv: if velocity.is_some() {
+                SPEED_NOISE_MPS.powi(2)
+            } else {
+                UNKNOWN_SPEED_MPS.powi(2)
+            },
+        }
+    }
+
+    fn predict(&mut self, dt: f64) {
+        let q = ACCELERATION_NOISE_MPS2.powi(2);
+        self.position += self.velocity * dt;
+        self.pp += 2.0 * dt * self.pv + dt * dt * self.vv + q * dt.powi(3) / 3.0;
+        self.pv += dt * self.vv + q * dt * dt / 2.0;
+        self.vv += q * dt;
+    }
+
+    fn observe_position(&mut self, z: f64, variance: f64) {
+        let s = self.pp + variance;
+        let (kp, kv) = (self.pp / s, self.pv / s);
+        let residual = z - self.position;
+        self.position += kp * residual;
+        self.velocity += kv * residual;
+        self.vv -= kv * self.pv;
+        self.pv *= 1.0 - kp;
+        self.pp *= 1.0 - kp;
+    }
+
+    fn observe_velocity(&mut self, z: f64, variance: f64) {
+        let s = self.vv + variance;
+        let (kp, kv) = (self.pv / s, self.vv / s);
+        let residual = z - self.velocity;
+        self.position += kp * residual;
+        self.velocity += kv * residual;
+        self.pp -= kp * self.pv;
+        self.pv *= 1.0 - kv;
+        self.vv *= 1.0 - kv;
+    }
+}
+
+/// Smoothing state of one member
+#[derive(Debug, Clone)]
+pub struct Kalman {
+    origin: GeoPoint,
+    time: DateTime<Utc>,
+    east: Axis,
+    north: Axis,
+    /// Last fix fed in, for the fields the filter does not estimate
+    last: GeoPoint,
+}
+
+impl Kalman {
+    pub fn new(fix: &GeoPoint, time: DateTime<Utc>) -> Self {
+        let variance = position_variance(fix);
+        let (east_velocity, north_velocity) = velocity(fix).unzip();
+        Self {
+            origin: *fix,
+            time,
+            east: Axis::new(0.0, variance, east_velocity),
+            north: Axis::new(0.0, variance, north_velocity),
+            last: *fix,
+        }
+    }
+
+    /// Fold in a fix taken at `time`
+    ///
+    /// Fixes older than the last one are ignored; a fix after a long gap
+    /// restarts the filter.
+    pub fn update(&mut self, fix: &GeoPoint, time: DateTime<Utc>) {
+        if time <= self.time {
+            return;
+        }
+        let dt = (time - self.time).num_milliseconds() as f64 / 1000.0;
+        if dt > RESET_AFTER_SECS {
+            *self = Self::new(fix, time);
+            return;
+        }
+
+        self.east.predict(dt);
+        self.north.predict(dt);
+
+        let (east, north) = geo::to_local(&self.origin, fix);
+        let variance = position_variance(fix);
+        self.east.observe_position(east, variance);
+        self.north.observe_position(north, variance);
+        if let Some((east_velocity, north_velocity)) = velocity(fix) {
+            let variance = SPEED_NOISE_MPS.powi(2);
+            self.east.observe_velocity(east_velocity, variance);
+            self.north.observe_velocity(north_velocity, variance);
+        }
+
+        self.time = time;
+        self.last = *fix;
+    }
+
+    /// The smoothed position, speed and heading
+    ///
+    /// `accuracy` is the filter's own one-sigma radius; altitude and
+    /// `fix_time` are taken from the last fix.
+    pub fn estimate(&self) -> GeoPoint {
+        let (latitude, longitude) =
+            geo::from_local(&self.origin, self.east.position, self.north.position);
+        let speed = self.east.velocity.hypot(self.north.velocity);
+        GeoPoint {
+            latitude,
+            longitude,
+            altitude: self.last.altitude,
+            accuracy: Some(self.east.pp.max(self.north.pp).sqrt()),
+            heading: Some(
+                self.east
+                    .velocity
+                    .atan2(self.north.velocity)
+                    .to_degrees()
+                    .rem_euclid(360.0),
+            ),
+            speed: Some(speed),
+            fix_time: self.last.fix_time,
+        }
+    }
+}
+
+fn position_variance(fix: &GeoPoint) -> f64 {
+    fix.accuracy.unwrap_or(DEFAULT_ACCURACY_M).max(1.0).powi(2)
+}
+
+/// Reported (east, north) velocity, if the fix has both speed and heading
+fn velocity(fix: &GeoPoint) -> Option<(f64, f64)> {
+    let (speed, heading) = (fix.speed?, fix.heading?.to_radians());
+    Some((speed * heading.sin(), speed * heading.cos()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn fix(east: f64, north: f64, speed: Option<f64>, heading: Option<f64>) -> GeoPoint {
+        let origin = GeoPoint {
+            accuracy: Some(20.0),
+            heading,
+            speed,
+            ..GeoPoint::at(45.0, 7.0)
+        };
+        let (latitude, longitude) = geo::from_local(&origin, east, north);
+        GeoPoint {
+            latitude,
+            longitude,
+            ..origin
+        }
+    }
+
+    fn offset(point: &GeoPoint) -> (f64, f64) {
+        geo::to_local(&fix(0.0, 0.0, None, None), point)
+    }
+
+    #[test]
+    fn test_stationary_jitter_is_damped() {
+        let start = Utc::now();
+        let mut filter = Kalman::new(&fix(0.0, 0.0, None, None), start);
+        // Indoors: alternating 40 m either side of the true position
+        for i in 1..=30 {
+            let east = if i % 2 == 0 { 40.0 } else { -40.0 };
+            filter.update(&fix(east, 0.0, None, None), start + Duration::seconds(i));
+        }
+        let (east, north) = offset(&filter.estimate());
+        assert!(east.abs() < 10.0 && north.abs() < 1.0, "{east} {north}");
+    }
+
+    #[test]
+    fn test_moving_member_is_followed() {
+        let start = Utc::now();
+        let mut filter = Kalman::new(&fix(0.0, 0.0, Some(5.0), Some(0.0)), start);
+        // Jogging north at 5 m/s with ±15 m east-west noise
+        for i in 1..=60 {
+            let noise = if i % 2 == 0 { 15.0 } else { -15.0 };
+            let north = 5.0 * i as f64;
+            filter.update(&fix(noise, north, Some(5.0), Some(0.0)), start + Duration::seconds(i));
+        }
+        let estimate = filter.estimate();
+        let (east, north) = offset(&estimate);
+        assert!(east.abs() < 8.0, "{east}");
+        assert!((north - 300.0).abs() < 5.0, "{north}");
+        assert!((estimate.speed.unwrap() - 5.0).abs() < 0.5);
+        let heading = estimate.heading.unwrap();
+        assert!(!(5.0..=355.0).contains(&heading), "{heading}");
+
+        // Out-of-order fixes are ignored; a long gap restarts at the new fix
+        filter.update(&fix(500.0, 0.0, None, None), start);
+        assert!((offset(&filter.estimate()).1 - north).abs() < 1e-6);
+        filter.update(&fix(500.0, 0.0, None, None), start + Duration::hours(1));
+        assert!((offset(&filter.estimate()).0 - 500.0).abs() < 1e-6);
+    }
+}
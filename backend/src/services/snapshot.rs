@@ -0,0 +1,404 @@
+//! State snapshots for Squadz
+//!
+//! Squads, locations, member sessions, webhooks, open distress calls and
+//! messages live in memory. On shutdown they are written to a JSON snapshot
+//! file and reloaded on the next start, so a rolling restart keeps every
+//! squad, join code and API key.
+//!
+//! The same snapshot can be exported and imported (admin API or CLI) as JSON
+//! or as compact MessagePack to move state between hosts.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::models::{DistressCall, PlannedRoute, Squad, SquadMessage, Waypoint};
+use crate::services::location_store::{LocationRecord, LocationStore};
+use crate::services::messages::MessageStore;
+use crate::services::session::{MemberSession, SessionStore};
+use crate::services::sos::SosStore;
+use crate::services::squad_manager::SquadManager;
+use crate::services::webhooks::{WebhookRecord, WebhookStore};
+
+/// Snapshot format version; bump on incompatible changes
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A squad as written to snapshots, including its passphrase hash, route and
+/// waypoints
+///
+/// `Squad` never serializes the hash so it cannot leak through the API.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SquadRecord {
+    #[serde(flatten)]
+    pub squad: Squad,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passphrase_hash: Option<String>,
+    /// `Squad` leaves its route to the route endpoints as well
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<PlannedRoute>,
+    /// Likewise only served to members by the waypoint endpoints
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub waypoints: Vec<Waypoint>,
+}
+
+/// Point-in-time copy of all in-memory state
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Snapshot {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub squads: Vec<SquadRecord>,
+    pub locations: Vec<LocationRecord>,
+    pub sessions: Vec<MemberSession>,
+    /// Registered webhooks with their secrets (missing in older snapshots)
+    #[serde(default)]
+    pub webhooks: Vec<WebhookRecord>,
+    /// Open distress calls (missing in older snapshots)
+    #[serde(default)]
+    pub sos: Vec<DistressCall>,
+    /// Squad messages (missing in older snapshots)
+    #[serde(default)]
+    pub messages: Vec<SquadMessage>,
+}
+
+/// Encoding of an exported snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    #[default]
+    Json,
+    /// MessagePack
+    Binary,
+}
+
+impl SnapshotFormat {
+    /// Guess the encoding of raw snapshot bytes; JSON always starts with `{`
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => Self::Json,
+            _ => Self::Binary,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Binary => "application/msgpack",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Binary => "msgpack",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("Snapshot I/O failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid snapshot: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to encode binary snapshot: {0}")]
+    BinaryEncode(#[from] rmp_serde::encode::Error),
+    #[error("Invalid binary snapshot: {0}")]
+    BinaryDecode(#[from] rmp_serde::decode::Error),
+    #[error("Unsupported snapshot version {0} (expected {SNAPSHOT_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+impl Snapshot {
+    /// Copy the current state of every store
+    pub fn capture(
+        squads: &SquadManager,
+        locations: &LocationStore,
+        sessions: &SessionStore,
+        webhooks: &WebhookStore,
+        sos: &SosStore,
+        messages: &MessageStore,
+    ) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            created_at: Utc::now(),
+            squads: squads
+                .list_squads()
+                .into_iter()
+                .map(|squad| SquadRecord {
+                    passphrase_hash: squad.passphrase_hash.clone(),
+                    route: squad.route.clone(),
+                    waypoints: squad.waypoints.clone(),
+                    squad: squad.clone(),
+                })
+                .collect(),
+            locations: locations.snapshot(),
+            sessions: sessions.snapshot(),
+            webhooks: webhooks.snapshot(),
+            sos: sos.snapshot(),
+            messages: messages.snapshot(),
+        }
+    }
+
+    /// Replace the contents of every store with this snapshot
+    pub fn restore(
+        self,
+        squads: &mut SquadManager,
+        locations: &mut LocationStore,
+        sessions: &SessionStore,
+        webhooks: &WebhookStore,
+        sos: &SosStore,
+        messages: &MessageStore,
+    ) {
+        squads.restore(
+            self.squads
+                .into_iter()
+                .map(|record| Squad {
+                    passphrase_hash: record.passphrase_hash,
+                    route: record.route,
+                    waypoints: record.waypoints,
+                    ..record.squad
+                })
+                .collect(),
+        );
+        locations.restore(self.locations);
+        sessions.restore(self.sessions);
+        webhooks.restore(self.webhooks);
+        sos.restore(self.sos);
+        messages.restore(self.messages);
+    }
+
+    /// Serialize in the given format
+    pub fn encode(&self, format: SnapshotFormat) -> Result<Vec<u8>, SnapshotError> {
+        Ok(match format {
+            SnapshotFormat::Json => serde_json::to_vec_pretty(self)?,
+            SnapshotFormat::Binary => rmp_serde::to_vec_named(self)?,
+        })
+    }
+
+    /// Parse a JSON or binary snapshot, rejecting versions this build does not
+    /// understand
+    pub fn decode(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let snapshot: Self = match SnapshotFormat::detect(bytes) {
+            SnapshotFormat::Json => serde_json::from_slice(bytes)?,
+            SnapshotFormat::Binary => rmp_serde::from_slice(bytes)?,
+        };
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+
+    /// Read a snapshot file in either format; `Ok(None)` if it does not exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>, SnapshotError> {
+        match fs::read(path) {
+            Ok(bytes) => Self::decode(&bytes).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the snapshot as JSON, atomically (temp file, then rename)
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        self.save_as(path, SnapshotFormat::Json)
+    }
+
+    /// Write the snapshot in the given format, atomically
+    ///
+    /// The file holds API keys and webhook secrets, so it is readable by the
+    /// owner only, and it is on disk before it replaces the previous one.
+    pub fn save_as(
+        &self,
+        path: impl AsRef<Path>,
+        format: SnapshotFormat,
+    ) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        // A temp file left by a crash may have other permissions
+        match fs::remove_file(&tmp) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(&self.encode(format)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GeoPoint, SosRequest, WaypointIcon, WaypointRequest};
+    use crate::services::audit::AuditContext;
+
+    #[test]
+    fn test_round_trip_preserves_state() {
+        let ctx = AuditContext::system();
+        let mut squads = SquadManager::new();
+        let mut locations = LocationStore::new();
+        let sessions = SessionStore::new();
+
+        let (squad, leader_id) = squads.create_squad(
+            &ctx,
+            "Alpha".to_string(),
+            "Lead".to_string(),
+            None,
+            Some("$argon2id$fake".to_string()),
+        );
+        locations
+            .update_location(
+                squad.squad_id,
+                leader_id,
+                "Lead".to_string(),
+                GeoPoint::at(40.0, -105.0),
+                &squad.settings,
+            )
+            .unwrap();
+        let session = sessions.create(&ctx, leader_id, squad.squad_id, 3600);
+        let webhooks = WebhookStore::new();
+        let (hook, secret) = webhooks
+            .create(&ctx, squad.squad_id, "https://example.com/hook".to_string(), vec![])
+            .unwrap();
+        let sos = SosStore::new();
+        let call = sos.raise(
+            &ctx,
+            squad.squad_id,
+            leader_id,
+            "Lead".to_string(),
+            SosRequest {
+                location: locations.get_squad_locations(&squad.squad_id)[0].location,
+                message: Some("Twisted ankle".to_string()),
+            },
+            Utc::now(),
+        );
+
+        let leader = squads.get_squad(&squad.squad_id).unwrap().members[0].clone();
+        let (waypoint, _) = squads
+            .add_waypoint(
+                &ctx,
+                &squad.squad_id,
+                &leader,
+                WaypointRequest {
+                    name: "Camp".to_string(),
+                    icon: WaypointIcon::RallyPoint,
+                    location: locations.get_squad_locations(&squad.squad_id)[0].location,
+                    description: None,
+                    expires_at: None,
+                },
+            )
+            .unwrap();
+        let messages = MessageStore::new();
+        let message =
+            messages.send(squad.squad_id, leader_id, "Lead".into(), "Regroup".into(), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        Snapshot::capture(&squads, &locations, &sessions, &webhooks, &sos, &messages)
+            .save(&path)
+            .unwrap();
+
+        let mut squads = SquadManager::new();
+        let mut locations = LocationStore::new();
+        let sessions = SessionStore::new();
+        let webhooks = WebhookStore::new();
+        let sos = SosStore::new();
+        let messages = MessageStore::new();
+        Snapshot::load(&path)
+            .unwrap()
+            .unwrap()
+            .restore(&mut squads, &mut locations, &sessions, &webhooks, &sos, &messages);
+
+        let restored = squads.get_squad_by_code(&squad.join_code).unwrap();
+        assert_eq!(restored.squad_id, squad.squad_id);
+        assert_eq!(restored.passphrase_hash.as_deref(), Some("$argon2id$fake"));
+        assert_eq!(restored.waypoints[0].waypoint_id, waypoint.waypoint_id);
+        assert_eq!(locations.get_squad_locations(&squad.squad_id).len(), 1);
+        assert!(sessions.validate(&session.api_key).is_some());
+        let restored = webhooks.snapshot();
+        assert_eq!(restored[0].webhook.webhook_id, hook.webhook_id);
+        assert_eq!(restored[0].secret, secret);
+        let restored = sos.squad_calls(&squad.squad_id);
+        assert_eq!(restored[0].sos_id, call.sos_id);
+        assert_eq!(restored[0].message.as_deref(), Some("Twisted ankle"));
+        assert!(sos.is_in_distress(&squad.squad_id, &leader_id));
+        let (restored, _) = messages.since(&squad.squad_id, None, 10);
+        assert_eq!(restored[0].message_id, message.message_id);
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let ctx = AuditContext::system();
+        let mut squads = SquadManager::new();
+        let (squad, _) = squads.create_squad(
+            &ctx,
+            "Bravo".to_string(),
+            "Lead".to_string(),
+            None,
+            Some("$argon2id$fake".to_string()),
+        );
+        let snapshot = Snapshot::capture(
+            &squads,
+            &LocationStore::new(),
+            &SessionStore::new(),
+            &WebhookStore::new(),
+            &SosStore::new(),
+            &MessageStore::new(),
+        );
+
+        let bytes = snapshot.encode(SnapshotFormat::Binary).unwrap();
+        assert_eq!(SnapshotFormat::detect(&bytes), SnapshotFormat::Binary);
+
+        let decoded = Snapshot::decode(&bytes).unwrap();
+        assert_eq!(decoded.squads[0].squad.join_code, squad.join_code);
+        assert_eq!(decoded.squads[0].passphrase_hash.as_deref(), Some("$argon2id$fake"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        // Left over from an interrupted save
+        fs::write(path.with_extension("tmp"), "partial").unwrap();
+        let sessions = SessionStore::new();
+        let snapshot = Snapshot::capture(
+            &SquadManager::new(),
+            &LocationStore::new(),
+            &sessions,
+            &WebhookStore::new(),
+            &SosStore::new(),
+            &MessageStore::new(),
+        );
+
+        snapshot.save(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!path.with_extension("tmp").exists());
+        assert!(Snapshot::load(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_missing_file_and_bad_version() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Snapshot::load(dir.path().join("none.json")).unwrap().is_none());
+
+        let json = r#"{"version":99,"created_at":"2024-01-01T00:00:00Z","squads":[],"locations":[],"sessions":[]}"#;
+        assert!(matches!(
+            Snapshot::decode(json.as_bytes()),
+            Err(SnapshotError::UnsupportedVersion(99))
+        ));
+    }
+}
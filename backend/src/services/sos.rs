@@ -0,0 +1,387 @@
+//! Distress calls
+//!
+//! A member raises an SOS with their exact position. The call is published
+//! on the squad feed when it is raised, whenever the caller's position
+//! changes, and again every [`REBROADCAST_INTERVAL`] until another member
+//! acknowledges it. It stays listed until the caller or the squad leader
+//! resolves it; resolved calls are only kept in the audit log.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::models::{DistressCall, DistressState, GeoPoint, SosRequest, SquadEvent};
+use crate::services::audit::{AuditContext, AuditLog};
+use crate::AppState;
+
+/// How often unacknowledged calls are published again
+pub const REBROADCAST_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Location update interval asked of a member while their call is open
+pub const SOS_UPDATE_INTERVAL_SECS: u32 = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SosError {
+    #[error("Distress call not found")]
+    NotFound,
+    #[error("A member cannot acknowledge their own distress call")]
+    OwnCall,
+    #[error("Only the caller or the squad leader can resolve a distress call")]
+    NotAllowed,
+}
+
+/// Open distress calls of every squad (cheap to clone)
+#[derive(Clone)]
+pub struct SosStore {
+    calls: Arc<Mutex<HashMap<Uuid, DistressCall>>>,
+    audit: AuditLog,
+}
+
+impl SosStore {
+    pub fn new() -> Self {
+        Self::with_audit(AuditLog::new())
+    }
+
+    pub fn with_audit(audit: AuditLog) -> Self {
+        Self {
+            calls: Arc::new(Mutex::new(HashMap::new())),
+            audit,
+        }
+    }
+
+    /// Raise a distress call, or renew the member's open one
+    ///
+    /// Renewing moves the call to the new location, replaces the message if
+    /// one is given and makes an acknowledged call active again.
+    pub fn raise(
+        &self,
+        ctx: &AuditContext,
+        squad_id: Uuid,
+        member_id: Uuid,
+        display_name: String,
+        request: SosRequest,
+        now: DateTime<Utc>,
+    ) -> DistressCall {
+        let SosRequest { location, message } = request;
+        let mut calls = self.calls.lock().unwrap();
+        let existing = calls
+            .values_mut()
+            .find(|call| call.squad_id == squad_id && call.member_id == member_id);
+        let (call, renewed) = match existing {
+            Some(call) => {
+                call.display_name = display_name;
+                call.location = location;
+                if message.is_some() {
+                    call.message = message;
+                }
+                call.state = DistressState::Active;
+                call.acknowledged_by = None;
+                call.acknowledged_at = None;
+                call.updated_at = now;
+                (call.clone(), true)
+            }
+            None => {
+                let call = DistressCall {
+                    sos_id: Uuid::new_v4(),
+                    squad_id,
+                    member_id,
+                    display_name,
+                    location,
+                    message,
+                    state: DistressState::Active,
+                    raised_at: now,
+                    updated_at: now,
+                    acknowledged_by: None,
+                    acknowledged_at: None,
+                    resolved_by: None,
+                    resolved_at: None,
+                };
+                calls.insert(call.sos_id, call.clone());
+                (call, false)
+            }
+        };
+        drop(calls);
+
+        warn!(
+            squad_id = %squad_id,
+            member_id = %member_id,
+            sos_id = %call.sos_id,
+            "Distress call raised"
+        );
+        self.audit.record(
+            ctx,
+            "sos.raise",
+            Some(squad_id),
+            Some(member_id),
+            json!({
+                "sos_id": call.sos_id,
+                "renewed": renewed,
+                "latitude": call.location.latitude,
+                "longitude": call.location.longitude,
+            }),
+        );
+        call
+    }
+
+    /// Move the member's open call, if they have one, to a new position
+    pub fn track(
+        &self,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+        location: GeoPoint,
+        now: DateTime<Utc>,
+    ) -> Option<DistressCall> {
+        let mut calls = self.calls.lock().unwrap();
+        let call = calls
+            .values_mut()
+            .find(|call| call.squad_id == *squad_id && call.member_id == *member_id)?;
+        call.location = location;
+        call.updated_at = now;
+        Some(call.clone())
+    }
+
+    /// Mark a call as being responded to by `member_id`, who must be another
+    /// member of the caller's squad
+    pub fn acknowledge(
+        &self,
+        ctx: &AuditContext,
+        sos_id: &Uuid,
+        squad_id: &Uuid,
+        member_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<DistressCall, SosError> {
+        let mut calls = self.calls.lock().unwrap();
+        let call = calls
+            .get_mut(sos_id)
+            .filter(|call| call.squad_id == *squad_id)
+            .ok_or(SosError::NotFound)?;
+        if call.member_id == member_id {
+            return Err(SosError::OwnCall);
+        }
+        call.state = DistressState::Acknowledged;
+        call.acknowledged_by = Some(member_id);
+        call.acknowledged_at = Some(now);
+        call.updated_at = now;
+        let call = call.clone();
+        drop(calls);
+
+        info!(squad_id = %squad_id, sos_id = %sos_id, "Distress call acknowledged");
+        self.audit.record(
+            ctx,
+            "sos.acknowledge",
+            Some(*squad_id),
+            Some(member_id),
+            json!({ "sos_id": sos_id, "caller_id": call.member_id }),
+        );
+        Ok(call)
+    }
+
+    /// Close a call; only the caller or (with `is_leader`) the squad leader may
+    pub fn resolve(
+        &self,
+        ctx: &AuditContext,
+        sos_id: &Uuid,
+        squad_id: &Uuid,
+        member_id: Uuid,
+        is_leader: bool,
+        now: DateTime<Utc>,
+    ) -> Result<DistressCall, SosError> {
+        let mut calls = self.calls.lock().unwrap();
+        let call = calls
+            .get(sos_id)
+            .filter(|call| call.squad_id == *squad_id)
+            .ok_or(SosError::NotFound)?;
+        if call.member_id != member_id && !is_leader {
+            return Err(SosError::NotAllowed);
+        }
+        let mut call = calls.remove(sos_id).ok_or(SosError::NotFound)?;
+        drop(calls);
+        call.state = DistressState::Resolved;
+        call.resolved_by = Some(member_id);
+        call.resolved_at = Some(now);
+        call.updated_at = now;
+
+        info!(squad_id = %squad_id, sos_id = %sos_id, "Distress call resolved");
+        self.audit.record(
+            ctx,
+            "sos.resolve",
+            Some(*squad_id),
+            Some(member_id),
+            json!({ "sos_id": sos_id, "caller_id": call.member_id }),
+        );
+        Ok(call)
+    }
+
+    /// Open calls of a squad, oldest first
+    pub fn squad_calls(&self, squad_id: &Uuid) -> Vec<DistressCall> {
+        let calls = self.calls.lock().unwrap();
+        let mut open: Vec<_> = calls
+            .values()
+            .filter(|call| call.squad_id == *squad_id)
+            .cloned()
+            .collect();
+        open.sort_by_key(|call| call.raised_at);
+        open
+    }
+
+    /// Whether the member has an open call
+    pub fn is_in_distress(&self, squad_id: &Uuid, member_id: &Uuid) -> bool {
+        self.calls
+            .lock()
+            .unwrap()
+            .values()
+            .any(|call| call.squad_id == *squad_id && call.member_id == *member_id)
+    }
+
+    /// Calls nobody has acknowledged yet
+    pub fn unacknowledged(&self) -> Vec<DistressCall> {
+        self.calls
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|call| call.state == DistressState::Active)
+            .cloned()
+            .collect()
+    }
+
+    /// Drop calls of members no longer in a squad; `members` holds
+    /// (squad_id, member_id) of every current member
+    pub fn retain_members(&self, members: &HashSet<(Uuid, Uuid)>) {
+        self.calls
+            .lock()
+            .unwrap()
+            .retain(|_, call| members.contains(&(call.squad_id, call.member_id)));
+    }
+
+    /// Drop every call of a deleted squad
+    pub fn remove_squad(&self, squad_id: &Uuid) {
+        self.calls.lock().unwrap().retain(|_, call| call.squad_id != *squad_id);
+    }
+
+    /// Every open call, for snapshots
+    pub fn snapshot(&self) -> Vec<DistressCall> {
+        self.calls.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Replace every open call
+    pub fn restore(&self, calls: Vec<DistressCall>) {
+        *self.calls.lock().unwrap() = calls.into_iter().map(|call| (call.sos_id, call)).collect();
+    }
+}
+
+impl Default for SosStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Follow the member's open call, if any, to a newly stored position
+pub fn track_location(state: &AppState, squad_id: &Uuid, member_id: &Uuid, location: GeoPoint) {
+    if let Some(call) = state.sos.track(squad_id, member_id, location, Utc::now()) {
+        state.feed.publish(SquadEvent::Sos(call));
+    }
+}
+
+/// Publish unacknowledged calls again every [`REBROADCAST_INTERVAL`]
+pub fn spawn_rebroadcaster(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REBROADCAST_INTERVAL);
+        loop {
+            interval.tick().await;
+            let members = state
+                .squad_manager
+                .read()
+                .await
+                .list_squads()
+                .iter()
+                .flat_map(|squad| squad.members.iter().map(|m| (squad.squad_id, m.member_id)))
+                .collect();
+            state.sos.retain_members(&members);
+            for call in state.sos.unacknowledged() {
+                state.feed.publish(SquadEvent::Sos(call));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: f64) -> GeoPoint {
+        GeoPoint {
+            accuracy: Some(5.0),
+            ..GeoPoint::at(latitude, 7.0)
+        }
+    }
+
+    fn request(latitude: f64, message: Option<&str>) -> SosRequest {
+        SosRequest {
+            location: point(latitude),
+            message: message.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_call_lifecycle() {
+        let ctx = AuditContext::system();
+        let store = SosStore::new();
+        let (squad_id, caller, medic) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc::now();
+
+        let raised = request(45.0, Some("Ankle"));
+        let call = store.raise(&ctx, squad_id, caller, "Hiker".into(), raised, now);
+        assert_eq!(call.state, DistressState::Active);
+        assert_eq!(store.unacknowledged().len(), 1);
+
+        // A second press renews the same call at the new position
+        let renewal = request(45.001, None);
+        let renewed = store.raise(&ctx, squad_id, caller, "Hiker".into(), renewal, now);
+        assert_eq!(renewed.sos_id, call.sos_id);
+        assert_eq!(renewed.message.as_deref(), Some("Ankle"));
+        assert_eq!(store.squad_calls(&squad_id).len(), 1);
+
+        let moved = store.track(&squad_id, &caller, point(45.002), now).unwrap();
+        assert_eq!(moved.location.latitude, 45.002);
+        assert!(store.track(&squad_id, &medic, point(45.0), now).is_none());
+
+        assert!(matches!(
+            store.acknowledge(&ctx, &call.sos_id, &squad_id, caller, now),
+            Err(SosError::OwnCall)
+        ));
+        assert!(matches!(
+            store.acknowledge(&ctx, &call.sos_id, &Uuid::new_v4(), medic, now),
+            Err(SosError::NotFound)
+        ));
+        let acked = store.acknowledge(&ctx, &call.sos_id, &squad_id, medic, now).unwrap();
+        assert_eq!(acked.acknowledged_by, Some(medic));
+        assert!(store.unacknowledged().is_empty());
+        assert!(store.is_in_distress(&squad_id, &caller));
+
+        assert!(matches!(
+            store.resolve(&ctx, &call.sos_id, &squad_id, medic, false, now),
+            Err(SosError::NotAllowed)
+        ));
+        let resolved = store.resolve(&ctx, &call.sos_id, &squad_id, caller, false, now).unwrap();
+        assert_eq!(resolved.state, DistressState::Resolved);
+        assert!(store.squad_calls(&squad_id).is_empty());
+    }
+
+    #[test]
+    fn test_calls_of_departed_members_are_dropped() {
+        let ctx = AuditContext::system();
+        let store = SosStore::new();
+        let (squad_id, stays, left) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        store.raise(&ctx, squad_id, stays, "A".into(), request(45.0, None), Utc::now());
+        store.raise(&ctx, squad_id, left, "B".into(), request(45.0, None), Utc::now());
+
+        store.retain_members(&HashSet::from([(squad_id, stays)]));
+        let calls = store.squad_calls(&squad_id);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].member_id, stays);
+    }
+}
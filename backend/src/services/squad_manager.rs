@@ -1,26 +1,97 @@
 //! Squad management service
 
-use std::collections::HashMap;
-use chrono::Utc;
+use std::collections::{HashMap, VecDeque};
+use chrono::{DateTime, Duration, Utc};
 use rand::Rng;
+use serde_json::{json, Value};
 use uuid::Uuid;
 
-use crate::models::{Member, Squad, SquadSettings};
+use crate::models::{
+    Member, PlannedRoute, RouteRequest, Squad, SquadSettings, Waypoint, WaypointRequest,
+};
+use crate::services::audit::{AuditContext, AuditLog};
+use crate::services::{password, routes};
+
+/// Waypoints a squad may hold at once
+pub const MAX_WAYPOINTS_PER_SQUAD: usize = 100;
+
+/// Outcome of checking a join passphrase
+///
+/// Argon2 is deliberately slow, so callers verify the passphrase without
+/// holding the manager's lock and hand the outcome to
+/// [`SquadManager::join_squad`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassphraseCheck {
+    /// The squad had no passphrase when it was looked up
+    NotRequired,
+    /// The squad has a passphrase but none was supplied
+    Missing,
+    /// The passphrase matched this hash
+    Matched(String),
+    /// The passphrase did not match
+    Mismatched,
+}
+
+impl PassphraseCheck {
+    /// Verify `passphrase` against the squad's `hash`; blocks for the length
+    /// of an Argon2id verification
+    pub fn verify(hash: Option<&str>, passphrase: Option<&str>) -> Self {
+        match (hash, passphrase) {
+            (None, _) => PassphraseCheck::NotRequired,
+            (Some(_), None) => PassphraseCheck::Missing,
+            (Some(hash), Some(passphrase)) if password::verify_password(passphrase, hash) => {
+                PassphraseCheck::Matched(hash.to_string())
+            }
+            (Some(_), Some(_)) => PassphraseCheck::Mismatched,
+        }
+    }
+}
 
 /// Manages squads and membership
 pub struct SquadManager {
     squads: HashMap<Uuid, Squad>,
     join_codes: HashMap<String, Uuid>,
+    max_squad_size: usize,
+    /// Wrong passphrases a squad accepts per `join_failure_window` before
+    /// refusing further join attempts
+    max_join_failures: usize,
+    join_failure_window: Duration,
+    /// Recent wrong passphrases per squad, oldest first
+    join_failures: HashMap<Uuid, VecDeque<DateTime<Utc>>>,
+    audit: AuditLog,
 }
 
 impl SquadManager {
     pub fn new() -> Self {
+        Self::with_audit(AuditLog::new())
+    }
+
+    pub fn with_audit(audit: AuditLog) -> Self {
         Self {
             squads: HashMap::new(),
             join_codes: HashMap::new(),
+            max_squad_size: 50,
+            max_join_failures: 5,
+            join_failure_window: Duration::seconds(60),
+            join_failures: HashMap::new(),
+            audit,
         }
     }
 
+    /// Cap the number of members (leader included) a squad may hold
+    pub fn with_max_squad_size(mut self, max_squad_size: usize) -> Self {
+        self.max_squad_size = max_squad_size;
+        self
+    }
+
+    /// Refuse joins to a squad once it has seen `max_failures` wrong
+    /// passphrases within `window_secs`
+    pub fn with_join_throttle(mut self, max_failures: usize, window_secs: i64) -> Self {
+        self.max_join_failures = max_failures;
+        self.join_failure_window = Duration::seconds(window_secs);
+        self
+    }
+
     /// Generate a unique 6-character join code
     fn generate_join_code(&self) -> String {
         let chars: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
@@ -35,11 +106,16 @@ impl SquadManager {
     }
 
     /// Create a new squad
+    ///
+    /// `passphrase_hash` is an Argon2id PHC string; when set, members must
+    /// supply the matching passphrase in addition to the join code.
     pub fn create_squad(
         &mut self,
+        ctx: &AuditContext,
         name: String,
         leader_name: String,
         settings: Option<SquadSettings>,
+        passphrase_hash: Option<String>,
     ) -> (Squad, Uuid) {
         let squad_id = Uuid::new_v4();
         let leader_id = Uuid::new_v4();
@@ -61,11 +137,22 @@ impl SquadManager {
             leader_id,
             members: vec![leader],
             settings: settings.unwrap_or_default(),
+            waypoints: Vec::new(),
+            passphrase_hash,
+            route: None,
         };
 
         self.join_codes.insert(join_code, squad_id);
         self.squads.insert(squad_id, squad.clone());
 
+        self.audit.record(
+            ctx,
+            "squad.create",
+            Some(squad_id),
+            Some(leader_id),
+            json!({ "name": squad.name, "has_passphrase": squad.passphrase_hash.is_some() }),
+        );
+
         (squad, leader_id)
     }
 
@@ -86,11 +173,41 @@ impl SquadManager {
         self.squads.values().collect()
     }
 
+    /// Squad ID and passphrase hash for a join code, for checking the
+    /// passphrase before [`SquadManager::join_squad`]
+    ///
+    /// Fails with `TooManyJoinAttempts` while the squad is refusing joins
+    /// after repeated wrong passphrases.
+    pub fn join_passphrase(
+        &self,
+        join_code: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(Uuid, Option<String>), SquadError> {
+        let squad = self
+            .get_squad_by_code(join_code)
+            .ok_or(SquadError::InvalidJoinCode)?;
+        let window_start = now - self.join_failure_window;
+        let recent_failures = self
+            .join_failures
+            .get(&squad.squad_id)
+            .map_or(0, |failures| failures.iter().filter(|at| **at > window_start).count());
+        if recent_failures >= self.max_join_failures {
+            return Err(SquadError::TooManyJoinAttempts);
+        }
+        Ok((squad.squad_id, squad.passphrase_hash.clone()))
+    }
+
     /// Join a squad
+    ///
+    /// `passphrase` is the outcome of checking the supplied passphrase against
+    /// the hash from [`SquadManager::join_passphrase`]. A match only counts if
+    /// the squad's passphrase has not changed since.
     pub fn join_squad(
         &mut self,
+        ctx: &AuditContext,
         join_code: &str,
         display_name: String,
+        passphrase: PassphraseCheck,
     ) -> Result<(Squad, Uuid), SquadError> {
         let squad_id = self
             .join_codes
@@ -103,6 +220,40 @@ impl SquadManager {
             .get_mut(&squad_id)
             .ok_or(SquadError::SquadNotFound)?;
 
+        // Check the passphrase, if the squad has one
+        if let Some(hash) = &squad.passphrase_hash {
+            let err = match passphrase {
+                PassphraseCheck::Matched(matched) if matched == *hash => None,
+                PassphraseCheck::NotRequired | PassphraseCheck::Missing => {
+                    Some(SquadError::PassphraseRequired)
+                }
+                _ => Some(SquadError::InvalidPassphrase),
+            };
+            if let Some(err) = err {
+                if matches!(err, SquadError::InvalidPassphrase) {
+                    let now = Utc::now();
+                    let failures = self.join_failures.entry(squad_id).or_default();
+                    let window_start = now - self.join_failure_window;
+                    while failures.front().is_some_and(|at| *at <= window_start) {
+                        failures.pop_front();
+                    }
+                    failures.push_back(now);
+                }
+                self.audit.record(
+                    ctx,
+                    "squad.join_denied",
+                    Some(squad_id),
+                    None,
+                    json!({ "reason": err.to_string() }),
+                );
+                return Err(err);
+            }
+        }
+
+        if squad.members.len() >= self.max_squad_size {
+            return Err(SquadError::SquadFull);
+        }
+
         // Check if name is taken
         if squad.members.iter().any(|m| m.display_name == display_name) {
             return Err(SquadError::NameTaken);
@@ -111,7 +262,7 @@ impl SquadManager {
         let member_id = Uuid::new_v4();
         let member = Member {
             member_id,
-            display_name,
+            display_name: display_name.clone(),
             avatar_url: None,
             joined_at: Utc::now(),
             is_leader: false,
@@ -119,12 +270,48 @@ impl SquadManager {
 
         squad.members.push(member);
 
+        self.audit.record(
+            ctx,
+            "squad.join",
+            Some(squad_id),
+            Some(member_id),
+            json!({ "display_name": display_name }),
+        );
         Ok((squad.clone(), member_id))
     }
 
+    /// Set or clear a squad's join passphrase (leader only)
+    pub fn set_passphrase(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+        passphrase_hash: Option<String>,
+    ) -> Result<(), SquadError> {
+        let squad = self
+            .squads
+            .get_mut(squad_id)
+            .ok_or(SquadError::SquadNotFound)?;
+
+        if &squad.leader_id != member_id {
+            return Err(SquadError::NotLeader);
+        }
+
+        let action = if passphrase_hash.is_some() {
+            "squad.passphrase_set"
+        } else {
+            "squad.passphrase_cleared"
+        };
+        squad.passphrase_hash = passphrase_hash;
+
+        self.audit.record(ctx, action, Some(*squad_id), None, Value::Null);
+        Ok(())
+    }
+
     /// Leave a squad
     pub fn leave_squad(
         &mut self,
+        ctx: &AuditContext,
         squad_id: &Uuid,
         member_id: &Uuid,
     ) -> Result<(), SquadError> {
@@ -145,16 +332,105 @@ impl SquadManager {
         if member.is_leader {
             self.join_codes.remove(&squad.join_code);
             self.squads.remove(squad_id);
+            self.join_failures.remove(squad_id);
+            self.audit.record(
+                ctx,
+                "squad.delete",
+                Some(*squad_id),
+                Some(*member_id),
+                json!({ "reason": "leader_left" }),
+            );
             return Ok(());
         }
 
         squad.members.remove(idx);
+        self.audit.record(ctx, "squad.leave", Some(*squad_id), Some(*member_id), Value::Null);
         Ok(())
     }
 
+    /// Delete a squad regardless of who leads it (admin action)
+    pub fn force_delete_squad(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+    ) -> Result<Squad, SquadError> {
+        let squad = self
+            .squads
+            .remove(squad_id)
+            .ok_or(SquadError::SquadNotFound)?;
+        self.join_codes.remove(&squad.join_code);
+        self.join_failures.remove(squad_id);
+
+        self.audit.record(
+            ctx,
+            "squad.delete",
+            Some(*squad_id),
+            None,
+            json!({ "reason": "admin", "name": squad.name }),
+        );
+        Ok(squad)
+    }
+
+    /// Remove a member from a squad (admin action)
+    ///
+    /// The leader cannot be kicked; delete the squad instead.
+    pub fn kick_member(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+    ) -> Result<Member, SquadError> {
+        let squad = self
+            .squads
+            .get_mut(squad_id)
+            .ok_or(SquadError::SquadNotFound)?;
+
+        let idx = squad
+            .members
+            .iter()
+            .position(|m| &m.member_id == member_id)
+            .ok_or(SquadError::MemberNotFound)?;
+
+        if squad.members[idx].is_leader {
+            return Err(SquadError::CannotKickLeader);
+        }
+
+        let member = squad.members.remove(idx);
+        self.audit.record(
+            ctx,
+            "squad.kick",
+            Some(*squad_id),
+            Some(*member_id),
+            json!({ "display_name": member.display_name }),
+        );
+        Ok(member)
+    }
+
+    /// Replace a squad's join code with a fresh one; the old code stops working
+    pub fn rotate_join_code(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+    ) -> Result<String, SquadError> {
+        if !self.squads.contains_key(squad_id) {
+            return Err(SquadError::SquadNotFound);
+        }
+
+        let new_code = self.generate_join_code();
+        let squad = self.squads.get_mut(squad_id).expect("checked above");
+        let old_code = std::mem::replace(&mut squad.join_code, new_code.clone());
+
+        self.join_codes.remove(&old_code);
+        self.join_codes.insert(new_code.clone(), *squad_id);
+
+        self.audit.record(ctx, "squad.rotate_join_code", Some(*squad_id), None, Value::Null);
+        Ok(new_code)
+    }
+
     /// Delete a squad (leader only)
     pub fn delete_squad(
         &mut self,
+        ctx: &AuditContext,
         squad_id: &Uuid,
         member_id: &Uuid,
     ) -> Result<(), SquadError> {
@@ -169,8 +445,244 @@ impl SquadManager {
 
         self.join_codes.remove(&squad.join_code);
         self.squads.remove(squad_id);
+        self.join_failures.remove(squad_id);
+
+        self.audit.record(
+            ctx,
+            "squad.delete",
+            Some(*squad_id),
+            Some(*member_id),
+            json!({ "reason": "leader" }),
+        );
         Ok(())
     }
+
+    /// Add a waypoint to a squad on behalf of one of its members; expired
+    /// waypoints are dropped first and their IDs returned with the new one
+    pub fn add_waypoint(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        creator: &Member,
+        request: WaypointRequest,
+    ) -> Result<(Waypoint, Vec<Uuid>), SquadError> {
+        let squad = self
+            .squads
+            .get_mut(squad_id)
+            .ok_or(SquadError::SquadNotFound)?;
+
+        let now = Utc::now();
+        let mut expired = Vec::new();
+        squad.waypoints.retain(|waypoint| {
+            let keep = !waypoint.is_expired(now);
+            if !keep {
+                expired.push(waypoint.waypoint_id);
+            }
+            keep
+        });
+        if squad.waypoints.len() >= MAX_WAYPOINTS_PER_SQUAD {
+            return Err(SquadError::TooManyWaypoints);
+        }
+
+        let waypoint = Waypoint {
+            waypoint_id: Uuid::new_v4(),
+            squad_id: *squad_id,
+            name: request.name,
+            icon: request.icon,
+            location: request.location,
+            description: request.description,
+            created_by: creator.member_id,
+            created_by_name: creator.display_name.clone(),
+            created_at: now,
+            updated_at: now,
+            expires_at: request.expires_at,
+        };
+        squad.waypoints.push(waypoint.clone());
+
+        self.audit.record(
+            ctx,
+            "waypoint.create",
+            Some(*squad_id),
+            Some(creator.member_id),
+            json!({ "waypoint_id": waypoint.waypoint_id, "name": waypoint.name }),
+        );
+        Ok((waypoint, expired))
+    }
+
+    /// Replace a waypoint's details; only its creator or the leader may
+    pub fn update_waypoint(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        waypoint_id: &Uuid,
+        member: &Member,
+        request: WaypointRequest,
+    ) -> Result<Waypoint, SquadError> {
+        let waypoint = self.editable_waypoint(squad_id, waypoint_id, member)?;
+        waypoint.name = request.name;
+        waypoint.icon = request.icon;
+        waypoint.location = request.location;
+        waypoint.description = request.description;
+        waypoint.expires_at = request.expires_at;
+        waypoint.updated_at = Utc::now();
+        let waypoint = waypoint.clone();
+
+        self.audit.record(
+            ctx,
+            "waypoint.update",
+            Some(*squad_id),
+            Some(member.member_id),
+            json!({ "waypoint_id": waypoint_id, "name": waypoint.name }),
+        );
+        Ok(waypoint)
+    }
+
+    /// Delete a waypoint; only its creator or the leader may
+    pub fn delete_waypoint(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        waypoint_id: &Uuid,
+        member: &Member,
+    ) -> Result<Waypoint, SquadError> {
+        self.editable_waypoint(squad_id, waypoint_id, member)?;
+        let squad = self.squads.get_mut(squad_id).expect("checked above");
+        let idx = squad
+            .waypoints
+            .iter()
+            .position(|w| &w.waypoint_id == waypoint_id)
+            .expect("checked above");
+        let waypoint = squad.waypoints.remove(idx);
+
+        self.audit.record(
+            ctx,
+            "waypoint.delete",
+            Some(*squad_id),
+            Some(member.member_id),
+            json!({ "waypoint_id": waypoint_id, "name": waypoint.name }),
+        );
+        Ok(waypoint)
+    }
+
+    /// Look up a live waypoint `member` is allowed to change
+    fn editable_waypoint(
+        &mut self,
+        squad_id: &Uuid,
+        waypoint_id: &Uuid,
+        member: &Member,
+    ) -> Result<&mut Waypoint, SquadError> {
+        let now = Utc::now();
+        let waypoint = self
+            .squads
+            .get_mut(squad_id)
+            .ok_or(SquadError::SquadNotFound)?
+            .waypoints
+            .iter_mut()
+            .find(|w| &w.waypoint_id == waypoint_id && !w.is_expired(now))
+            .ok_or(SquadError::WaypointNotFound)?;
+        if waypoint.created_by != member.member_id && !member.is_leader {
+            return Err(SquadError::NotWaypointCreator);
+        }
+        Ok(waypoint)
+    }
+
+    /// Drop expired waypoints from every squad; returns (squad, waypoint) IDs
+    pub fn expire_waypoints(&mut self, now: DateTime<Utc>) -> Vec<(Uuid, Uuid)> {
+        let mut expired = Vec::new();
+        for squad in self.squads.values_mut() {
+            squad.waypoints.retain(|waypoint| {
+                let keep = !waypoint.is_expired(now);
+                if !keep {
+                    expired.push((squad.squad_id, waypoint.waypoint_id));
+                }
+                keep
+            });
+        }
+        expired
+    }
+
+    /// Set or replace a squad's planned route (leader only)
+    pub fn set_route(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+        request: RouteRequest,
+    ) -> Result<PlannedRoute, SquadError> {
+        let squad = self
+            .squads
+            .get_mut(squad_id)
+            .ok_or(SquadError::SquadNotFound)?;
+
+        if &squad.leader_id != member_id {
+            return Err(SquadError::NotLeader);
+        }
+
+        let route = PlannedRoute {
+            route_id: Uuid::new_v4(),
+            squad_id: *squad_id,
+            name: request.name,
+            length_m: routes::length_m(&request.points),
+            points: request.points,
+            max_deviation_m: request.max_deviation_m,
+            finish_by: request.finish_by,
+            uploaded_by: *member_id,
+            uploaded_at: Utc::now(),
+        };
+        squad.route = Some(route.clone());
+
+        self.audit.record(
+            ctx,
+            "route.set",
+            Some(*squad_id),
+            Some(*member_id),
+            json!({
+                "route_id": route.route_id,
+                "name": route.name,
+                "points": route.points.len(),
+                "length_m": route.length_m,
+            }),
+        );
+        Ok(route)
+    }
+
+    /// Remove a squad's planned route (leader only)
+    pub fn clear_route(
+        &mut self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        member_id: &Uuid,
+    ) -> Result<PlannedRoute, SquadError> {
+        let squad = self
+            .squads
+            .get_mut(squad_id)
+            .ok_or(SquadError::SquadNotFound)?;
+
+        if &squad.leader_id != member_id {
+            return Err(SquadError::NotLeader);
+        }
+
+        let route = squad.route.take().ok_or(SquadError::RouteNotFound)?;
+        self.audit.record(
+            ctx,
+            "route.clear",
+            Some(*squad_id),
+            Some(*member_id),
+            json!({ "route_id": route.route_id }),
+        );
+        Ok(route)
+    }
+
+    /// Replace every squad, e.g. when restoring a snapshot; join failures
+    /// counted so far are forgotten
+    pub fn restore(&mut self, squads: Vec<Squad>) {
+        self.join_failures.clear();
+        self.join_codes = squads
+            .iter()
+            .map(|s| (s.join_code.clone(), s.squad_id))
+            .collect();
+        self.squads = squads.into_iter().map(|s| (s.squad_id, s)).collect();
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -185,4 +697,243 @@ pub enum SquadError {
     NameTaken,
     #[error("Only the leader can perform this action")]
     NotLeader,
+    #[error("Squad is full")]
+    SquadFull,
+    #[error("The leader cannot be removed from their squad")]
+    CannotKickLeader,
+    #[error("Squad requires a passphrase")]
+    PassphraseRequired,
+    #[error("Invalid passphrase")]
+    InvalidPassphrase,
+    #[error("Waypoint not found")]
+    WaypointNotFound,
+    #[error("Only the creator or the squad leader can change a waypoint")]
+    NotWaypointCreator,
+    #[error("Squad already has {MAX_WAYPOINTS_PER_SQUAD} waypoints")]
+    TooManyWaypoints,
+    #[error("Squad has no planned route")]
+    RouteNotFound,
+    #[error("Too many wrong passphrases; try again later")]
+    TooManyJoinAttempts,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_without_passphrase() {
+        let mut manager = SquadManager::new();
+        let ctx = AuditContext::system();
+        let (squad, _) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, None);
+
+        let (joined, _) = manager
+            .join_squad(&ctx, &squad.join_code, "Scout".into(), PassphraseCheck::NotRequired)
+            .unwrap();
+        assert_eq!(joined.members.len(), 2);
+    }
+
+    #[test]
+    fn test_join_with_passphrase() {
+        let mut manager = SquadManager::new();
+        let ctx = AuditContext::system();
+        let hash = password::hash_password("rally at dawn").unwrap();
+        let (squad, _) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, Some(hash));
+
+        assert!(matches!(
+            manager.join_squad(&ctx, &squad.join_code, "Scout".into(), PassphraseCheck::NotRequired),
+            Err(SquadError::PassphraseRequired)
+        ));
+        let (_, hash) = manager.join_passphrase(&squad.join_code, Utc::now()).unwrap();
+        let wrong = PassphraseCheck::verify(hash.as_deref(), Some("wrong"));
+        assert_eq!(wrong, PassphraseCheck::Mismatched);
+        assert!(matches!(
+            manager.join_squad(&ctx, &squad.join_code, "Scout".into(), wrong),
+            Err(SquadError::InvalidPassphrase)
+        ));
+        let right = PassphraseCheck::verify(hash.as_deref(), Some("rally at dawn"));
+        assert!(manager
+            .join_squad(&ctx, &squad.join_code, "Scout".into(), right.clone())
+            .is_ok());
+
+        // A match against a passphrase that has since changed does not count
+        manager
+            .set_passphrase(&ctx, &squad.squad_id, &squad.leader_id, Some(password::hash_password("new").unwrap()))
+            .unwrap();
+        assert!(matches!(
+            manager.join_squad(&ctx, &squad.join_code, "Medic".into(), right),
+            Err(SquadError::InvalidPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_wrong_passphrases_throttled() {
+        let mut manager = SquadManager::new().with_join_throttle(3, 60);
+        let ctx = AuditContext::system();
+        let hash = password::hash_password("rally at dawn").unwrap();
+        let (squad, _) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, Some(hash));
+
+        for _ in 0..3 {
+            assert!(manager.join_passphrase(&squad.join_code, Utc::now()).is_ok());
+            let check = PassphraseCheck::Mismatched;
+            assert!(manager.join_squad(&ctx, &squad.join_code, "Scout".into(), check).is_err());
+        }
+        assert!(matches!(
+            manager.join_passphrase(&squad.join_code, Utc::now()),
+            Err(SquadError::TooManyJoinAttempts)
+        ));
+        // Refusals lapse once the failures leave the window
+        let later = Utc::now() + Duration::seconds(61);
+        assert!(manager.join_passphrase(&squad.join_code, later).is_ok());
+    }
+
+    #[test]
+    fn test_join_failures_dropped_with_squad() {
+        let mut manager = SquadManager::new();
+        let ctx = AuditContext::system();
+        let hash = password::hash_password("rally at dawn").unwrap();
+        let (squad, leader_id) =
+            manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, Some(hash.clone()));
+        let check = PassphraseCheck::Mismatched;
+        assert!(manager.join_squad(&ctx, &squad.join_code, "Scout".into(), check.clone()).is_err());
+
+        manager.leave_squad(&ctx, &squad.squad_id, &leader_id).unwrap();
+        assert!(manager.join_failures.is_empty());
+
+        let (squad, _) =
+            manager.create_squad(&ctx, "Bravo".into(), "Lead".into(), None, Some(hash));
+        assert!(manager.join_squad(&ctx, &squad.join_code, "Scout".into(), check).is_err());
+        manager.restore(vec![squad]);
+        assert!(manager.join_failures.is_empty());
+    }
+
+    #[test]
+    fn test_squad_full() {
+        let mut manager = SquadManager::new().with_max_squad_size(2);
+        let ctx = AuditContext::system();
+        let (squad, _) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, None);
+
+        assert!(manager
+            .join_squad(&ctx, &squad.join_code, "Scout".into(), PassphraseCheck::NotRequired)
+            .is_ok());
+        assert!(matches!(
+            manager.join_squad(&ctx, &squad.join_code, "Medic".into(), PassphraseCheck::NotRequired),
+            Err(SquadError::SquadFull)
+        ));
+    }
+
+    #[test]
+    fn test_rotate_join_code() {
+        let mut manager = SquadManager::new();
+        let ctx = AuditContext::system();
+        let (squad, _) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, None);
+
+        let new_code = manager.rotate_join_code(&ctx, &squad.squad_id).unwrap();
+        assert_ne!(new_code, squad.join_code);
+        assert!(manager.get_squad_by_code(&squad.join_code).is_none());
+        assert_eq!(
+            manager.get_squad_by_code(&new_code).unwrap().squad_id,
+            squad.squad_id
+        );
+    }
+
+    #[test]
+    fn test_kick_member() {
+        let mut manager = SquadManager::new();
+        let ctx = AuditContext::system();
+        let (squad, leader_id) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, None);
+        let (_, member_id) = manager
+            .join_squad(&ctx, &squad.join_code, "Scout".into(), PassphraseCheck::NotRequired)
+            .unwrap();
+
+        assert!(matches!(
+            manager.kick_member(&ctx, &squad.squad_id, &leader_id),
+            Err(SquadError::CannotKickLeader)
+        ));
+        assert_eq!(
+            manager.kick_member(&ctx, &squad.squad_id, &member_id).unwrap().member_id,
+            member_id
+        );
+        assert_eq!(manager.get_squad(&squad.squad_id).unwrap().members.len(), 1);
+    }
+
+    #[test]
+    fn test_set_passphrase_leader_only() {
+        let mut manager = SquadManager::new();
+        let ctx = AuditContext::system();
+        let (squad, leader_id) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, None);
+        let (_, member_id) = manager
+            .join_squad(&ctx, &squad.join_code, "Scout".into(), PassphraseCheck::NotRequired)
+            .unwrap();
+
+        assert!(matches!(
+            manager.set_passphrase(&ctx, &squad.squad_id, &member_id, Some("x".into())),
+            Err(SquadError::NotLeader)
+        ));
+
+        let hash = password::hash_password("secret").unwrap();
+        manager
+            .set_passphrase(&ctx, &squad.squad_id, &leader_id, Some(hash))
+            .unwrap();
+        assert!(matches!(
+            manager.join_squad(&ctx, &squad.join_code, "Medic".into(), PassphraseCheck::NotRequired),
+            Err(SquadError::PassphraseRequired)
+        ));
+    }
+
+    #[test]
+    fn test_waypoints_creator_or_leader() {
+        use crate::models::{GeoPoint, WaypointIcon};
+
+        let mut manager = SquadManager::new();
+        let ctx = AuditContext::system();
+        let (squad, leader_id) = manager.create_squad(&ctx, "Alpha".into(), "Lead".into(), None, None);
+        let (_, scout_id) = manager
+            .join_squad(&ctx, &squad.join_code, "Scout".into(), PassphraseCheck::NotRequired)
+            .unwrap();
+        let (_, medic_id) = manager
+            .join_squad(&ctx, &squad.join_code, "Medic".into(), PassphraseCheck::NotRequired)
+            .unwrap();
+        let members = manager.get_squad(&squad.squad_id).unwrap().members.clone();
+        let member = |id: Uuid| members.iter().find(|m| m.member_id == id).unwrap().clone();
+        let (leader, scout, medic) = (member(leader_id), member(scout_id), member(medic_id));
+        let request = |name: &str, expires_at| WaypointRequest {
+            name: name.into(),
+            icon: WaypointIcon::RallyPoint,
+            location: GeoPoint::at(51.5, -0.1),
+            description: None,
+            expires_at,
+        };
+
+        let (rally, _) = manager
+            .add_waypoint(&ctx, &squad.squad_id, &scout, request("Rally", None))
+            .unwrap();
+        assert_eq!(rally.created_by_name, "Scout");
+        assert!(matches!(
+            manager.update_waypoint(&ctx, &squad.squad_id, &rally.waypoint_id, &medic, request("X", None)),
+            Err(SquadError::NotWaypointCreator)
+        ));
+        let renamed = manager
+            .update_waypoint(&ctx, &squad.squad_id, &rally.waypoint_id, &scout, request("Camp", None))
+            .unwrap();
+        assert_eq!(renamed.name, "Camp");
+        manager
+            .delete_waypoint(&ctx, &squad.squad_id, &rally.waypoint_id, &leader)
+            .unwrap();
+        assert!(matches!(
+            manager.delete_waypoint(&ctx, &squad.squad_id, &rally.waypoint_id, &leader),
+            Err(SquadError::WaypointNotFound)
+        ));
+
+        let soon = Utc::now() + chrono::Duration::minutes(5);
+        let (hazard, _) = manager
+            .add_waypoint(&ctx, &squad.squad_id, &medic, request("Ice", Some(soon)))
+            .unwrap();
+        assert!(manager.expire_waypoints(Utc::now()).is_empty());
+        assert_eq!(
+            manager.expire_waypoints(soon),
+            vec![(squad.squad_id, hazard.waypoint_id)]
+        );
+        assert!(manager.get_squad(&squad.squad_id).unwrap().waypoints.is_empty());
+    }
 }
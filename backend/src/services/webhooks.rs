@@ -0,0 +1,896 @@
+//! Outbound webhooks
+//!
+//! Leaders and admins register URLs per squad. Every matching squad event
+//! becomes a delivery: a JSON [`WebhookPayload`] POSTed with an HMAC-SHA256
+//! signature of `"{timestamp}.{body}"` keyed by the webhook's secret.
+//! Attempts that fail (no answer or a non-2xx status) are queued again after
+//! a growing delay until [`RETRY_DELAYS_SECS`] runs out. Each webhook keeps a
+//! bounded log of its deliveries.
+//!
+//! URLs must resolve to public addresses, both when they are registered and
+//! on every attempt (a DNS answer can change in between). All deliveries go
+//! through one client whose resolver only hands out addresses that pass the
+//! check, so the request goes to the addresses that were checked. Hosts
+//! listed in `webhook_allowed_hosts` are exempt. At most
+//! [`MAX_CONCURRENT_DELIVERIES`] requests are in flight at once.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::{Notify, Semaphore};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::{
+    DeliveryState, SquadEvent, Webhook, WebhookDelivery, WebhookEventKind, WebhookPayload,
+};
+use crate::services::admin_auth::generate_token;
+use crate::services::audit::{AuditContext, AuditLog};
+use crate::AppState;
+
+/// `sha256=<hex HMAC of "{timestamp}.{body}">`
+pub const SIGNATURE_HEADER: &str = "x-squadz-signature";
+/// Unix seconds the signature was made at; reject old ones to stop replays
+pub const TIMESTAMP_HEADER: &str = "x-squadz-timestamp";
+pub const EVENT_HEADER: &str = "x-squadz-event";
+pub const DELIVERY_HEADER: &str = "x-squadz-delivery";
+
+/// Most webhooks one squad can register
+pub const MAX_WEBHOOKS_PER_SQUAD: usize = 10;
+
+/// Deliveries kept in each webhook's log
+pub const MAX_DELIVERY_LOG: usize = 100;
+
+/// Wait before each retry; a delivery is tried once more than there are
+/// entries
+pub const RETRY_DELAYS_SECS: [i64; 5] = [10, 60, 300, 1800, 7200];
+
+/// Deliveries waiting across all webhooks before the oldest are given up
+const MAX_QUEUED: usize = 1000;
+
+/// Deliveries in flight at once; due ones beyond this wait in the queue
+pub const MAX_CONCURRENT_DELIVERIES: usize = 32;
+
+/// How long an endpoint has to answer
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How often members are checked for newly stale fixes
+const STALE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Longest transport error kept in the log
+const MAX_ERROR_CHARS: usize = 200;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Webhook not found")]
+    NotFound,
+    #[error("A squad can have at most {MAX_WEBHOOKS_PER_SQUAD} webhooks")]
+    TooMany,
+    /// The URL's host can't be resolved or isn't a public address
+    #[error("{0}")]
+    Destination(String),
+}
+
+/// A webhook with its signing secret, as written to snapshots
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookRecord {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// A delivery waiting for its next attempt
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub delivery_id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEventKind,
+    pub url: String,
+    pub secret: String,
+    pub body: String,
+    /// Attempts made so far
+    pub attempts: u32,
+    pub due: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Inner {
+    hooks: HashMap<Uuid, WebhookRecord>,
+    /// Oldest delivery first
+    logs: HashMap<Uuid, VecDeque<WebhookDelivery>>,
+    queue: VecDeque<Job>,
+}
+
+impl Inner {
+    fn delivery_mut(
+        &mut self,
+        webhook_id: &Uuid,
+        delivery_id: &Uuid,
+    ) -> Option<&mut WebhookDelivery> {
+        self.logs
+            .get_mut(webhook_id)?
+            .iter_mut()
+            .rev()
+            .find(|d| d.delivery_id == *delivery_id)
+    }
+}
+
+/// Registered webhooks, their delivery logs and the retry queue (cheap to
+/// clone)
+#[derive(Clone)]
+pub struct WebhookStore {
+    inner: Arc<Mutex<Inner>>,
+    /// Wakes the dispatcher when a job is queued
+    wake: Arc<Notify>,
+    audit: AuditLog,
+    /// Hosts exempt from the public address check
+    allowed_hosts: Arc<[String]>,
+}
+
+impl WebhookStore {
+    pub fn new() -> Self {
+        Self::with_audit(AuditLog::new())
+    }
+
+    pub fn with_audit(audit: AuditLog) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::default())),
+            wake: Arc::new(Notify::new()),
+            audit,
+            allowed_hosts: Arc::new([]),
+        }
+    }
+
+    /// Let webhooks reach these hosts even at non-public addresses
+    pub fn with_allowed_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.allowed_hosts = hosts.into();
+        self
+    }
+
+    /// Check that a URL about to be registered resolves to public addresses
+    pub async fn check_destination(&self, url: &str) -> Result<(), WebhookError> {
+        resolve_destination(url, &self.allowed_hosts)
+            .await
+            .map(|_| ())
+            .map_err(WebhookError::Destination)
+    }
+
+    /// Register a webhook; returns it with its newly generated secret
+    pub fn create(
+        &self,
+        ctx: &AuditContext,
+        squad_id: Uuid,
+        url: String,
+        events: Vec<WebhookEventKind>,
+    ) -> Result<(Webhook, String), WebhookError> {
+        let mut inner = self.inner.lock().unwrap();
+        let count = inner.hooks.values().filter(|r| r.webhook.squad_id == squad_id).count();
+        if count >= MAX_WEBHOOKS_PER_SQUAD {
+            return Err(WebhookError::TooMany);
+        }
+
+        let webhook = Webhook {
+            webhook_id: Uuid::new_v4(),
+            squad_id,
+            url,
+            events,
+            created_at: Utc::now(),
+        };
+        let secret = format!("whsec_{}", generate_token());
+        inner.hooks.insert(
+            webhook.webhook_id,
+            WebhookRecord {
+                webhook: webhook.clone(),
+                secret: secret.clone(),
+            },
+        );
+        drop(inner);
+
+        self.audit.record(
+            ctx,
+            "webhook.create",
+            Some(squad_id),
+            None,
+            json!({
+                "webhook_id": webhook.webhook_id,
+                "url": webhook.url,
+                "events": webhook.events,
+            }),
+        );
+        Ok((webhook, secret))
+    }
+
+    /// Webhooks of a squad, oldest first
+    pub fn list(&self, squad_id: &Uuid) -> Vec<Webhook> {
+        let inner = self.inner.lock().unwrap();
+        let mut hooks: Vec<_> = inner
+            .hooks
+            .values()
+            .filter(|r| r.webhook.squad_id == *squad_id)
+            .map(|r| r.webhook.clone())
+            .collect();
+        hooks.sort_by_key(|hook| hook.created_at);
+        hooks
+    }
+
+    /// Remove a webhook with its log and any deliveries still queued
+    pub fn delete(
+        &self,
+        ctx: &AuditContext,
+        squad_id: &Uuid,
+        webhook_id: &Uuid,
+    ) -> Result<(), WebhookError> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.hooks.get(webhook_id).is_none_or(|r| r.webhook.squad_id != *squad_id) {
+            return Err(WebhookError::NotFound);
+        }
+        let record = inner.hooks.remove(webhook_id).ok_or(WebhookError::NotFound)?;
+        inner.logs.remove(webhook_id);
+        inner.queue.retain(|job| job.webhook_id != *webhook_id);
+        drop(inner);
+
+        self.audit.record(
+            ctx,
+            "webhook.delete",
+            Some(*squad_id),
+            None,
+            json!({ "webhook_id": webhook_id, "url": record.webhook.url }),
+        );
+        Ok(())
+    }
+
+    /// A webhook's delivery log, newest first
+    pub fn deliveries(
+        &self,
+        squad_id: &Uuid,
+        webhook_id: &Uuid,
+    ) -> Result<Vec<WebhookDelivery>, WebhookError> {
+        let inner = self.inner.lock().unwrap();
+        if inner.hooks.get(webhook_id).is_none_or(|r| r.webhook.squad_id != *squad_id) {
+            return Err(WebhookError::NotFound);
+        }
+        Ok(inner
+            .logs
+            .get(webhook_id)
+            .map(|log| log.iter().rev().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Queue a delivery of `event` to every webhook of the squad that wants
+    /// it; returns how many were queued
+    pub fn emit(
+        &self,
+        squad_id: Uuid,
+        event: WebhookEventKind,
+        data: Value,
+        now: DateTime<Utc>,
+    ) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let targets: Vec<_> = inner
+            .hooks
+            .values()
+            .filter(|r| r.webhook.squad_id == squad_id && r.webhook.wants(event))
+            .cloned()
+            .collect();
+
+        for record in &targets {
+            let payload = WebhookPayload {
+                delivery_id: Uuid::new_v4(),
+                event,
+                squad_id,
+                at: now,
+                data: data.clone(),
+            };
+            let Ok(body) = serde_json::to_string(&payload) else {
+                continue;
+            };
+            let log = inner.logs.entry(record.webhook.webhook_id).or_default();
+            log.push_back(WebhookDelivery {
+                delivery_id: payload.delivery_id,
+                webhook_id: record.webhook.webhook_id,
+                event,
+                created_at: now,
+                state: DeliveryState::Pending,
+                attempts: 0,
+                last_attempt_at: None,
+                last_status: None,
+                last_error: None,
+                next_attempt_at: Some(now),
+            });
+            if log.len() > MAX_DELIVERY_LOG {
+                log.pop_front();
+            }
+            inner.queue.push_back(Job {
+                delivery_id: payload.delivery_id,
+                webhook_id: record.webhook.webhook_id,
+                event,
+                url: record.webhook.url.clone(),
+                secret: record.secret.clone(),
+                body,
+                attempts: 0,
+                due: now,
+            });
+        }
+
+        while inner.queue.len() > MAX_QUEUED {
+            if let Some(job) = inner.queue.pop_front() {
+                warn!(webhook_id = %job.webhook_id, "Webhook queue full; dropping delivery");
+                if let Some(delivery) = inner.delivery_mut(&job.webhook_id, &job.delivery_id) {
+                    delivery.state = DeliveryState::Failed;
+                    delivery.last_error = Some("Delivery queue full".to_string());
+                    delivery.next_attempt_at = None;
+                }
+            }
+        }
+        drop(inner);
+
+        if !targets.is_empty() {
+            self.wake.notify_one();
+        }
+        targets.len()
+    }
+
+    /// Remove and return up to `limit` jobs due by `now`, oldest first
+    pub fn take_due(&self, now: DateTime<Utc>, limit: usize) -> Vec<Job> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut due = Vec::new();
+        let mut waiting = VecDeque::new();
+        for job in std::mem::take(&mut inner.queue) {
+            if job.due <= now && due.len() < limit {
+                due.push(job);
+            } else {
+                waiting.push_back(job);
+            }
+        }
+        inner.queue = waiting;
+        due
+    }
+
+    /// When the next queued job is due
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.inner.lock().unwrap().queue.iter().map(|job| job.due).min()
+    }
+
+    /// Record an attempt: the status the endpoint answered with, or why it
+    /// couldn't be reached. Failed attempts are queued again while retries
+    /// remain.
+    pub fn finish(&self, mut job: Job, outcome: Result<u16, String>, now: DateTime<Utc>) {
+        job.attempts += 1;
+        let delivered = matches!(outcome, Ok(status) if (200..300).contains(&status));
+        let retry_in = RETRY_DELAYS_SECS.get(job.attempts as usize - 1).copied();
+        let next_attempt_at = match (delivered, retry_in) {
+            (false, Some(secs)) => Some(now + Duration::seconds(secs)),
+            _ => None,
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        // The webhook may have been deleted while the request was in flight
+        if !inner.hooks.contains_key(&job.webhook_id) {
+            return;
+        }
+        if let Some(delivery) = inner.delivery_mut(&job.webhook_id, &job.delivery_id) {
+            delivery.attempts = job.attempts;
+            delivery.last_attempt_at = Some(now);
+            delivery.next_attempt_at = next_attempt_at;
+            match &outcome {
+                Ok(status) => {
+                    delivery.last_status = Some(*status);
+                    delivery.last_error = None;
+                }
+                Err(error) => {
+                    delivery.last_status = None;
+                    delivery.last_error = Some(error.chars().take(MAX_ERROR_CHARS).collect());
+                }
+            }
+            delivery.state = if delivered {
+                DeliveryState::Delivered
+            } else if next_attempt_at.is_some() {
+                DeliveryState::Pending
+            } else {
+                DeliveryState::Failed
+            };
+        }
+
+        match next_attempt_at {
+            Some(due) => {
+                debug!(
+                    webhook_id = %job.webhook_id,
+                    attempts = job.attempts,
+                    "Webhook delivery failed; will retry"
+                );
+                inner.queue.push_back(Job { due, ..job });
+                drop(inner);
+                self.wake.notify_one();
+            }
+            None if !delivered => {
+                warn!(
+                    webhook_id = %job.webhook_id,
+                    delivery_id = %job.delivery_id,
+                    "Webhook delivery failed; giving up"
+                );
+            }
+            None => {}
+        }
+    }
+
+    /// Forget webhooks of squads that no longer exist
+    pub fn retain_squads(&self, squad_ids: &HashSet<Uuid>) {
+        self.retain(|squad_id| squad_ids.contains(squad_id));
+    }
+
+    /// Forget a deleted squad's webhooks, their logs and queued deliveries
+    pub fn remove_squad(&self, squad_id: &Uuid) {
+        self.retain(|id| id != squad_id);
+    }
+
+    fn retain(&self, keep: impl Fn(&Uuid) -> bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.hooks.retain(|_, r| keep(&r.webhook.squad_id));
+        let Inner { hooks, logs, queue } = &mut *inner;
+        logs.retain(|id, _| hooks.contains_key(id));
+        queue.retain(|job| hooks.contains_key(&job.webhook_id));
+    }
+
+    /// Every webhook with its secret, for snapshots
+    pub fn snapshot(&self) -> Vec<WebhookRecord> {
+        self.inner.lock().unwrap().hooks.values().cloned().collect()
+    }
+
+    /// Replace every webhook; logs and queued deliveries are dropped
+    pub fn restore(&self, records: Vec<WebhookRecord>) {
+        *self.inner.lock().unwrap() = Inner {
+            hooks: records.into_iter().map(|r| (r.webhook.webhook_id, r)).collect(),
+            ..Default::default()
+        };
+    }
+}
+
+impl Default for WebhookStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `sha256=<hex>` signature of a delivery body sent at `timestamp`
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether webhooks may reach `ip` without being allowlisted: not loopback,
+/// private, link-local, unique-local, shared (CGNAT), multicast or unspecified
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            // 0.0.0.0/8 ("this network") and 100.64.0.0/10 (carrier-grade NAT)
+            let this_network = first == 0;
+            let shared = first == 100 && (second & 0xc0) == 64;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || this_network
+                || shared)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                // fec0::/10, the deprecated site-local range
+                let site_local = (ip.segments()[0] & 0xffc0) == 0xfec0;
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast()
+                    || site_local)
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address reaches: IPv4-mapped, NAT64
+/// (64:ff9b::/96) and 6to4 (2002::/16) addresses are judged by it
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return Some(v4);
+    }
+    let segments = ip.segments();
+    let octets = ip.octets();
+    match segments {
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => {
+            Some(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]))
+        }
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+/// Resolve a webhook URL's host; every address must be public unless the host
+/// is in `allowed_hosts`
+async fn resolve_destination(
+    url: &str,
+    allowed_hosts: &[String],
+) -> Result<Vec<SocketAddr>, String> {
+    let url = reqwest::Url::parse(url).map_err(|_| "URL is not valid".to_string())?;
+    let host = url
+        .host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .ok_or("URL has no host")?;
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+    lookup_public(host, port, allowed_hosts).await
+}
+
+/// Resolve `host`; every address must be public unless the host is in
+/// `allowed_hosts`
+async fn lookup_public(
+    host: &str,
+    port: u16,
+    allowed_hosts: &[String],
+) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<_> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| format!("{} could not be resolved", host))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} could not be resolved", host));
+    }
+    if allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)) {
+        return Ok(addrs);
+    }
+    match addrs.iter().find(|addr| !is_public(addr.ip())) {
+        Some(addr) => Err(format!(
+            "{} resolves to a loopback, private or link-local address ({})",
+            host,
+            addr.ip()
+        )),
+        None => Ok(addrs),
+    }
+}
+
+/// Resolver of the delivery client: a host's addresses are only handed out
+/// once they pass the same check as at registration
+struct CheckedResolver {
+    allowed_hosts: Arc<[String]>,
+}
+
+impl reqwest::dns::Resolve for CheckedResolver {
+    fn resolve(&self, name: hyper_014::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        let allowed_hosts = self.allowed_hosts.clone();
+        Box::pin(async move {
+            // The connector puts the URL's port on the addresses
+            let addrs = lookup_public(name.as_str(), 0, &allowed_hosts).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// The client every delivery is sent with
+fn delivery_client(allowed_hosts: Arc<[String]>) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        // A redirect could point the signed payload anywhere
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .dns_resolver(Arc::new(CheckedResolver { allowed_hosts }))
+        .user_agent(concat!("squadz-webhooks/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// POST one job; `Ok` is the status the endpoint answered with
+async fn send(
+    client: &reqwest::Client,
+    job: &Job,
+    allowed_hosts: &[String],
+) -> Result<u16, String> {
+    // IP literals never reach the client's resolver, so check here too
+    resolve_destination(&job.url, allowed_hosts).await?;
+
+    let timestamp = Utc::now().timestamp();
+    let event = serde_json::to_value(job.event)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+    client
+        .post(&job.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(&job.secret, timestamp, &job.body))
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(EVENT_HEADER, event)
+        .header(DELIVERY_HEADER, job.delivery_id.to_string())
+        .body(job.body.clone())
+        .send()
+        .await
+        .map(|response| response.status().as_u16())
+        .map_err(|e| e.to_string())
+}
+
+/// Send queued jobs as they fall due, at most [`MAX_CONCURRENT_DELIVERIES`]
+/// at a time
+pub fn spawn_dispatcher(webhooks: WebhookStore) -> reqwest::Result<()> {
+    let client = delivery_client(webhooks.allowed_hosts.clone())?;
+    let slots = Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES));
+    tokio::spawn(async move {
+        loop {
+            // Only this loop takes slots, so the one freed here stays free
+            drop(slots.acquire().await.expect("delivery slots are never closed"));
+            for job in webhooks.take_due(Utc::now(), slots.available_permits()) {
+                let slot = slots.clone().try_acquire_owned().expect("slot counted as free");
+                let (webhooks, client) = (webhooks.clone(), client.clone());
+                tokio::spawn(async move {
+                    let outcome = send(&client, &job, &webhooks.allowed_hosts).await;
+                    webhooks.finish(job, outcome, Utc::now());
+                    drop(slot);
+                });
+            }
+            let wait = webhooks
+                .next_due()
+                .map(|due| (due - Utc::now()).to_std().unwrap_or_default())
+                .unwrap_or(STALE_CHECK_INTERVAL);
+            tokio::select! {
+                _ = webhooks.wake.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Turn SOS, alert and staleness events into webhook deliveries
+///
+/// Join and leave events are emitted by their handlers.
+pub fn spawn_forwarder(state: Arc<AppState>) {
+    // Taken before spawning so events published meanwhile are kept
+    let mut feed = state.feed.queue();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STALE_CHECK_INTERVAL);
+        // Members already stale when the server started are not reported
+        let mut stale: Option<HashSet<(Uuid, Uuid)>> = None;
+        loop {
+            tokio::select! {
+                event = feed.recv() => match event {
+                    Some(SquadEvent::Sos(call)) => {
+                        let data = serde_json::to_value(&call).unwrap_or_default();
+                        state.webhooks.emit(call.squad_id, WebhookEventKind::Sos, data, Utc::now());
+                    }
+                    Some(SquadEvent::Alert(alert)) => {
+                        let data = serde_json::to_value(&alert).unwrap_or_default();
+                        let squad_id = alert.squad_id;
+                        state.webhooks.emit(squad_id, WebhookEventKind::Alert, data, Utc::now());
+                    }
+                    Some(_) => {}
+                    None => break,
+                },
+                _ = interval.tick() => {
+                    let now_stale = report_stale(&state, stale.as_ref()).await;
+                    stale = Some(now_stale);
+                }
+            }
+        }
+    });
+}
+
+/// Emit `member_stale` for members whose fix went stale since `previous`;
+/// returns who is stale now
+async fn report_stale(
+    state: &AppState,
+    previous: Option<&HashSet<(Uuid, Uuid)>>,
+) -> HashSet<(Uuid, Uuid)> {
+    let manager = state.squad_manager.read().await;
+    let squads = manager.list_squads();
+    state
+        .webhooks
+        .retain_squads(&squads.iter().map(|squad| squad.squad_id).collect());
+
+    let store = state.location_store.read().await;
+    let now = Utc::now();
+    let mut stale = HashSet::new();
+    for squad in squads {
+        for location in store.get_squad_locations(&squad.squad_id) {
+            if !location.is_stale {
+                continue;
+            }
+            let key = (squad.squad_id, location.member_id);
+            if previous.is_some_and(|previous| !previous.contains(&key)) {
+                let data = json!({
+                    "member_id": location.member_id,
+                    "display_name": location.display_name,
+                    "fix_time": location.fix_time,
+                    "location": location.location,
+                });
+                state.webhooks.emit(squad.squad_id, WebhookEventKind::MemberStale, data, now);
+            }
+            stale.insert(key);
+        }
+    }
+    stale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_with_hook(events: Vec<WebhookEventKind>) -> (WebhookStore, Uuid, Webhook) {
+        let store = WebhookStore::new();
+        let squad_id = Uuid::new_v4();
+        let (hook, secret) = store
+            .create(&AuditContext::system(), squad_id, "http://127.0.0.1:9/hook".into(), events)
+            .unwrap();
+        assert!(secret.starts_with("whsec_"));
+        (store, squad_id, hook)
+    }
+
+    #[test]
+    fn test_signature() {
+        let signature = sign("secret", 1_700_000_000, r#"{"a":1}"#);
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(br#"1700000000.{"a":1}"#);
+        mac.verify_slice(&hex::decode(signature.strip_prefix("sha256=").unwrap()).unwrap())
+            .unwrap();
+        assert_ne!(signature, sign("other", 1_700_000_000, r#"{"a":1}"#));
+    }
+
+    #[test]
+    fn test_events_filter_and_limit() {
+        let (store, squad_id, _) = store_with_hook(vec![WebhookEventKind::Sos]);
+        let now = Utc::now();
+        assert_eq!(store.emit(squad_id, WebhookEventKind::MemberJoined, Value::Null, now), 0);
+        assert_eq!(store.emit(squad_id, WebhookEventKind::Sos, Value::Null, now), 1);
+        assert_eq!(store.emit(Uuid::new_v4(), WebhookEventKind::Sos, Value::Null, now), 0);
+
+        for _ in 1..MAX_WEBHOOKS_PER_SQUAD {
+            store
+                .create(&AuditContext::system(), squad_id, "http://x/".into(), vec![])
+                .unwrap();
+        }
+        assert!(matches!(
+            store.create(&AuditContext::system(), squad_id, "http://x/".into(), vec![]),
+            Err(WebhookError::TooMany)
+        ));
+    }
+
+    #[test]
+    fn test_failed_delivery_is_retried_then_given_up() {
+        let (store, squad_id, hook) = store_with_hook(vec![]);
+        let mut now = Utc::now();
+        store.emit(squad_id, WebhookEventKind::MemberLeft, json!({"member_id": 1}), now);
+
+        let job = store.take_due(now, usize::MAX).pop().unwrap();
+        let payload: Value = serde_json::from_str(&job.body).unwrap();
+        assert_eq!(payload["event"], "member_left");
+        assert_eq!(payload["delivery_id"], job.delivery_id.to_string());
+        store.finish(job, Ok(503), now);
+
+        // Not due again until the first backoff has passed
+        assert!(store.take_due(now, usize::MAX).is_empty());
+        for (attempt, delay) in RETRY_DELAYS_SECS.iter().enumerate() {
+            now += Duration::seconds(*delay);
+            let job = store.take_due(now, usize::MAX).pop().expect("retry queued");
+            assert_eq!(job.attempts as usize, attempt + 1);
+            store.finish(job, Err("connection refused".into()), now);
+        }
+        assert!(store.next_due().is_none());
+
+        let log = store.deliveries(&squad_id, &hook.webhook_id).unwrap();
+        assert_eq!(log[0].state, DeliveryState::Failed);
+        assert_eq!(log[0].attempts as usize, RETRY_DELAYS_SECS.len() + 1);
+        assert_eq!(log[0].last_error.as_deref(), Some("connection refused"));
+    }
+
+    #[test]
+    fn test_delivered_and_deleted() {
+        let (store, squad_id, hook) = store_with_hook(vec![]);
+        let now = Utc::now();
+        store.emit(squad_id, WebhookEventKind::Sos, Value::Null, now);
+        store.emit(squad_id, WebhookEventKind::Alert, Value::Null, now);
+
+        let mut jobs = store.take_due(now, usize::MAX);
+        store.finish(jobs.remove(0), Ok(204), now);
+        let log = store.deliveries(&squad_id, &hook.webhook_id).unwrap();
+        assert_eq!(log[1].state, DeliveryState::Delivered);
+        assert_eq!(log[1].last_status, Some(204));
+        assert_eq!(log[0].state, DeliveryState::Pending);
+
+        assert!(matches!(
+            store.delete(&AuditContext::system(), &Uuid::new_v4(), &hook.webhook_id),
+            Err(WebhookError::NotFound)
+        ));
+        store.delete(&AuditContext::system(), &squad_id, &hook.webhook_id).unwrap();
+        // An attempt finishing after the delete doesn't resurrect anything
+        store.finish(jobs.remove(0), Ok(500), now);
+        assert!(store.next_due().is_none());
+        assert!(store.list(&squad_id).is_empty());
+    }
+
+    #[test]
+    fn test_public_addresses() {
+        for ip in ["93.184.215.14", "2606:4700::1111", "::ffff:8.8.8.8", "64:ff9b::808:808"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "0.1.2.3",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+            "2002:7f00:1::",
+            "fec0::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_destination_checked_at_registration() {
+        let store = WebhookStore::new();
+        for url in ["http://127.0.0.1:8080/hook", "https://[fd12::1]/", "http://localhost/"] {
+            assert!(
+                matches!(store.check_destination(url).await, Err(WebhookError::Destination(_))),
+                "{url}"
+            );
+        }
+        store.check_destination("https://93.184.215.14/hook").await.unwrap();
+
+        let store = WebhookStore::new().with_allowed_hosts(vec!["localhost".to_string()]);
+        store.check_destination("http://localhost:9/hook").await.unwrap();
+        assert!(store.check_destination("http://127.0.0.1:9/hook").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_destination_checked_on_send() {
+        // A registered URL whose host now resolves to a private address
+        let (store, squad_id, _) = store_with_hook(vec![]);
+        store.emit(squad_id, WebhookEventKind::Sos, Value::Null, Utc::now());
+        let job = store.take_due(Utc::now(), usize::MAX).pop().unwrap();
+        let client = delivery_client(store.allowed_hosts.clone()).unwrap();
+        let error = send(&client, &job, &store.allowed_hosts).await.unwrap_err();
+        assert!(error.contains("private"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_resolver_hands_out_public_addresses_only() {
+        use reqwest::dns::Resolve;
+
+        let resolver = CheckedResolver { allowed_hosts: Arc::new([]) };
+        let name = "localhost".parse().unwrap();
+        let error = resolver.resolve(name).await.err().unwrap();
+        assert!(error.to_string().contains("loopback"), "{error}");
+
+        let resolver = CheckedResolver { allowed_hosts: Arc::new(["localhost".to_string()]) };
+        let addrs: Vec<_> = resolver.resolve("localhost".parse().unwrap()).await.unwrap().collect();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+    }
+
+    #[test]
+    fn test_due_jobs_taken_up_to_limit() {
+        let (store, squad_id, _) = store_with_hook(vec![]);
+        let now = Utc::now();
+        for _ in 0..3 {
+            store.emit(squad_id, WebhookEventKind::Sos, Value::Null, now);
+        }
+        assert_eq!(store.take_due(now, 2).len(), 2);
+        assert_eq!(store.take_due(now, 2).len(), 1);
+        assert!(store.next_due().is_none());
+    }
+}
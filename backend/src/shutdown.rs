@@ -0,0 +1,71 @@
+//! Graceful shutdown
+//!
+//! SIGTERM or Ctrl-C stops accepting connections and lets in-flight requests
+//! finish, up to a grace period, before the state snapshot is written.
+
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::info;
+
+/// Broadcasts the shutdown signal to every listener
+#[derive(Clone)]
+pub struct Shutdown {
+    rx: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Start listening for SIGTERM and Ctrl-C
+    pub fn listen() -> Self {
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move {
+            signal().await;
+            let _ = tx.send(true);
+        });
+        Self { rx }
+    }
+
+    /// A signal that never fires, for tests
+    #[cfg(test)]
+    pub fn never() -> Self {
+        let (tx, rx) = watch::channel(false);
+        // Dropping the sender would read as a shutdown
+        std::mem::forget(tx);
+        Self { rx }
+    }
+
+    /// Resolves once shutdown has been requested
+    pub async fn requested(mut self) {
+        // An error means the sender is gone, which only happens after sending
+        let _ = self.rx.wait_for(|requested| *requested).await;
+    }
+
+    /// Resolves `grace` after shutdown has been requested
+    pub async fn deadline(self, grace: Duration) {
+        self.requested().await;
+        tokio::time::sleep(grace).await;
+    }
+}
+
+async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C, shutting down"),
+        _ = terminate => info!("Received SIGTERM, shutting down"),
+    }
+}
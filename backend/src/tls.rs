@@ -0,0 +1,131 @@
+//! Native HTTPS termination
+//!
+//! Serves the app over rustls when a certificate and key are configured,
+//! reloading them whenever either file changes on disk, and optionally runs a
+//! plain-HTTP listener that redirects every request to HTTPS.
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use axum::{
+    extract::Host,
+    http::Uri,
+    response::Redirect,
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::{error, info, warn};
+
+use crate::shutdown::Shutdown;
+
+/// Load the certificate chain and private key into a rustls config
+pub async fn load(cert_path: &str, key_path: &str) -> anyhow::Result<RustlsConfig> {
+    // Both ring and aws-lc end up in the dependency tree, so rustls cannot
+    // pick a provider on its own. Installing twice is harmless.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to load TLS certificate {}: {}", cert_path, e))
+}
+
+/// Poll the certificate and key files and hot-reload them when they change
+///
+/// A failed reload (e.g. a half-written file) keeps serving the previous
+/// certificate and retries on the next tick.
+pub fn spawn_reloader(config: RustlsConfig, cert_path: String, key_path: String, interval: Duration) {
+    tokio::spawn(async move {
+        let cert = PathBuf::from(cert_path);
+        let key = PathBuf::from(key_path);
+        let mut last_seen = (modified(&cert), modified(&key));
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            let current = (modified(&cert), modified(&key));
+            if current == last_seen {
+                continue;
+            }
+
+            match config.reload_from_pem_file(&cert, &key).await {
+                Ok(()) => {
+                    info!("Reloaded TLS certificate from {}", cert.display());
+                    last_seen = current;
+                }
+                Err(e) => warn!("TLS certificate reload failed, keeping the old one: {}", e),
+            }
+        }
+    });
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Serve a plain-HTTP listener that redirects everything to HTTPS on `https_port`
+pub fn spawn_redirect(
+    listener: TcpListener,
+    https_port: u16,
+    shutdown: Shutdown,
+) -> std::io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let app = Router::new().fallback(move |Host(host): Host, uri: Uri| async move {
+        Redirect::permanent(&https_location(&host, &uri, https_port))
+    });
+
+    let handle = axum_server::Handle::new();
+    let stop = handle.clone();
+    tokio::spawn(async move {
+        shutdown.requested().await;
+        stop.shutdown();
+    });
+
+    tokio::spawn(async move {
+        if let Err(e) = axum_server::from_tcp(listener)
+            .handle(handle)
+            .serve(app.into_make_service())
+            .await
+        {
+            error!("HTTP redirect listener failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// HTTPS URL for the same host and path, with the port swapped
+fn https_location(host: &str, uri: &Uri, https_port: u16) -> String {
+    let host = host.rsplit_once(':').map_or(host, |(name, port)| {
+        // Keep IPv6 literals like [::1] intact
+        if port.chars().all(|c| c.is_ascii_digit()) {
+            name
+        } else {
+            host
+        }
+    });
+    let authority = if https_port == 443 {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, https_port)
+    };
+    let path = uri.path_and_query().map_or("/", |pq| pq.as_str());
+
+    format!("https://{}{}", authority, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_https_location() {
+        let uri: Uri = "/api/v1/squads?limit=5".parse().unwrap();
+        assert_eq!(
+            https_location("squadz.local:8081", &uri, 8443),
+            "https://squadz.local:8443/api/v1/squads?limit=5"
+        );
+        assert_eq!(https_location("squadz.local", &uri, 443), "https://squadz.local/api/v1/squads?limit=5");
+        assert_eq!(https_location("[::1]:80", &"/".parse().unwrap(), 443), "https://[::1]/");
+    }
+}
@@ -0,0 +1,470 @@
+//! Request validation
+//!
+//! Every request model implements [`Validate`], which normalizes fields in
+//! place (NFC and whitespace for names, headings into `[0, 360)`) and collects
+//! every problem it finds rather than stopping at the first. Handlers receive
+//! models through [`crate::api::extract::ValidJson`], which turns the problems
+//! into a single 422 `validation_failed` error listing each field.
+
+use std::ops::RangeInclusive;
+
+use chrono::Utc;
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+use utoipa::ToSchema;
+
+use crate::models::{
+    AlertSettings, CreateSquadRequest, CreateWebhookRequest, GeoPoint, JoinSquadRequest,
+    LocationBatchRequest, LocationFix, PlausibilitySettings, QuarantineReviewRequest,
+    SendMessageRequest, RouteRequest, SetPassphraseRequest, SmoothingSettings, SosRequest,
+    SquadSettings, WaypointRequest,
+};
+use crate::services::location_store::MAX_QUARANTINED_POINTS;
+use crate::services::{plausibility, routes};
+
+pub const SQUAD_NAME_MAX_CHARS: usize = 64;
+pub const DISPLAY_NAME_MAX_CHARS: usize = 32;
+pub const PASSPHRASE_MAX_CHARS: usize = 128;
+pub const JOIN_CODE_MAX_CHARS: usize = 16;
+pub const SOS_MESSAGE_MAX_CHARS: usize = 280;
+pub const WEBHOOK_URL_MAX_CHARS: usize = 2048;
+pub const MESSAGE_MAX_CHARS: usize = 1000;
+pub const WAYPOINT_NAME_MAX_CHARS: usize = 64;
+pub const WAYPOINT_DESCRIPTION_MAX_CHARS: usize = 500;
+pub const ROUTE_NAME_MAX_CHARS: usize = 64;
+
+const LATITUDE: RangeInclusive<f64> = -90.0..=90.0;
+const LONGITUDE: RangeInclusive<f64> = -180.0..=180.0;
+/// Dead Sea shore to well above airliner altitude, in metres
+const ALTITUDE_M: RangeInclusive<f64> = -1_000.0..=100_000.0;
+const ACCURACY_M: RangeInclusive<f64> = 0.0..=100_000.0;
+const SPEED_MPS: RangeInclusive<f64> = 0.0..=1_000.0;
+const UPDATE_INTERVAL_SECS: RangeInclusive<u32> = 1..=3600;
+const ACCURACY_THRESHOLD_M: RangeInclusive<f64> = 1.0..=10_000.0;
+const ALERT_DISTANCE_M: RangeInclusive<f64> = 1.0..=1_000_000.0;
+/// Up to a day
+const STATIONARY_MINS: RangeInclusive<u32> = 1..=1440;
+/// Up to the speed treated as a teleport regardless of settings
+const SPEED_LIMIT_MPS: RangeInclusive<f64> = 0.5..=plausibility::TELEPORT_SPEED_MPS;
+const ROUTE_DEVIATION_M: RangeInclusive<f64> = 1.0..=10_000.0;
+
+/// One invalid field of a request
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Path to the field, e.g. `fixes[2].lat`
+    pub field: String,
+    pub message: String,
+}
+
+/// Every problem found in a request
+#[derive(Debug, thiserror::Error)]
+#[error("{}", summary(.0))]
+pub struct ValidationError(pub Vec<FieldError>);
+
+fn summary(errors: &[FieldError]) -> String {
+    match errors {
+        [] => "Invalid request".to_string(),
+        [only] => format!("{}: {}", only.field, only.message),
+        [first, rest @ ..] => format!(
+            "{}: {} (and {} more)",
+            first.field,
+            first.message,
+            rest.len()
+        ),
+    }
+}
+
+/// Collects field errors while walking a request
+#[derive(Debug, Default)]
+pub struct Validator {
+    path: Vec<String>,
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Run `check` with `name` appended to the current field path
+    pub fn field<R>(&mut self, name: impl Into<String>, check: impl FnOnce(&mut Self) -> R) -> R {
+        self.path.push(name.into());
+        let result = check(self);
+        self.path.pop();
+        result
+    }
+
+    /// Record a problem with the current field
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: self.path.join("."),
+            message: message.into(),
+        });
+    }
+}
+
+/// A request model that can normalize and check itself
+pub trait Validate {
+    fn validate(&mut self, v: &mut Validator);
+}
+
+/// Normalize `value` in place; `Err` lists every invalid field
+pub fn validate<T: Validate>(value: &mut T) -> Result<(), ValidationError> {
+    let mut v = Validator::default();
+    value.validate(&mut v);
+    if v.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError(v.errors))
+    }
+}
+
+/// NFC-normalize, trim, and collapse whitespace runs to a single space
+pub fn normalize_name(name: &str) -> String {
+    name.nfc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalize a user-visible name and check its length and characters
+fn name(v: &mut Validator, field: &str, value: &mut String, max_chars: usize) {
+    *value = normalize_name(value);
+    v.field(field, |v| {
+        let chars = value.chars().count();
+        if chars == 0 {
+            v.error("must not be empty");
+        } else if chars > max_chars {
+            v.error(format!("must be at most {} characters", max_chars));
+        }
+        if value.chars().any(|c| c.is_control() || is_bidi_control(c)) {
+            v.error("must not contain control characters");
+        }
+    });
+}
+
+/// Characters that reorder the text around them, used to spoof names
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Normalize optional free text, keeping line breaks; blank text becomes `None`
+fn text(v: &mut Validator, field: &str, value: &mut Option<String>, max_chars: usize) {
+    let Some(text) = value else {
+        return;
+    };
+    let normalized = text.nfc().collect::<String>().trim().to_string();
+    if normalized.is_empty() {
+        *value = None;
+        return;
+    }
+    v.field(field, |v| {
+        if normalized.chars().count() > max_chars {
+            v.error(format!("must be at most {} characters", max_chars));
+        }
+        if normalized
+            .chars()
+            .any(|c| (c.is_control() && c != '\n') || is_bidi_control(c))
+        {
+            v.error("must not contain control characters");
+        }
+    });
+    *text = normalized;
+}
+
+fn passphrase(v: &mut Validator, value: &Option<String>) {
+    // Not normalized: that would change what existing hashes accept
+    if let Some(passphrase) = value {
+        if passphrase.chars().count() > PASSPHRASE_MAX_CHARS {
+            v.field("passphrase", |v| {
+                v.error(format!("must be at most {} characters", PASSPHRASE_MAX_CHARS))
+            });
+        }
+    }
+}
+
+fn number(v: &mut Validator, field: &str, value: f64, range: RangeInclusive<f64>) {
+    v.field(field, |v| {
+        if !value.is_finite() {
+            v.error("must be a finite number");
+        } else if !range.contains(&value) {
+            v.error(format!("must be between {} and {}", range.start(), range.end()));
+        }
+    });
+}
+
+fn optional_number(v: &mut Validator, field: &str, value: Option<f64>, range: RangeInclusive<f64>) {
+    if let Some(value) = value {
+        number(v, field, value, range);
+    }
+}
+
+/// Wrap a heading into `[0, 360)`
+fn heading(v: &mut Validator, field: &str, value: &mut Option<f64>) {
+    if let Some(heading) = value {
+        if heading.is_finite() {
+            *heading = heading.rem_euclid(360.0);
+        } else {
+            v.field(field, |v| v.error("must be a finite number"));
+        }
+    }
+}
+
+impl Validate for GeoPoint {
+    fn validate(&mut self, v: &mut Validator) {
+        number(v, "latitude", self.latitude, LATITUDE);
+        number(v, "longitude", self.longitude, LONGITUDE);
+        optional_number(v, "altitude", self.altitude, ALTITUDE_M);
+        optional_number(v, "accuracy", self.accuracy, ACCURACY_M);
+        heading(v, "heading", &mut self.heading);
+        optional_number(v, "speed", self.speed, SPEED_MPS);
+    }
+}
+
+impl Validate for LocationFix {
+    fn validate(&mut self, v: &mut Validator) {
+        number(v, "lat", self.lat, LATITUDE);
+        number(v, "lon", self.lon, LONGITUDE);
+        optional_number(v, "altitude_m", self.altitude_m, ALTITUDE_M);
+        optional_number(v, "accuracy_m", self.accuracy_m, ACCURACY_M);
+        heading(v, "heading_deg", &mut self.heading_deg);
+        optional_number(v, "speed_mps", self.speed_mps, SPEED_MPS);
+    }
+}
+
+impl Validate for LocationBatchRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        for (index, fix) in self.fixes.iter_mut().enumerate() {
+            v.field(format!("fixes[{}]", index), |v| fix.validate(v));
+        }
+    }
+}
+
+impl Validate for QuarantineReviewRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        if let Some(times) = &self.recorded_at {
+            if !(1..=MAX_QUARANTINED_POINTS).contains(&times.len()) {
+                v.field("recorded_at", |v| {
+                    v.error(format!("must list 1 to {} fix times", MAX_QUARANTINED_POINTS))
+                });
+            }
+        }
+    }
+}
+
+impl Validate for SquadSettings {
+    fn validate(&mut self, v: &mut Validator) {
+        if !UPDATE_INTERVAL_SECS.contains(&self.location_update_interval_secs) {
+            v.field("location_update_interval_secs", |v| {
+                v.error(format!(
+                    "must be between {} and {}",
+                    UPDATE_INTERVAL_SECS.start(),
+                    UPDATE_INTERVAL_SECS.end()
+                ))
+            });
+        }
+        v.field("plausibility", |v| self.plausibility.validate(v));
+        v.field("smoothing", |v| self.smoothing.validate(v));
+        v.field("alerts", |v| self.alerts.validate(v));
+    }
+}
+
+impl Validate for AlertSettings {
+    fn validate(&mut self, v: &mut Validator) {
+        let (centroid, leader) = (self.max_distance_from_centroid_m, self.max_distance_from_leader_m);
+        optional_number(v, "max_distance_from_centroid_m", centroid, ALERT_DISTANCE_M);
+        optional_number(v, "max_distance_from_leader_m", leader, ALERT_DISTANCE_M);
+        if let Some(mins) = self.stationary_after_mins {
+            if !STATIONARY_MINS.contains(&mins) {
+                v.field("stationary_after_mins", |v| {
+                    v.error(format!(
+                        "must be between {} and {}",
+                        STATIONARY_MINS.start(),
+                        STATIONARY_MINS.end()
+                    ))
+                });
+            }
+        }
+        number(v, "stationary_radius_m", self.stationary_radius_m, ACCURACY_THRESHOLD_M);
+    }
+}
+
+impl Validate for SmoothingSettings {
+    fn validate(&mut self, v: &mut Validator) {
+        optional_number(v, "max_accuracy_m", self.max_accuracy_m, ACCURACY_THRESHOLD_M);
+    }
+}
+
+impl Validate for PlausibilitySettings {
+    fn validate(&mut self, v: &mut Validator) {
+        number(v, "max_speed_mps", self.max_speed_mps, SPEED_LIMIT_MPS);
+        number(v, "max_vertical_speed_mps", self.max_vertical_speed_mps, SPEED_LIMIT_MPS);
+    }
+}
+
+impl Validate for CreateSquadRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        name(v, "name", &mut self.name, SQUAD_NAME_MAX_CHARS);
+        name(v, "leader_name", &mut self.leader_name, DISPLAY_NAME_MAX_CHARS);
+        if let Some(settings) = &mut self.settings {
+            v.field("settings", |v| settings.validate(v));
+        }
+        passphrase(v, &self.passphrase);
+    }
+}
+
+impl Validate for JoinSquadRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        // Codes are issued in upper case; accept them typed any way
+        self.join_code = self.join_code.trim().to_ascii_uppercase();
+        v.field("join_code", |v| {
+            let code = &self.join_code;
+            if code.is_empty() || code.len() > JOIN_CODE_MAX_CHARS {
+                v.error(format!("must be 1 to {} characters", JOIN_CODE_MAX_CHARS));
+            } else if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+                v.error("must contain only letters and digits");
+            }
+        });
+        name(v, "display_name", &mut self.display_name, DISPLAY_NAME_MAX_CHARS);
+        passphrase(v, &self.passphrase);
+    }
+}
+
+impl Validate for SetPassphraseRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        passphrase(v, &self.passphrase);
+    }
+}
+
+impl Validate for SosRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        v.field("location", |v| self.location.validate(v));
+        text(v, "message", &mut self.message, SOS_MESSAGE_MAX_CHARS);
+    }
+}
+
+impl Validate for SendMessageRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        let mut message = Some(std::mem::take(&mut self.text));
+        text(v, "text", &mut message, MESSAGE_MAX_CHARS);
+        match message {
+            Some(message) => self.text = message,
+            None => v.field("text", |v| v.error("must not be empty")),
+        }
+        if let Some(location) = &mut self.location {
+            v.field("location", |v| location.validate(v));
+        }
+    }
+}
+
+impl Validate for WaypointRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        name(v, "name", &mut self.name, WAYPOINT_NAME_MAX_CHARS);
+        v.field("location", |v| self.location.validate(v));
+        text(v, "description", &mut self.description, WAYPOINT_DESCRIPTION_MAX_CHARS);
+        if self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            v.field("expires_at", |v| v.error("must be in the future"));
+        }
+    }
+}
+
+impl Validate for RouteRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        name(v, "name", &mut self.name, ROUTE_NAME_MAX_CHARS);
+        if !(2..=routes::MAX_ROUTE_POINTS).contains(&self.points.len()) {
+            v.field("points", |v| {
+                v.error(format!("must have 2 to {} points", routes::MAX_ROUTE_POINTS))
+            });
+        }
+        for (index, point) in self.points.iter_mut().enumerate() {
+            v.field(format!("points[{}]", index), |v| point.validate(v));
+        }
+        number(v, "max_deviation_m", self.max_deviation_m, ROUTE_DEVIATION_M);
+    }
+}
+
+impl Validate for CreateWebhookRequest {
+    fn validate(&mut self, v: &mut Validator) {
+        self.url = self.url.trim().to_string();
+        v.field("url", |v| {
+            if self.url.chars().count() > WEBHOOK_URL_MAX_CHARS {
+                v.error(format!("must be at most {} characters", WEBHOOK_URL_MAX_CHARS));
+                return;
+            }
+            match reqwest::Url::parse(&self.url) {
+                Ok(url) if !matches!(url.scheme(), "http" | "https") => {
+                    v.error("must be an http or https URL")
+                }
+                Ok(url) if !url.username().is_empty() || url.password().is_some() => {
+                    v.error("must not contain credentials")
+                }
+                Ok(_) => {}
+                Err(_) => v.error("must be an absolute URL"),
+            }
+        });
+        let mut seen = Vec::new();
+        self.events.retain(|event| {
+            let first = !seen.contains(event);
+            seen.push(*event);
+            first
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: f64, heading: Option<f64>) -> GeoPoint {
+        GeoPoint {
+            accuracy: Some(-5.0),
+            heading,
+            ..GeoPoint::at(latitude, 0.0)
+        }
+    }
+
+    #[test]
+    fn test_geo_point_errors_are_collected() {
+        let mut p = point(200.0, Some(f64::NAN));
+        let errors = validate(&mut p).unwrap_err().0;
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["latitude", "accuracy", "heading"]);
+
+        let mut p = GeoPoint {
+            accuracy: None,
+            ..point(45.0, Some(720.0 + 90.0))
+        };
+        validate(&mut p).unwrap();
+        assert_eq!(p.heading, Some(90.0));
+        p.heading = Some(-90.0);
+        validate(&mut p).unwrap();
+        assert_eq!(p.heading, Some(270.0));
+    }
+
+    #[test]
+    fn test_names_are_normalized() {
+        // "e" + combining acute, padded and with a double space
+        let mut req = JoinSquadRequest {
+            join_code: " abc234 ".to_string(),
+            display_name: "  Rene\u{301}   Smith ".to_string(),
+            passphrase: None,
+        };
+        validate(&mut req).unwrap();
+        assert_eq!(req.display_name, "Ren\u{e9} Smith");
+        assert_eq!(req.join_code, "ABC234");
+
+        req.display_name = "\u{202E}nimda".to_string();
+        req.join_code = "ab-12".to_string();
+        let errors = validate(&mut req).unwrap_err();
+        assert_eq!(errors.0.len(), 2);
+        assert_eq!(errors.to_string(), "join_code: must contain only letters and digits (and 1 more)");
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let mut batch = LocationBatchRequest {
+            fixes: vec![point(0.0, None).into(), point(0.0, None).into()],
+        };
+        batch.fixes[1].lon = f64::INFINITY;
+        let errors = validate(&mut batch).unwrap_err().0;
+        assert_eq!(errors[0].field, "fixes[0].accuracy_m");
+        assert_eq!(errors[1].field, "fixes[1].lon");
+    }
+}